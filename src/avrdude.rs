@@ -0,0 +1,449 @@
+//! Finding the avrdude executable and detecting its version
+
+use std::{
+    env, fmt, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use eframe::egui;
+
+use crate::board::BoardSpec;
+
+/// The environment variable with the path of the avrdude executable.
+pub const PATH_VARIABLE: &str = "AVRDUDE_PATH";
+
+/// The environment variable with the path of the avrdude.conf.
+pub const CONF_VARIABLE: &str = "AVRDUDE_CONF";
+
+/// The file name of the avrdude executable.
+const FILE_NAME: &str = if cfg!(windows) {
+    "avrdude.exe"
+} else {
+    "avrdude"
+};
+
+/// The first version of avrdude which supports serialupdi.
+pub const UPDI_VERSION: Version = Version { major: 7, minor: 0 };
+
+/// The version of avrdude, without the patch level or build suffix
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct Version {
+    /// The major version.
+    pub major: u32,
+    /// The minor version.
+    pub minor: u32,
+}
+
+impl Version {
+    /// Parse a version like "7.1", "6.3-20171130" or "8.0-rc1".
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.split('.');
+        let number = |part: &str| {
+            let end = part
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(part.len());
+            part[..end].parse().ok()
+        };
+        Some(Self {
+            major: number(parts.next()?)?,
+            minor: parts.next().and_then(number).unwrap_or(0),
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Where the avrdude executable was found
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Source {
+    /// The path configured by the user
+    Setting,
+    /// An environment variable
+    Environment,
+    /// Next to this program, for portable installations
+    Bundled,
+    /// The PATH environment variable
+    Path,
+}
+
+impl Source {
+    /// The name shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Setting => "configured",
+            Self::Environment => "from the environment",
+            Self::Bundled => "bundled",
+            Self::Path => "system, from PATH",
+        }
+    }
+}
+
+/// What running the executable revealed
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Detection {
+    /// The executable runs and reported its version
+    Version(String),
+    /// The executable runs but did not report a version, it may not be avrdude
+    UnknownVersion,
+    /// The executable could not be run
+    Unavailable(Unavailable),
+}
+
+/// Why the executable could not be run
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Unavailable {
+    /// The executable does not exist
+    NotFound,
+    /// The user may not run the executable
+    PermissionDenied,
+    /// Running the executable failed for another reason
+    Failed(String),
+}
+
+impl Unavailable {
+    /// The message shown to the user, including how to fix the problem.
+    pub fn message(&self, executable: &Path) -> String {
+        match self {
+            Self::NotFound => format!(
+                "Error: avrdude is not installed or could not be found. {}",
+                install_guidance()
+            ),
+            Self::PermissionDenied => format!(
+                "Error: {} may not be run, check that it is executable{}",
+                executable.display(),
+                if cfg!(unix) { " (chmod +x)" } else { "" }
+            ),
+            Self::Failed(e) => format!("Error: could not run {}: {}", executable.display(), e),
+        }
+    }
+}
+
+/// How avrdude is installed on the current operating system.
+pub fn install_guidance() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "Install it with Homebrew: brew install avrdude"
+    } else if cfg!(windows) {
+        "Install the Arduino IDE and choose its avrdude.exe below, it is in \
+        %LOCALAPPDATA%\\Arduino15\\packages\\arduino\\tools\\avrdude\\<version>\\bin"
+    } else {
+        "Install the avrdude package of your distribution: sudo apt install avrdude, \
+        sudo dnf install avrdude or sudo pacman -S avrdude"
+    }
+}
+
+/// The avrdude executable which is used
+pub struct Avrdude {
+    /// The executable the commands are run with, only the name if it is not in PATH either.
+    pub executable: PathBuf,
+    /// Where the executable was found.
+    pub source: Source,
+    /// The avrdude.conf from `AVRDUDE_CONF` or shipped with a bundled executable and where it was
+    /// found, it is passed unless the user chose one.
+    pub conf: Option<(PathBuf, Source)>,
+    /// The path of the executable in `AVRDUDE_PATH`, if it is set.
+    pub env_path: Option<PathBuf>,
+    /// Why configured paths are not used, like a configured executable which does not exist.
+    pub warnings: Vec<String>,
+    /// What running the executable revealed, `None` while it is detected.
+    pub detection: Option<Detection>,
+    /// Receives the detection once the executable ran.
+    pending: Option<Receiver<Detection>>,
+}
+
+impl Avrdude {
+    /// Find the executable, the configured one is used if it exists. Otherwise the one in
+    /// `AVRDUDE_PATH`, then one bundled next to this program and PATH last. The version is
+    /// detected on a background thread and the context is repainted when it is known.
+    pub fn find(configured: Option<&Path>, ctx: &egui::Context) -> Self {
+        let mut warnings = Vec::new();
+        let mut existing = |path: Option<PathBuf>, name: &str| {
+            let path = path?;
+            if path.is_file() {
+                return Some(path);
+            }
+            warnings.push(format!(
+                "The {} {} does not exist, it is ignored",
+                name,
+                path.display()
+            ));
+            None
+        };
+        let setting = existing(configured.map(Path::to_owned), "configured avrdude");
+        let env_path = env::var_os(PATH_VARIABLE).map(PathBuf::from);
+        let from_env = existing(env_path.clone(), PATH_VARIABLE);
+        let env_conf = existing(env::var_os(CONF_VARIABLE).map(PathBuf::from), CONF_VARIABLE)
+            .map(|conf| (conf, Source::Environment));
+
+        let (executable, source, bundled_conf) = match (setting, from_env) {
+            (Some(path), _) => (path, Source::Setting, None),
+            (None, Some(path)) => (path, Source::Environment, None),
+            (None, None) => match find_bundled() {
+                Some((executable, conf)) => (executable, Source::Bundled, conf),
+                None => {
+                    let found = find_in_path().unwrap_or_else(|| PathBuf::from(FILE_NAME));
+                    (found, Source::Path, None)
+                }
+            },
+        };
+        let conf = env_conf.or(bundled_conf.map(|conf| (conf, Source::Bundled)));
+
+        let (sender, pending) = mpsc::channel();
+        let (detected, ctx) = (executable.clone(), ctx.clone());
+        thread::spawn(move || {
+            // The receiver is dropped when the executable changed in the meantime.
+            let _ = sender.send(detect(&detected));
+            ctx.request_repaint();
+        });
+
+        Self {
+            executable,
+            source,
+            conf,
+            env_path,
+            warnings,
+            detection: None,
+            pending: Some(pending),
+        }
+    }
+
+    /// Receive the detection if the executable ran.
+    pub fn poll(&mut self) {
+        if let Some(detection) = self.pending.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.detection = Some(detection);
+            self.pending = None;
+        }
+    }
+
+    /// The parsed version, `None` if it is unknown or still detected.
+    pub fn version(&self) -> Option<Version> {
+        match self.detection {
+            Some(Detection::Version(ref version)) => Version::parse(version),
+            _ => None,
+        }
+    }
+
+    /// Wether UPDI chips can be programmed, which needs serialupdi. An unknown version is assumed
+    /// to be too old, everything else still works with it.
+    pub fn supports_updi(&self) -> bool {
+        self.version()
+            .is_some_and(|version| version >= UPDI_VERSION)
+    }
+
+    /// Why the given spec can't be used with this avrdude, `None` if it can.
+    pub fn unsupported(&self, spec: &BoardSpec) -> Option<String> {
+        if !spec.updi || self.supports_updi() {
+            return None;
+        }
+        Some(match self.version() {
+            Some(version) => format!(
+                "UPDI chips need avrdude {} or newer, the installed avrdude is {}",
+                UPDI_VERSION, version
+            ),
+            None => format!(
+                "UPDI chips need avrdude {} or newer, the version of the installed avrdude is \
+                unknown",
+                UPDI_VERSION
+            ),
+        })
+    }
+
+    /// Why avrdude can't be used, `None` if it can be used or the detection is still running.
+    pub fn unavailable(&self) -> Option<String> {
+        match self.detection {
+            Some(Detection::Unavailable(ref unavailable)) => {
+                Some(unavailable.message(&self.executable))
+            }
+            _ => None,
+        }
+    }
+
+    /// Run the executable again right away, it may have been installed or removed since it was
+    /// detected.
+    pub fn check(&mut self) -> Result<(), String> {
+        self.detection = Some(detect(&self.executable));
+        self.pending = None;
+        self.unavailable().map_or(Ok(()), Err)
+    }
+}
+
+/// The avrdude.conf next to the given executable, like the one bundled with the Arduino IDE in
+/// `tools/avrdude/<version>/etc`.
+pub fn sibling_conf(executable: &Path) -> Option<PathBuf> {
+    let conf = executable
+        .parent()?
+        .parent()?
+        .join("etc")
+        .join("avrdude.conf");
+    conf.is_file().then_some(conf)
+}
+
+/// The avrdude executable shipped next to this program or in its tools directory, with the
+/// avrdude.conf next to it if there is one.
+fn find_bundled() -> Option<(PathBuf, Option<PathBuf>)> {
+    let exe = env::current_exe().ok()?;
+    let dir = exe.parent()?;
+    [dir.to_owned(), dir.join("tools")]
+        .into_iter()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+        .map(|executable| {
+            let next_to = executable.with_file_name("avrdude.conf");
+            let conf = if next_to.is_file() {
+                Some(next_to)
+            } else {
+                sibling_conf(&executable)
+            };
+            (executable, conf)
+        })
+}
+
+/// The avrdude executable in the directories of PATH.
+fn find_in_path() -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Run the given executable to read its version from the usage it prints.
+fn detect(executable: &Path) -> Detection {
+    let out = match Command::new(executable)
+        .arg("-?")
+        .stdin(Stdio::null())
+        .output()
+    {
+        Ok(out) => out,
+        Err(e) => {
+            return Detection::Unavailable(match e.kind() {
+                io::ErrorKind::NotFound => Unavailable::NotFound,
+                io::ErrorKind::PermissionDenied => Unavailable::PermissionDenied,
+                _ => Unavailable::Failed(e.to_string()),
+            })
+        }
+    };
+    // The usage ends with a line like "avrdude version 7.1, https://github.com/avrdudes/avrdude".
+    let usage = String::from_utf8_lossy(&out.stderr) + String::from_utf8_lossy(&out.stdout);
+    usage
+        .lines()
+        .find_map(|line| {
+            let start = line.find("version ")? + "version ".len();
+            let version = line[start..].split([',', ' ']).next()?;
+            Some(version.to_owned()).filter(|version| !version.is_empty())
+        })
+        .map_or(Detection::UnknownVersion, Detection::Version)
+}
+
+/// Check an extended programmer parameter passed as `-x key=value`, an empty value passes only the
+/// key.
+pub fn check_extended_param(key: &str, value: &str) -> Result<(), &'static str> {
+    if key.is_empty() {
+        Err("the key is empty")
+    } else if key.contains(|c: char| c.is_whitespace() || c == '=') {
+        Err("the key may not contain spaces or =")
+    } else if value.contains(char::is_whitespace) {
+        Err("the value may not contain spaces")
+    } else {
+        Ok(())
+    }
+}
+
+/// Split the arguments entered by the user like a shell does. Quotes keep spaces in arguments and a
+/// backslash escapes quotes, spaces and itself, other backslashes are kept for Windows paths.
+pub fn split_args(s: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some((_, end)) if end == c => break,
+                        Some((_, '\\')) if c == '"' => match chars.peek() {
+                            Some(&(_, escaped @ ('"' | '\\'))) => {
+                                arg.push(escaped);
+                                chars.next();
+                            }
+                            _ => arg.push('\\'),
+                        },
+                        Some((_, other)) => arg.push(other),
+                        None => {
+                            return Err(format!(
+                                "Error: the quote at character {} is not closed",
+                                i + 1
+                            ))
+                        }
+                    }
+                }
+            }
+            '\\' => {
+                let arg = current.get_or_insert_with(String::new);
+                match chars.peek() {
+                    Some(&(_, escaped @ ('"' | '\'' | '\\' | ' '))) => {
+                        arg.push(escaped);
+                        chars.next();
+                    }
+                    _ => arg.push('\\'),
+                }
+            }
+            c if c.is_whitespace() => args.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The arguments split from the given string.
+    fn split(s: &str) -> Vec<String> {
+        split_args(s).unwrap()
+    }
+
+    #[test]
+    fn whitespace_separates_arguments() {
+        assert_eq!(split("  -B 10\t-V  "), ["-B", "10", "-V"]);
+        assert!(split("").is_empty());
+    }
+
+    #[test]
+    fn quotes_keep_spaces() {
+        assert_eq!(
+            split(r#"-C "my conf.conf" -x 'a b'"#),
+            ["-C", "my conf.conf", "-x", "a b"]
+        );
+        assert_eq!(split(r#"-x"a b"c"#), ["-xa bc"]);
+        assert_eq!(split(r#""" ''"#), ["", ""]);
+    }
+
+    #[test]
+    fn backslashes() {
+        assert_eq!(split(r"my\ file \\ \'"), ["my file", r"\", "'"]);
+        assert_eq!(split(r#""a \"b\" \\ \n""#), [r#"a "b" \ \n"#]);
+        assert_eq!(split(r"'a\b \'"), [r"a\b \"]);
+        // Windows paths keep their separators.
+        assert_eq!(
+            split(r"-C C:\avr\avrdude.conf"),
+            ["-C", r"C:\avr\avrdude.conf"]
+        );
+    }
+
+    #[test]
+    fn unclosed_quotes() {
+        assert_eq!(
+            split_args(r#"-x "abc"#),
+            Err("Error: the quote at character 4 is not closed".to_owned())
+        );
+        assert!(split_args("'abc").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! The copies of the flash read before a board is flashed
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    file_info::{file_time, format_time},
+    firmware::{self, FirmwareFormat},
+    group_digits,
+};
+
+/// The number of backups kept per port if none is configured.
+pub const DEFAULT_KEPT: usize = 10;
+
+/// The warning shown when backups are read through a bootloader.
+pub const SLOW_WARNING: &str = "Reading the whole flash through a bootloader is slow, it adds \
+    about 10 s for an Uno and a minute for a Mega to each flash.";
+
+/// The warning shown when the bootloader would leave after reading the backup.
+pub const SINGLE_SESSION_WARNING: &str = "The bootloader of this board only serves a single run, \
+    flashing is refused while the backup is turned on.";
+
+/// A backup offered to be restored
+#[derive(Debug, Clone)]
+pub struct Backup {
+    /// The file of the backup.
+    pub path: PathBuf,
+    /// When the backup was taken, `None` if the file system does not know it.
+    pub modified: Option<SystemTime>,
+    /// The number of bytes of the flash in the backup, an error if it can't be read.
+    pub size: Result<u64, String>,
+}
+
+impl Backup {
+    /// Read the time and the size of the backup in the given file.
+    pub fn read(path: PathBuf) -> Self {
+        let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+        let size = end_address(&path);
+        Self {
+            path,
+            modified,
+            size,
+        }
+    }
+
+    /// A line like "2023-04-01 13:37:00 UTC, 32 256 bytes" shown in the list of backups.
+    pub fn describe(&self) -> String {
+        let time = self.modified.map(format_time).unwrap_or_default();
+        match self.size {
+            Ok(size) => format!("{}, {} bytes", time, group_digits(size)),
+            Err(ref e) => format!("{}, {}", time, e),
+        }
+    }
+}
+
+/// The address after the last byte of the given dump, which has to fit into the flash to restore
+/// it. avrdude leaves out the erased bytes at the end of a dump.
+pub fn end_address(path: &Path) -> Result<u64, String> {
+    let blocks = firmware::flash_image(path, FirmwareFormat::IntelHex)?;
+    Ok(blocks
+        .iter()
+        .map(|block| block.end())
+        .max()
+        .unwrap_or_default())
+}
+
+/// The directory the backups are saved in if none is configured, `None` if there is no data
+/// directory.
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("arduino_installer_gui").join("backups"))
+}
+
+/// The path of a new backup of the board on the given port in the given directory, like
+/// "ttyUSB0_2023-04-01_13-37-00.hex".
+pub fn new_path(dir: &Path, port: &str, time: SystemTime) -> PathBuf {
+    dir.join(format!("{}_{}.hex", port_prefix(port), file_time(time)))
+}
+
+/// The backups of the board on the given port in the given directory, the newest one first.
+pub fn list(dir: &Path, port: &str) -> io::Result<Vec<PathBuf>> {
+    let prefix = format!("{}_", port_prefix(port));
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with(&prefix) && name.ends_with(".hex")
+        })
+        .collect();
+    // The time in the name sorts like the times do.
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+/// Remove the oldest backups of the board on the port of the given backup until the given number
+/// of backups is left.
+pub fn prune(backup: &Path, port: &str, keep: usize) -> io::Result<()> {
+    let Some(dir) = backup.parent() else {
+        return Ok(());
+    };
+    for old in list(dir, port)?.iter().skip(keep.max(1)) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// The given port as part of a file name, like "ttyUSB0" for "/dev/ttyUSB0", the backups and logs
+/// are named after it. Characters which can't be part of a file name are replaced, underscores
+/// separate the time.
+pub fn port_prefix(port: &str) -> String {
+    let name = Path::new(port)
+        .file_name()
+        .map_or_else(|| port.into(), |name| name.to_string_lossy());
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
@@ -0,0 +1,165 @@
+//! Flashing the same file to several ports, one after another or at the same time
+
+use std::collections::VecDeque;
+
+use serialport::SerialPortInfo;
+
+use crate::{progress::Progress, run_log::RunInfo, upload::RunningUpload, HistoryEntry};
+
+/// The largest number of ports flashed at the same time.
+pub const MAX_CONCURRENCY: usize = 16;
+
+/// A port of the batch which is flashed right now
+pub struct Worker {
+    /// The name of the port.
+    pub port: String,
+    /// The running flash.
+    pub upload: RunningUpload,
+    /// What is written to the log of the flash, `None` if no log is written.
+    pub info: Option<RunInfo>,
+    /// The entry added to the history once the flash finished.
+    pub entry: HistoryEntry,
+}
+
+/// How the flash of a port of the batch ended
+pub struct PortResult {
+    /// The name of the port.
+    pub port: String,
+    /// `Ok` if the port was flashed, the reason why not otherwise.
+    pub result: Result<(), String>,
+    /// The output of the flash, empty if it did not start.
+    pub output: String,
+}
+
+/// The state of a port of the batch
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PortState<'a> {
+    /// The port waits for a free worker
+    Queued,
+    /// The flash is written, with the progress so far
+    Flashing(f32),
+    /// The flash is read back and compared, with the progress so far
+    Verifying(f32),
+    /// The port was flashed
+    Done,
+    /// The port was not flashed, with the reason
+    Failed(&'a str),
+}
+
+/// The ports of a batch flash and how each of them ended
+pub struct Batch {
+    /// The ports which are still to be flashed, the next one first.
+    queue: VecDeque<SerialPortInfo>,
+    /// The ports which are flashed right now.
+    pub workers: Vec<Worker>,
+    /// The finished ports in the order they finished.
+    pub results: Vec<PortResult>,
+    /// The number of ports in the batch.
+    pub total: usize,
+    /// The number of ports flashed at the same time, 1 to flash them one after another.
+    concurrency: usize,
+    /// Wether the remaining ports are skipped after the first failure.
+    stop_on_failure: bool,
+    /// The port whose output is shown, `None` to show none.
+    pub shown: Option<String>,
+}
+
+impl Batch {
+    /// Flash the given ports with the given number of them at the same time.
+    pub fn new(ports: Vec<SerialPortInfo>, concurrency: usize, stop_on_failure: bool) -> Self {
+        Self {
+            total: ports.len(),
+            queue: ports.into(),
+            workers: Vec::new(),
+            results: Vec::new(),
+            concurrency: concurrency.clamp(1, MAX_CONCURRENCY),
+            stop_on_failure,
+            shown: None,
+        }
+    }
+
+    /// Wether all ports were flashed or skipped.
+    pub fn is_done(&self) -> bool {
+        self.queue.is_empty() && self.workers.is_empty()
+    }
+
+    /// The next port to flash, `None` if all workers are busy or no port is left. The caller
+    /// adds the worker flashing the port or reports that it could not be started.
+    pub fn next(&mut self) -> Option<SerialPortInfo> {
+        if self.workers.len() >= self.concurrency {
+            return None;
+        }
+        self.queue.pop_front()
+    }
+
+    /// The names of the ports which wait for a free worker.
+    fn queued(&self) -> impl Iterator<Item = &str> {
+        self.queue.iter().map(|port| port.port_name.as_str())
+    }
+
+    /// The ports of the batch with their states, the finished ones first.
+    pub fn states(&self) -> Vec<(&str, PortState<'_>)> {
+        let finished = self.results.iter().map(|res| {
+            let state = match res.result {
+                Ok(()) => PortState::Done,
+                Err(ref e) => PortState::Failed(e),
+            };
+            (res.port.as_str(), state)
+        });
+        let running = self.workers.iter().map(|worker| {
+            let state = match Progress::parse(&worker.upload.log.text()) {
+                Some(progress) if progress.phase == "Verifying" => {
+                    PortState::Verifying(progress.fraction)
+                }
+                Some(progress) => PortState::Flashing(progress.fraction),
+                None => PortState::Flashing(0.0),
+            };
+            (worker.port.as_str(), state)
+        });
+        let queued = self.queued().map(|port| (port, PortState::Queued));
+        finished.chain(running).chain(queued).collect()
+    }
+
+    /// The output of the given port so far, `None` if it was not started yet.
+    pub fn output(&self, port: &str) -> Option<String> {
+        let running = self.workers.iter().find(|worker| worker.port == port);
+        match running {
+            Some(worker) => Some(worker.upload.log.text()),
+            None => self
+                .results
+                .iter()
+                .find(|res| res.port == port)
+                .map(|res| res.output.clone()),
+        }
+    }
+
+    /// Remember how the flash of the given port ended, the remaining ports are skipped after a
+    /// failure if the batch stops on the first one.
+    pub fn finished(&mut self, port: String, result: Result<(), String>, output: String) {
+        let failed = result.is_err();
+        self.results.push(PortResult {
+            port,
+            result,
+            output,
+        });
+        if failed && self.stop_on_failure {
+            self.skip_remaining("skipped after the failure");
+        }
+    }
+
+    /// Skip the ports which were not flashed yet with the given reason.
+    pub fn skip_remaining(&mut self, reason: &str) {
+        for port in self.queue.drain(..) {
+            self.results.push(PortResult {
+                port: port.port_name,
+                result: Err(reason.to_owned()),
+                output: String::new(),
+            });
+        }
+    }
+
+    /// The number of ports which were flashed successfully.
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|res| res.result.is_ok()).count()
+    }
+}
@@ -0,0 +1,1302 @@
+//! The supported boards and how programs are installed to them
+
+use std::{borrow::Cow, ffi::OsStr, fmt, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serialport::{SerialPortInfo, SerialPortType};
+
+use crate::{
+    bootloader::{
+        Bootloader, ATMEGABOOT_328, ATMEGABOOT_328_8MHZ, CATERINA_LEONARDO, CATERINA_MICRO,
+        OPTIBOOT_328, STK500V2_2560,
+    },
+    custom_boards::CustomBoard,
+    elf::{EM_ARM, EM_AVR, EM_XTENSA},
+    fuses::{
+        FusePreset, ATMEGABOOT_328_PRESETS, ATTINY85_PRESETS, CATERINA_PRESETS, MEGA_2560_PRESETS,
+        OPTIBOOT_328_PRESETS,
+    },
+    touch,
+    upload::ResetPulse,
+};
+
+/// The usb ids of the CH340 usb to serial converter used on many clones.
+const CH340: (u16, u16) = (0x1a86, 0x7523);
+/// The usb ids of the FTDI FT232R usb to serial converter.
+const FT232R: (u16, u16) = (0x0403, 0x6001);
+/// The usb ids of the CP2102 usb to serial converter.
+const CP2102: (u16, u16) = (0x10c4, 0xea60);
+
+/// The usb ids of the Arduino Uno and its clones.
+const UNO_IDS: &[(u16, u16)] = &[
+    (0x2341, 0x0043),
+    (0x2341, 0x0001),
+    (0x2a03, 0x0043),
+    (0x2341, 0x0243),
+    CH340,
+];
+/// The usb ids of the Arduino Nano and its clones.
+const NANO_IDS: &[(u16, u16)] = &[FT232R, CH340];
+/// The usb ids of the Arduino Mega 2560 and its clones.
+const MEGA_IDS: &[(u16, u16)] = &[
+    (0x2341, 0x0010),
+    (0x2341, 0x0042),
+    (0x2a03, 0x0042),
+    (0x2341, 0x0242),
+    CH340,
+];
+/// The usb ids of the Arduino Leonardo, in bootloader and sketch mode.
+const LEONARDO_IDS: &[(u16, u16)] = &[
+    (0x2341, 0x0036),
+    (0x2341, 0x8036),
+    (0x2a03, 0x0036),
+    (0x2a03, 0x8036),
+];
+/// The usb ids of the Arduino Micro and the SparkFun Pro Micro.
+const MICRO_IDS: &[(u16, u16)] = &[
+    (0x2341, 0x0037),
+    (0x2341, 0x8037),
+    (0x1b4f, 0x9205),
+    (0x1b4f, 0x9206),
+];
+/// The usb ids of common usb to serial adapters used for boards without usb.
+const USB_SERIAL_IDS: &[(u16, u16)] = &[FT232R, CH340, CP2102, (0x067b, 0x2303)];
+/// The usb ids of the programming and native port of the Arduino Due.
+const DUE_IDS: &[(u16, u16)] = &[(0x2341, 0x003d), (0x2341, 0x003e)];
+/// The usb ids of the Arduino Zero, including its debug port.
+const ZERO_IDS: &[(u16, u16)] = &[(0x2341, 0x804d), (0x2341, 0x004d), (0x03eb, 0x2157)];
+/// The usb ids of the Arduino MKR 1000, MKR Zero and MKR WiFi 1010.
+const MKR_IDS: &[(u16, u16)] = &[
+    (0x2341, 0x804e),
+    (0x2341, 0x004e),
+    (0x2341, 0x804f),
+    (0x2341, 0x004f),
+    (0x2341, 0x8054),
+    (0x2341, 0x0054),
+];
+/// The usb ids of the usb to serial converters on ESP dev kits and native usb ESP32s.
+const ESP_IDS: &[(u16, u16)] = &[CP2102, CH340, (0x1a86, 0x55d4), (0x303a, 0x1001)];
+
+/// The board selected by the user
+#[derive(Debug, PartialEq, Clone)]
+pub enum SelectedBoard {
+    /// One of the built in boards
+    Builtin(ArduinoBoard),
+    /// A board loaded from the custom board definitions
+    Custom(CustomBoard),
+}
+
+impl Default for SelectedBoard {
+    fn default() -> Self {
+        Self::Builtin(ArduinoBoard::default())
+    }
+}
+
+impl SelectedBoard {
+    /// The specification required to install a program to the board.
+    pub fn spec(&self) -> BoardSpec {
+        match self {
+            Self::Builtin(board) => board.spec(),
+            Self::Custom(board) => board.spec(),
+        }
+    }
+
+    /// The name shown to the user.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Builtin(board) => board.label(),
+            Self::Custom(board) => &board.name,
+        }
+    }
+
+    /// The category the board is listed in.
+    pub fn category(&self) -> BoardCategory {
+        match self {
+            Self::Builtin(board) => board.category(),
+            Self::Custom(board) => board.category,
+        }
+    }
+}
+
+/// The categories boards are grouped in
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoardCategory {
+    /// Boards with an AVR chip
+    #[default]
+    Avr,
+    /// Boards with an Atmel SAM or SAMD chip
+    Samd,
+    /// Boards with an Espressif chip
+    Esp,
+    /// All other boards
+    Other,
+}
+
+impl BoardCategory {
+    /// All categories in the order they are shown.
+    pub const ALL: [Self; 4] = [Self::Avr, Self::Samd, Self::Esp, Self::Other];
+
+    /// The name shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Avr => "AVR (Uno, Nano, Mega...)",
+            Self::Samd => "SAMD (Zero, MKR, Due)",
+            Self::Esp => "ESP (ESP8266, ESP32)",
+            Self::Other => "Other",
+        }
+    }
+}
+
+/// Enumeration of all supported Arduino boards
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum ArduinoBoard {
+    /// The Arduino Uno
+    #[default]
+    ArduinoUno,
+    /// The Arduino Nano with the new optiboot bootloader
+    ArduinoNano,
+    /// The Arduino Nano with the old bootloader, common on clones
+    ArduinoNanoOldBootloader,
+    /// The Arduino Mega 2560
+    ArduinoMega2560,
+    /// The Arduino Leonardo
+    ArduinoLeonardo,
+    /// The Arduino Micro, also used for the Pro Micro
+    ArduinoMicro,
+    /// The Arduino Pro Mini running at 5V and 16 MHz
+    ProMini5V16MHz,
+    /// The Arduino Pro Mini running at 3.3V and 8 MHz
+    ProMini3V8MHz,
+    /// The Arduino Nano Every
+    ArduinoNanoEvery,
+    /// A bare tinyAVR or AVR-Dx chip programmed through a SerialUPDI adapter
+    SerialUpdi,
+    /// A bare ATtiny85 programmed through an ISP programmer
+    ATtiny85,
+    /// The Digispark ATtiny85 board with the micronucleus bootloader
+    Digispark,
+    /// The Arduino Due
+    ArduinoDue,
+    /// The Arduino Zero connected through its native usb port
+    ArduinoZero,
+    /// The Arduino MKR family
+    ArduinoMkr,
+    /// ESP8266 boards like the NodeMCU
+    Esp8266,
+    /// ESP32 dev kits
+    Esp32,
+    /// The Teensy 3.2
+    Teensy32,
+    /// The Teensy 4.0
+    Teensy40,
+    /// The Teensy 4.1
+    Teensy41,
+    /// The STM32F103 Blue Pill
+    BluePill,
+    /// The Raspberry Pi Pico
+    RaspberryPiPico,
+    /// The Arduino Uno R4 Minima
+    UnoR4Minima,
+    /// The Arduino Uno R4 WiFi
+    UnoR4Wifi,
+}
+
+impl fmt::Display for ArduinoBoard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+impl ArduinoBoard {
+    /// All built in boards.
+    pub const ALL: [Self; 24] = [
+        Self::ArduinoUno,
+        Self::ArduinoNano,
+        Self::ArduinoNanoOldBootloader,
+        Self::ArduinoMega2560,
+        Self::ArduinoLeonardo,
+        Self::ArduinoMicro,
+        Self::ProMini5V16MHz,
+        Self::ProMini3V8MHz,
+        Self::ArduinoNanoEvery,
+        Self::SerialUpdi,
+        Self::ATtiny85,
+        Self::Digispark,
+        Self::ArduinoDue,
+        Self::ArduinoZero,
+        Self::ArduinoMkr,
+        Self::Esp8266,
+        Self::Esp32,
+        Self::Teensy32,
+        Self::Teensy40,
+        Self::Teensy41,
+        Self::BluePill,
+        Self::RaspberryPiPico,
+        Self::UnoR4Minima,
+        Self::UnoR4Wifi,
+    ];
+
+    /// The name shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::ArduinoUno => "Arduino Uno",
+            Self::ArduinoNano => "Arduino Nano",
+            Self::ArduinoNanoOldBootloader => "Arduino Nano (old bootloader)",
+            Self::ArduinoMega2560 => "Arduino Mega 2560",
+            Self::ArduinoLeonardo => "Arduino Leonardo",
+            Self::ArduinoMicro => "Arduino Micro / Pro Micro",
+            Self::ProMini5V16MHz => "Arduino Pro Mini (5V, 16 MHz)",
+            Self::ProMini3V8MHz => "Arduino Pro Mini (3.3V, 8 MHz)",
+            Self::ArduinoNanoEvery => "Arduino Nano Every",
+            Self::SerialUpdi => "SerialUPDI target (tinyAVR, AVR-Dx)",
+            Self::ATtiny85 => "ATtiny85 (ISP)",
+            Self::Digispark => "Digispark (micronucleus)",
+            Self::ArduinoDue => "Arduino Due",
+            Self::ArduinoZero => "Arduino Zero (native USB port)",
+            Self::ArduinoMkr => "Arduino MKR (WiFi 1010, Zero, 1000...)",
+            Self::Esp8266 => "ESP8266 (NodeMCU)",
+            Self::Esp32 => "ESP32",
+            Self::Teensy32 => "Teensy 3.2",
+            Self::Teensy40 => "Teensy 4.0",
+            Self::Teensy41 => "Teensy 4.1",
+            Self::BluePill => "STM32 Blue Pill",
+            Self::RaspberryPiPico => "Raspberry Pi Pico",
+            Self::UnoR4Minima => "Arduino Uno R4 Minima",
+            Self::UnoR4Wifi => "Arduino Uno R4 WiFi",
+        }
+    }
+
+    /// The category the board is listed in.
+    pub fn category(self) -> BoardCategory {
+        match self {
+            Self::ArduinoUno
+            | Self::ArduinoNano
+            | Self::ArduinoNanoOldBootloader
+            | Self::ArduinoMega2560
+            | Self::ArduinoLeonardo
+            | Self::ArduinoMicro
+            | Self::ProMini5V16MHz
+            | Self::ProMini3V8MHz
+            | Self::ArduinoNanoEvery
+            | Self::SerialUpdi
+            | Self::ATtiny85
+            | Self::Digispark => BoardCategory::Avr,
+            Self::ArduinoDue | Self::ArduinoZero | Self::ArduinoMkr => BoardCategory::Samd,
+            Self::Esp8266 | Self::Esp32 => BoardCategory::Esp,
+            Self::Teensy32
+            | Self::Teensy40
+            | Self::Teensy41
+            | Self::BluePill
+            | Self::RaspberryPiPico
+            | Self::UnoR4Minima
+            | Self::UnoR4Wifi => BoardCategory::Other,
+        }
+    }
+
+    /// The board the given port belongs to, if its usb ids are unique to one board.
+    ///
+    /// Ports of generic usb to serial converters are never detected.
+    pub fn detect(port: &SerialPortInfo) -> Option<Self> {
+        let SerialPortType::UsbPort(ref usb) = port.port_type else {
+            return None;
+        };
+        let id = (usb.vid, usb.pid);
+        if USB_SERIAL_IDS.contains(&id) || ESP_IDS.contains(&id) {
+            return None;
+        }
+
+        let mut matching = Self::ALL
+            .into_iter()
+            .filter(|board| board.spec().usb_ids.contains(&id));
+        match (matching.next(), matching.next()) {
+            (Some(board), None) => Some(board),
+            _ => None,
+        }
+    }
+
+    /// The specification required to install a program to the board.
+    pub fn spec(self) -> BoardSpec {
+        match self {
+            Self::ArduinoUno => BoardSpec {
+                usb_ids: Cow::Borrowed(UNO_IDS),
+                bootloader: Some(OPTIBOOT_328),
+                fuse_presets: OPTIBOOT_328_PRESETS,
+                bootloader_size: 512,
+                ..BoardSpec::avrdude("arduino", "atmega328p", Some(115_200))
+            },
+            Self::ArduinoNano => BoardSpec {
+                usb_ids: Cow::Borrowed(NANO_IDS),
+                bootloader: Some(OPTIBOOT_328),
+                fuse_presets: OPTIBOOT_328_PRESETS,
+                bootloader_size: 512,
+                fallback_baud: Some(57_600),
+                ..BoardSpec::avrdude("arduino", "atmega328p", Some(115_200))
+            },
+            Self::ArduinoNanoOldBootloader => BoardSpec {
+                usb_ids: Cow::Borrowed(NANO_IDS),
+                bootloader: Some(ATMEGABOOT_328),
+                fuse_presets: ATMEGABOOT_328_PRESETS,
+                bootloader_size: 2048,
+                fallback_baud: Some(115_200),
+                ..BoardSpec::avrdude("arduino", "atmega328p", Some(57_600))
+            },
+            // Chip erase over the wiring protocol is slow, the stk500v2 bootloader erases pages
+            // while writing anyway.
+            Self::ArduinoMega2560 => BoardSpec {
+                erase: EraseMode::NoErase,
+                usb_ids: Cow::Borrowed(MEGA_IDS),
+                bootloader: Some(STK500V2_2560),
+                fuse_presets: MEGA_2560_PRESETS,
+                bootloader_size: 8192,
+                ..BoardSpec::avrdude("wiring", "atmega2560", Some(115_200))
+            },
+            Self::ArduinoLeonardo => BoardSpec {
+                touch_1200bps: true,
+                usb_ids: Cow::Borrowed(LEONARDO_IDS),
+                bootloader: Some(CATERINA_LEONARDO),
+                fuse_presets: CATERINA_PRESETS,
+                bootloader_size: 4096,
+                ..BoardSpec::avrdude("avr109", "atmega32u4", Some(57_600))
+            },
+            Self::ArduinoMicro => BoardSpec {
+                touch_1200bps: true,
+                usb_ids: Cow::Borrowed(MICRO_IDS),
+                bootloader: Some(CATERINA_MICRO),
+                fuse_presets: CATERINA_PRESETS,
+                bootloader_size: 4096,
+                ..BoardSpec::avrdude("avr109", "atmega32u4", Some(57_600))
+            },
+            // Only newer 5V Pro Minis ship with optiboot at 115200, the old bootloader is far more
+            // common on them.
+            Self::ProMini5V16MHz => BoardSpec {
+                usb_ids: Cow::Borrowed(USB_SERIAL_IDS),
+                bootloader: Some(ATMEGABOOT_328),
+                fuse_presets: ATMEGABOOT_328_PRESETS,
+                bootloader_size: 2048,
+                ..BoardSpec::avrdude("arduino", "atmega328p", Some(57_600))
+            },
+            Self::ProMini3V8MHz => BoardSpec {
+                usb_ids: Cow::Borrowed(USB_SERIAL_IDS),
+                clock_hz: 8_000_000,
+                bootloader: Some(ATMEGABOOT_328_8MHZ),
+                fuse_presets: ATMEGABOOT_328_PRESETS,
+                bootloader_size: 2048,
+                ..BoardSpec::avrdude("arduino", "atmega328p", Some(57_600))
+            },
+            // The onboard SAMD11 runs jtag2updi and resets into it when touched at 1200 baud.
+            Self::ArduinoNanoEvery => BoardSpec {
+                touch_1200bps: true,
+                updi: true,
+                usb_ids: Cow::Borrowed(&[(0x2341, 0x0058)]),
+                ..BoardSpec::avrdude("jtag2updi", "atmega4809", Some(115_200))
+            },
+            // The partno is picked by the user from the UPDI parts.
+            Self::SerialUpdi => BoardSpec {
+                updi: true,
+                pick_partno: true,
+                clock_hz: 0,
+                ..BoardSpec::avrdude("serialupdi", "", Some(230_400))
+            },
+            // The usbasp is used unless another external programmer is selected.
+            Self::ATtiny85 => BoardSpec {
+                over_isp: true,
+                // Fresh chips run from the internal oscillator divided by 8.
+                clock_hz: 1_000_000,
+                fuse_presets: ATTINY85_PRESETS,
+                ..BoardSpec::avrdude("usbasp", "attiny85", None)
+            },
+            Self::Digispark => BoardSpec {
+                uploader: Uploader::Micronucleus,
+                clock_hz: 16_500_000,
+                bootloader_size: 2180,
+                ..BoardSpec::avrdude("", "attiny85", None)
+            },
+            Self::ArduinoDue => BoardSpec {
+                uploader: Uploader::Bossac,
+                touch_1200bps: true,
+                usb_ids: Cow::Borrowed(DUE_IDS),
+                clock_hz: 84_000_000,
+                ..BoardSpec::avrdude("", "at91sam3x8e", None)
+            },
+            // Both keep an 8 KB bootloader at the start of the flash.
+            Self::ArduinoZero => BoardSpec {
+                uploader: Uploader::Bossac,
+                touch_1200bps: true,
+                flash_offset: Some(0x2000),
+                usb_ids: Cow::Borrowed(ZERO_IDS),
+                clock_hz: 48_000_000,
+                bootloader_size: 8192,
+                ..BoardSpec::avrdude("", "atsamd21g18", None)
+            },
+            Self::ArduinoMkr => BoardSpec {
+                uploader: Uploader::Bossac,
+                touch_1200bps: true,
+                flash_offset: Some(0x2000),
+                usb_ids: Cow::Borrowed(MKR_IDS),
+                clock_hz: 48_000_000,
+                bootloader_size: 8192,
+                ..BoardSpec::avrdude("", "atsamd21g18", None)
+            },
+            Self::Esp8266 => BoardSpec {
+                uploader: Uploader::Esptool,
+                flash_offset: Some(0x0),
+                usb_ids: Cow::Borrowed(ESP_IDS),
+                clock_hz: 80_000_000,
+                ota_port: 8266,
+                ..BoardSpec::avrdude("", "esp8266", Some(460_800))
+            },
+            // The bootloader and partition table live in front of the application image.
+            Self::Esp32 => BoardSpec {
+                uploader: Uploader::Esptool,
+                flash_offset: Some(0x10000),
+                usb_ids: Cow::Borrowed(ESP_IDS),
+                clock_hz: 240_000_000,
+                ota_port: 3232,
+                ..BoardSpec::avrdude("", "esp32", Some(460_800))
+            },
+            // teensy_loader_cli identifies the boards by name, the 4.0 and 4.1 share their chip.
+            Self::Teensy32 => BoardSpec::teensy("TEENSY32", 72_000_000),
+            Self::Teensy40 => BoardSpec::teensy("TEENSY40", 600_000_000),
+            Self::Teensy41 => BoardSpec::teensy("TEENSY41", 600_000_000),
+            // The maple bootloader writes to flash through alternate setting 2.
+            Self::BluePill => BoardSpec {
+                uploader: Uploader::Stm32flash,
+                dfu_alt: 2,
+                usb_ids: Cow::Borrowed(USB_SERIAL_IDS),
+                clock_hz: 72_000_000,
+                ..BoardSpec::avrdude("", "stm32f103c8", None)
+            },
+            Self::RaspberryPiPico => BoardSpec {
+                uploader: Uploader::Picotool,
+                clock_hz: 125_000_000,
+                ..BoardSpec::avrdude("", "rp2040", None)
+            },
+            // The Minima enters its dfu bootloader with a new product id, while the WiFi is
+            // flashed through the bossa compatible bootloader of its usb bridge.
+            Self::UnoR4Minima => BoardSpec {
+                uploader: Uploader::DfuUtil,
+                touch_1200bps: true,
+                dfu_device: Some("0x2341:0x0069,:0x0369"),
+                usb_ids: Cow::Borrowed(&[(0x2341, 0x0069)]),
+                clock_hz: 48_000_000,
+                ..BoardSpec::avrdude("", "r7fa4m1ab", None)
+            },
+            Self::UnoR4Wifi => BoardSpec {
+                uploader: Uploader::Bossac,
+                touch_1200bps: true,
+                usb_ids: Cow::Borrowed(&[(0x2341, 0x1002)]),
+                clock_hz: 48_000_000,
+                ..BoardSpec::avrdude("", "r7fa4m1ab", None)
+            },
+        }
+    }
+}
+
+/// A specification used to install a program to board (passed to avrdude).
+#[derive(Debug, Clone)]
+pub struct BoardSpec {
+    /// The program used to install a program to the board.
+    pub uploader: Uploader,
+    /// The name of the onboard programmer.
+    pub programmer: Cow<'static, str>,
+    /// The name of the chip the program should be installed to.
+    pub partno: Cow<'static, str>,
+    /// The baud rate used to talk to the bootloader, avrdudes default is used if `None`.
+    pub baud: Option<u32>,
+    /// The baud rate tried once when the bootloader does not sync at the baud rate, `None` if a
+    /// sync failure has other causes.
+    pub fallback_baud: Option<u32>,
+    /// How the flash is erased before installing.
+    pub erase: EraseMode,
+    /// Wether the board has to be reset right before flashing to enter its bootloader.
+    pub needs_reset: bool,
+    /// Wether the board has no serial bootloader and is flashed through an ISP programmer.
+    pub over_isp: bool,
+    /// Wether the chip is programmed over UPDI, ISP programmers can't be used for it.
+    pub updi: bool,
+    /// Wether the user has to pick the partno from [`UPDI_PARTS`].
+    pub pick_partno: bool,
+    /// The external programmer used instead of the bootloader, `None` to use the bootloader.
+    pub isp: Option<ProgrammerSpec>,
+    /// Wether the port has to be opened at 1200 baud before flashing to enter the bootloader.
+    pub touch_1200bps: bool,
+    /// How long is waited for the bootloader port to appear after the 1200 baud touch.
+    pub touch_timeout: Duration,
+    /// The flash address the program is written to, `None` if the uploader decides on its own.
+    pub flash_offset: Option<u32>,
+    /// The usb ids dfu-util uses to find the board, the first dfu device is used if `None`.
+    pub dfu_device: Option<&'static str>,
+    /// The dfu alternate setting which writes the program to flash.
+    pub dfu_alt: u8,
+    /// The default port of the over the air update service, 0 if the board has none.
+    pub ota_port: u16,
+    /// The clock speed the chip runs with in Hz, 0 if unknown.
+    pub clock_hz: u32,
+    /// The bootloader which can be burned through an ISP programmer, `None` if unknown.
+    pub bootloader: Option<Bootloader>,
+    /// The number of bytes of the flash occupied by the bootloader.
+    pub bootloader_size: u32,
+    /// The fuse values which are safe to write to the chip of the board.
+    pub fuse_presets: &'static [FusePreset],
+    /// The usb vendor and product ids of the serial port of the board, empty if unknown.
+    pub usb_ids: Cow<'static, [(u16, u16)]>,
+    /// Additional arguments passed to avrdude after the standard ones, each one is passed as a
+    /// separate argument so values containing spaces are kept intact.
+    pub extra_args: Vec<Cow<'static, str>>,
+    /// The avrdude executable the commands are run with, `None` to run the one in PATH.
+    pub avrdude_path: Option<PathBuf>,
+    /// The avrdude.conf avrdude reads its parts and programmers from, `None` for its default one.
+    pub avrdude_conf: Option<PathBuf>,
+    /// How much avrdude prints while it runs.
+    pub verbosity: Verbosity,
+    /// The bit clock period passed to the ISP programmer with `-B`, `None` for its default.
+    pub bitclock: Option<String>,
+    /// How often avrdude is run before a transient failure is reported, at least once.
+    pub attempts: u8,
+    /// How long an avrdude command may run before it is killed, `None` to wait forever. Jobs
+    /// transferring a lot of data get more time.
+    pub timeout: Option<Duration>,
+    /// The reset pulse sent through the serial port before avrdude is run, `None` to send none.
+    pub reset_pulse: Option<ResetPulse>,
+    /// The extended parameters of the programmer, each one is passed as `-x key=value`.
+    pub extended_params: Vec<(String, String)>,
+}
+
+impl BoardSpec {
+    /// The spec of a board flashed by avrdude through a serial bootloader.
+    pub const fn avrdude(
+        programmer: &'static str,
+        partno: &'static str,
+        baud: Option<u32>,
+    ) -> Self {
+        Self {
+            uploader: Uploader::Avrdude,
+            programmer: Cow::Borrowed(programmer),
+            partno: Cow::Borrowed(partno),
+            baud,
+            fallback_baud: None,
+            erase: EraseMode::ForceChipErase,
+            needs_reset: false,
+            over_isp: false,
+            updi: false,
+            pick_partno: false,
+            isp: None,
+            touch_1200bps: false,
+            touch_timeout: Duration::from_secs(touch::DEFAULT_TIMEOUT_SECS),
+            flash_offset: None,
+            dfu_device: None,
+            dfu_alt: 0,
+            ota_port: 0,
+            clock_hz: 16_000_000,
+            bootloader: None,
+            bootloader_size: 0,
+            fuse_presets: &[],
+            usb_ids: Cow::Borrowed(&[]),
+            extra_args: Vec::new(),
+            avrdude_path: None,
+            avrdude_conf: None,
+            verbosity: Verbosity::Normal,
+            bitclock: None,
+            attempts: 1,
+            timeout: None,
+            reset_pulse: None,
+            extended_params: Vec::new(),
+        }
+    }
+
+    /// The spec of a Teensy with the HalfKay bootloader.
+    fn teensy(mcu: &'static str, clock_hz: u32) -> Self {
+        Self {
+            uploader: Uploader::TeensyLoader,
+            clock_hz,
+            ..Self::avrdude("", mcu, None)
+        }
+    }
+
+    /// The avrdude executable the commands are run with.
+    pub fn avrdude_executable(&self) -> &OsStr {
+        match self.avrdude_path {
+            Some(ref path) => path.as_os_str(),
+            None => Uploader::Avrdude.executable().as_ref(),
+        }
+    }
+
+    /// The information about the chip of the board, if it is known.
+    pub fn chip(&self) -> Option<&'static Chip> {
+        CHIPS
+            .iter()
+            .find(|chip| chip.partno.eq_ignore_ascii_case(&self.partno))
+    }
+
+    /// The ELF machine the programs for the board are built for.
+    pub fn elf_machine(&self) -> u16 {
+        match self.uploader {
+            Uploader::Avrdude | Uploader::Stk500 | Uploader::Micronucleus => EM_AVR,
+            Uploader::Esptool | Uploader::Espota => EM_XTENSA,
+            Uploader::Bossac
+            | Uploader::TeensyLoader
+            | Uploader::Stm32flash
+            | Uploader::DfuUtil
+            | Uploader::Picotool
+            | Uploader::Uf2Copy => EM_ARM,
+        }
+    }
+
+    /// The number of bytes of the flash a program can use, `None` if the chip is unknown.
+    pub fn usable_flash(&self) -> Option<u32> {
+        self.chip()
+            .map(|chip| chip.flash_size.saturating_sub(self.bootloader_size))
+    }
+
+    /// A short summary of the chip of the board like "ATmega328P, 32 KB flash, 2 KB SRAM, 16 MHz".
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        match self.chip() {
+            Some(chip) => {
+                parts.push(chip.name.to_owned());
+                parts.push(format!("{} flash", format_size(chip.flash_size)));
+                parts.push(format!("{} SRAM", format_size(chip.sram_size)));
+            }
+            None if self.partno.is_empty() => (),
+            None => parts.push(self.partno.to_string()),
+        }
+        if self.clock_hz.is_multiple_of(1_000_000) && self.clock_hz != 0 {
+            parts.push(format!("{} MHz", self.clock_hz / 1_000_000));
+        } else if self.clock_hz != 0 {
+            parts.push(format!("{:.1} MHz", f64::from(self.clock_hz) / 1e6));
+        }
+        parts.join(", ")
+    }
+
+    /// Flash the board through the given external programmer instead of its bootloader.
+    pub fn use_programmer(&mut self, isp: ProgrammerSpec) {
+        // The usb port of the board itself stays untouched.
+        self.usb_ids = Cow::Borrowed(&[]);
+        self.needs_reset = false;
+        self.touch_1200bps = false;
+        // The chip erase removes the bootloader, so the whole flash can be used.
+        self.bootloader_size = 0;
+        self.isp = Some(isp);
+    }
+
+    /// Wether avrdude talks to the chip through an ISP programmer.
+    pub fn uses_isp(&self) -> bool {
+        self.uploader == Uploader::Avrdude && !self.updi && (self.over_isp || self.isp.is_some())
+    }
+
+    /// Wether the bootloader only serves a single avrdude run after it was started, like Caterina
+    /// which starts the program as soon as avrdude disconnects.
+    pub fn single_bootloader_session(&self) -> bool {
+        self.isp.is_none()
+            && (self.needs_reset || self.touch_1200bps || self.programmer == "avr109")
+    }
+
+    /// Wether the fuses can be written, which ISP programmers can unlike the serial bootloaders.
+    pub fn can_write_fuses(&self) -> bool {
+        self.uses_isp()
+    }
+
+    /// Wether avrdude can really erase the chip, the serial bootloaders only acknowledge the
+    /// erase and leave the flash as it is.
+    pub fn can_erase_chip(&self) -> bool {
+        self.can_write_fuses() || (self.uploader == Uploader::Avrdude && self.updi)
+    }
+
+    /// The name of the programmer passed to avrdude, the external one if it is used.
+    pub fn upload_programmer(&self) -> &str {
+        match self.isp {
+            Some(ref isp) => &isp.name,
+            None => &self.programmer,
+        }
+    }
+
+    /// The baud rate passed to avrdude, the one of the external programmer if it is used.
+    pub fn upload_baud(&self) -> Option<u32> {
+        match self.isp {
+            Some(ref isp) => isp.baud,
+            None => self.baud,
+        }
+    }
+
+    /// Wether avrdude finds the programmer on the usb bus instead of a serial port.
+    pub fn uses_usb_programmer(&self) -> bool {
+        self.uploader == Uploader::Avrdude
+            && match self.isp {
+                Some(ref isp) => isp.port == ProgrammerPort::Usb,
+                None => self.over_isp,
+            }
+    }
+
+    /// Wether the given port has the usb ids of the board.
+    pub fn matches_port(&self, port: &SerialPortInfo) -> bool {
+        match port.port_type {
+            SerialPortType::UsbPort(ref usb) => self.usb_ids.contains(&(usb.vid, usb.pid)),
+            _ => false,
+        }
+    }
+
+    /// The other baud rate of the STK500v1 bootloaders of ATmega328P boards, the old ATmegaBOOT
+    /// listens at 57600 and optiboot at 115200. `None` if the board has another bootloader.
+    pub fn alternate_bootloader_baud(&self) -> Option<u32> {
+        if self.isp.is_some()
+            || self.programmer != "arduino"
+            || !self.partno.eq_ignore_ascii_case("atmega328p")
+        {
+            return None;
+        }
+        match self.baud? {
+            115_200 => Some(57_600),
+            57_600 => Some(115_200),
+            _ => None,
+        }
+    }
+
+    /// Wether the built-in STK500v1 uploader can flash the board instead of avrdude, which is the
+    /// case for ATmega328P boards with an optiboot compatible bootloader.
+    pub fn supports_builtin_uploader(&self) -> bool {
+        self.uploader == Uploader::Avrdude
+            && self.isp.is_none()
+            && self.programmer == "arduino"
+            && self.partno.eq_ignore_ascii_case("atmega328p")
+    }
+
+    /// Wether a serial port has to be selected to install a program to the board.
+    pub fn uses_serial_port(&self) -> bool {
+        match self.uploader {
+            Uploader::Avrdude => !self.uses_usb_programmer(),
+            Uploader::Stk500 | Uploader::Bossac | Uploader::Esptool | Uploader::Stm32flash => true,
+            // The port is only needed to reset the board into its dfu bootloader.
+            Uploader::DfuUtil => self.touch_1200bps,
+            Uploader::Micronucleus
+            | Uploader::Espota
+            | Uploader::TeensyLoader
+            | Uploader::Picotool
+            | Uploader::Uf2Copy => false,
+        }
+    }
+}
+
+/// Information about a chip used on a board
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Chip {
+    /// The partno of the chip, as used in [`BoardSpec::partno`].
+    pub partno: &'static str,
+    /// The name shown to the user.
+    pub name: &'static str,
+    /// The size of the program flash in bytes.
+    pub flash_size: u32,
+    /// The size of the SRAM in bytes.
+    pub sram_size: u32,
+    /// The size of the EEPROM in bytes, 0 if the chip has none.
+    pub eeprom_size: u32,
+    /// The signature avrdude reads from the chip, `None` if it is not an AVR.
+    pub signature: Option<[u8; 3]>,
+}
+
+impl Chip {
+    /// Create the information about a chip.
+    const fn new(
+        partno: &'static str,
+        name: &'static str,
+        flash_size: u32,
+        sram_size: u32,
+        eeprom_size: u32,
+    ) -> Self {
+        Self {
+            partno,
+            name,
+            flash_size,
+            sram_size,
+            eeprom_size,
+            signature: None,
+        }
+    }
+
+    /// Create the information about an AVR chip with the given signature.
+    const fn avr(
+        partno: &'static str,
+        name: &'static str,
+        flash_size: u32,
+        sram_size: u32,
+        eeprom_size: u32,
+        signature: [u8; 3],
+    ) -> Self {
+        Self {
+            signature: Some(signature),
+            ..Self::new(partno, name, flash_size, sram_size, eeprom_size)
+        }
+    }
+
+    /// The known chip with the given signature.
+    pub fn with_signature(signature: [u8; 3]) -> Option<&'static Self> {
+        CHIPS.iter().find(|chip| chip.signature == Some(signature))
+    }
+}
+
+/// All known chips, the Teensy boards use the board name as partno.
+const CHIPS: &[Chip] = &[
+    Chip::avr(
+        "atmega328p",
+        "ATmega328P",
+        32 * KB,
+        2 * KB,
+        KB,
+        [0x1E, 0x95, 0x0F],
+    ),
+    Chip::avr(
+        "atmega168",
+        "ATmega168",
+        16 * KB,
+        KB,
+        512,
+        [0x1E, 0x94, 0x06],
+    ),
+    Chip::avr(
+        "atmega2560",
+        "ATmega2560",
+        256 * KB,
+        8 * KB,
+        4 * KB,
+        [0x1E, 0x98, 0x01],
+    ),
+    Chip::avr(
+        "atmega1284p",
+        "ATmega1284P",
+        128 * KB,
+        16 * KB,
+        4 * KB,
+        [0x1E, 0x97, 0x05],
+    ),
+    Chip::avr(
+        "atmega32u4",
+        "ATmega32U4",
+        32 * KB,
+        2560,
+        KB,
+        [0x1E, 0x95, 0x87],
+    ),
+    Chip::avr("attiny85", "ATtiny85", 8 * KB, 512, 512, [0x1E, 0x93, 0x0B]),
+    Chip::avr(
+        "atmega4809",
+        "ATmega4809",
+        48 * KB,
+        6 * KB,
+        256,
+        [0x1E, 0x96, 0x51],
+    ),
+    Chip::avr(
+        "attiny412",
+        "ATtiny412",
+        4 * KB,
+        256,
+        128,
+        [0x1E, 0x92, 0x23],
+    ),
+    Chip::avr(
+        "attiny1614",
+        "ATtiny1614",
+        16 * KB,
+        2 * KB,
+        256,
+        [0x1E, 0x94, 0x22],
+    ),
+    Chip::avr(
+        "attiny1616",
+        "ATtiny1616",
+        16 * KB,
+        2 * KB,
+        256,
+        [0x1E, 0x94, 0x21],
+    ),
+    Chip::avr(
+        "attiny3216",
+        "ATtiny3216",
+        32 * KB,
+        2 * KB,
+        256,
+        [0x1E, 0x95, 0x21],
+    ),
+    Chip::avr(
+        "attiny3226",
+        "ATtiny3226",
+        32 * KB,
+        3 * KB,
+        256,
+        [0x1E, 0x95, 0x27],
+    ),
+    Chip::avr(
+        "avr128da28",
+        "AVR128DA28",
+        128 * KB,
+        16 * KB,
+        512,
+        [0x1E, 0x97, 0x0A],
+    ),
+    Chip::avr(
+        "avr128db28",
+        "AVR128DB28",
+        128 * KB,
+        16 * KB,
+        512,
+        [0x1E, 0x97, 0x0E],
+    ),
+    Chip::new("avr64dd28", "AVR64DD28", 64 * KB, 8 * KB, 256),
+    Chip::new("at91sam3x8e", "AT91SAM3X8E", 512 * KB, 96 * KB, 0),
+    Chip::new("atsamd21g18", "ATSAMD21G18", 256 * KB, 32 * KB, 0),
+    Chip::new("esp8266", "ESP8266", 4 * MB, 80 * KB, 0),
+    Chip::new("esp32", "ESP32", 4 * MB, 520 * KB, 0),
+    Chip::new("TEENSY32", "MK20DX256", 256 * KB, 64 * KB, 2 * KB),
+    Chip::new("TEENSY40", "IMXRT1062", 1984 * KB, MB, 1080),
+    Chip::new("TEENSY41", "IMXRT1062", 7936 * KB, MB, 4284),
+    Chip::new("stm32f103c8", "STM32F103C8", 64 * KB, 20 * KB, 0),
+    Chip::new("rp2040", "RP2040", 2 * MB, 264 * KB, 0),
+    Chip::new("r7fa4m1ab", "RA4M1", 256 * KB, 32 * KB, 8 * KB),
+];
+
+/// The partnos of the chips which can be picked for a SerialUPDI target.
+pub const UPDI_PARTS: [&str; 9] = [
+    "atmega4809",
+    "attiny412",
+    "attiny1614",
+    "attiny1616",
+    "attiny3216",
+    "attiny3226",
+    "avr128da28",
+    "avr128db28",
+    "avr64dd28",
+];
+
+/// One kilobyte.
+const KB: u32 = 1024;
+/// One megabyte.
+const MB: u32 = 1024 * KB;
+
+/// Format the given number of bytes with the largest fitting unit like "32 KB".
+pub fn format_size(bytes: u32) -> String {
+    if bytes >= MB && bytes.is_multiple_of(MB) {
+        format!("{} MB", bytes / MB)
+    } else if bytes >= KB && bytes.is_multiple_of(KB) {
+        format!("{} KB", bytes / KB)
+    } else if bytes >= KB {
+        format!("{:.1} KB", f64::from(bytes) / f64::from(KB))
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
+
+/// Enumeration of how much avrdude prints while it runs
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Verbosity {
+    /// Only errors and the result, `-q` is passed
+    Quiet,
+    /// The default output of avrdude
+    #[default]
+    Normal,
+    /// Details about the programmer and the chip, `-v` is passed
+    Verbose,
+    /// The communication with the programmer, `-v -v` is passed
+    Debug,
+}
+
+impl Verbosity {
+    /// All verbosity levels.
+    pub const ALL: [Self; 4] = [Self::Quiet, Self::Normal, Self::Verbose, Self::Debug];
+
+    /// The name shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Quiet => "Quiet (-q)",
+            Self::Normal => "Normal",
+            Self::Verbose => "Verbose (-v)",
+            Self::Debug => "Debug (-v -v)",
+        }
+    }
+
+    /// The avrdude arguments selecting the level.
+    pub fn avrdude_args(self) -> &'static [&'static str] {
+        match self {
+            Self::Quiet => &["-q"],
+            Self::Normal => &[],
+            Self::Verbose => &["-v"],
+            Self::Debug => &["-v", "-v"],
+        }
+    }
+}
+
+/// Enumeration of the ways avrdude erases the flash before writing it
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EraseMode {
+    /// avrdude decides, no flag is passed
+    Auto,
+    /// The chip is erased with `-e` before writing
+    ForceChipErase,
+    /// Nothing is erased, `-D` is passed
+    NoErase,
+}
+
+impl EraseMode {
+    /// All erase modes.
+    pub const ALL: [Self; 3] = [Self::Auto, Self::ForceChipErase, Self::NoErase];
+
+    /// The name shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Auto => "Auto",
+            Self::ForceChipErase => "Chip erase (-e)",
+            Self::NoErase => "No erase (-D)",
+        }
+    }
+
+    /// What the mode does, for uploads over ISP and over a bootloader.
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Auto => {
+                "avrdude erases the chip before writing the flash. Bootloaders ignore the erase \
+                and erase each page while writing it."
+            }
+            Self::ForceChipErase => {
+                "The chip is always erased first. Over ISP this clears the whole flash including \
+                the bootloader, bootloaders only acknowledge it."
+            }
+            Self::NoErase => {
+                "Nothing is erased. Bootloaders erase each page while writing it, over ISP the \
+                flash has to be erased before or it gets corrupted."
+            }
+        }
+    }
+
+    /// Wether the EEPROM may be cleared by the erase, unless the EESAVE fuse is set.
+    pub fn erases_chip(self) -> bool {
+        self != Self::NoErase
+    }
+
+    /// The avrdude argument selecting the mode, `None` if avrdude decides.
+    pub fn avrdude_arg(self) -> Option<&'static str> {
+        match self {
+            Self::Auto => None,
+            Self::ForceChipErase => Some("-e"),
+            Self::NoErase => Some("-D"),
+        }
+    }
+}
+
+/// Enumeration of the external programs used to install programs
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Uploader {
+    /// avrdude, used for nearly all AVR boards
+    Avrdude,
+    /// The built-in STK500v1 uploader, used instead of avrdude for the Uno and Nano if chosen
+    Stk500,
+    /// micronucleus, used for the Digispark bootloader
+    Micronucleus,
+    /// bossac, used for the SAM based boards
+    Bossac,
+    /// esptool, used for the ESP8266 and ESP32
+    Esptool,
+    /// espota, used for over the air updates of the ESP8266 and ESP32
+    Espota,
+    /// teensy_loader_cli, used for the Teensy boards
+    TeensyLoader,
+    /// stm32flash, used for STM32 boards with the serial bootloader
+    Stm32flash,
+    /// dfu-util, used for STM32 boards with the maple dfu bootloader
+    DfuUtil,
+    /// picotool, used for the Raspberry Pi Pico
+    Picotool,
+    /// Copying an uf2 file to the drive of the bootloader, used for the Raspberry Pi Pico
+    Uf2Copy,
+}
+
+impl Uploader {
+    /// The name of the executable.
+    pub fn executable(self) -> &'static str {
+        match self {
+            Self::Avrdude => "avrdude",
+            Self::Micronucleus => "micronucleus",
+            Self::Bossac => "bossac",
+            Self::Esptool => "esptool.py",
+            Self::Espota => "espota.py",
+            Self::TeensyLoader => "teensy_loader_cli",
+            Self::Stm32flash => "stm32flash",
+            Self::DfuUtil => "dfu-util",
+            Self::Picotool => "picotool",
+            // No external program is involved.
+            Self::Stk500 | Self::Uf2Copy => "",
+        }
+    }
+
+    /// The file dialog filters of the files the uploader accepts.
+    pub fn file_filters(self) -> &'static [(&'static str, &'static [&'static str])] {
+        match self {
+            Self::Avrdude | Self::Stk500 => &[
+                ("elf file", &["elf"]),
+                ("hex file", &["hex"]),
+                ("bin file", &["bin"]),
+            ],
+            Self::Micronucleus | Self::TeensyLoader => &[("hex file", &["hex"])],
+            Self::Bossac | Self::Esptool | Self::Espota | Self::Stm32flash | Self::DfuUtil => {
+                &[("bin file", &["bin"])]
+            }
+            Self::Picotool | Self::Uf2Copy => &[("uf2 file", &["uf2"])],
+        }
+    }
+
+    /// The message shown while the uploader runs.
+    pub fn running_message(self) -> &'static str {
+        match self {
+            Self::Avrdude
+            | Self::Stk500
+            | Self::Bossac
+            | Self::Esptool
+            | Self::Stm32flash
+            | Self::DfuUtil => "Flashing...",
+            Self::Micronucleus => {
+                "Plug in your device now, micronucleus waits up to a minute for it to appear..."
+            }
+            Self::Espota => "Uploading over the air...",
+            Self::TeensyLoader => "Waiting for the button press on the Teensy...",
+            Self::Picotool | Self::Uf2Copy => "Flashing, the Pico has to be in BOOTSEL mode...",
+        }
+    }
+}
+
+/// Enumeration of the ways an STM32 board can be flashed
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum Stm32Method {
+    /// Through the serial bootloader in the system memory
+    #[default]
+    Serial,
+    /// Through the maple dfu bootloader
+    Dfu,
+}
+
+impl Stm32Method {
+    /// All methods in the order they are shown.
+    pub const ALL: [Self; 2] = [Self::Serial, Self::Dfu];
+
+    /// The name shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Serial => "Serial (stm32flash)",
+            Self::Dfu => "DFU (dfu-util)",
+        }
+    }
+}
+
+/// Enumeration of the ways an ESP board can be flashed
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum EspMethod {
+    /// Through the serial bootloader
+    #[default]
+    Serial,
+    /// Over the air through the network
+    Ota,
+}
+
+impl EspMethod {
+    /// All methods in the order they are shown.
+    pub const ALL: [Self; 2] = [Self::Serial, Self::Ota];
+
+    /// The name shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Serial => "Serial (esptool)",
+            Self::Ota => "Over the air (espota)",
+        }
+    }
+}
+
+/// Where a program for the Raspberry Pi Pico is installed to
+#[derive(Debug, Default, PartialEq, Clone)]
+pub enum PicoTarget {
+    /// The Pico is found by picotool
+    #[default]
+    Picotool,
+    /// The mounted drive of the Pico
+    Drive(PathBuf),
+}
+
+impl PicoTarget {
+    /// The name shown to the user.
+    pub fn label(&self) -> Cow<'_, str> {
+        match self {
+            Self::Picotool => "picotool (USB)".into(),
+            Self::Drive(path) => path.to_string_lossy(),
+        }
+    }
+}
+
+/// Enumeration of all programmers a board can be flashed with
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum Programmer {
+    /// The programmer of the board itself, its bootloader
+    #[default]
+    Bootloader,
+    /// The USBasp programmer
+    UsbAsp,
+    /// The USBtinyISP programmer
+    UsbTiny,
+    /// The Atmel AVRISP mkII programmer
+    AvrIspMkII,
+    /// An Arduino running the ArduinoISP sketch
+    ArduinoAsIsp,
+}
+
+impl Programmer {
+    /// All programmers in the order they are shown.
+    pub const ALL: [Self; 5] = [
+        Self::Bootloader,
+        Self::UsbAsp,
+        Self::UsbTiny,
+        Self::AvrIspMkII,
+        Self::ArduinoAsIsp,
+    ];
+
+    /// The spec of the external programmer, `None` for the bootloader of the board.
+    pub fn spec(self) -> Option<ProgrammerSpec> {
+        let (name, port, baud) = match self {
+            Self::Bootloader => return None,
+            Self::UsbAsp => ("usbasp", ProgrammerPort::Usb, None),
+            Self::UsbTiny => ("usbtiny", ProgrammerPort::Usb, None),
+            Self::AvrIspMkII => ("avrispmkii", ProgrammerPort::Usb, None),
+            Self::ArduinoAsIsp => ("stk500v1", ProgrammerPort::Serial, Some(19_200)),
+        };
+        Some(ProgrammerSpec {
+            name: Cow::Borrowed(name),
+            port,
+            baud,
+        })
+    }
+
+    /// The name shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Bootloader => "Board bootloader",
+            Self::UsbAsp => "USBasp",
+            Self::UsbTiny => "USBtinyISP",
+            Self::AvrIspMkII => "AVRISP mkII",
+            Self::ArduinoAsIsp => "Arduino as ISP",
+        }
+    }
+}
+
+/// A specification of an external programmer used instead of the bootloader of a board.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ProgrammerSpec {
+    /// The name avrdude uses for the programmer.
+    pub name: Cow<'static, str>,
+    /// How the programmer is connected.
+    pub port: ProgrammerPort,
+    /// The baud rate used to talk to the programmer, avrdudes default is used if `None`.
+    pub baud: Option<u32>,
+}
+
+/// Enumeration of the ways an external programmer is connected
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ProgrammerPort {
+    /// The programmer is found by avrdude on the usb bus
+    Usb,
+    /// The programmer is connected to a serial port
+    Serial,
+}
@@ -0,0 +1,112 @@
+//! A searchable popup listing all boards grouped by their category
+
+use eframe::egui;
+
+use crate::board::{ArduinoBoard, BoardCategory, SelectedBoard};
+use crate::custom_boards::CustomBoard;
+
+/// Show a button with the name of the selected board, which opens the board list when clicked.
+/// The boards for which `unsupported` returns a reason are shown but can't be picked.
+pub fn board_picker(
+    ui: &mut egui::Ui,
+    selected: &mut SelectedBoard,
+    custom_boards: &[CustomBoard],
+    unsupported: &dyn Fn(&SelectedBoard) -> Option<String>,
+) {
+    let popup_id = ui.make_persistent_id("BoardPicker");
+    let search_id = popup_id.with("search");
+    let button = ui.button(format!("{} \u{23f7}", selected.name()));
+    if button.clicked() {
+        ui.memory_mut(|mem| mem.toggle_popup(popup_id));
+        ui.memory_mut(|mem| mem.request_focus(search_id));
+    }
+    if !ui.memory(|mem| mem.is_popup_open(popup_id)) {
+        return;
+    }
+
+    let mut search = ui
+        .data_mut(|data| data.get_temp::<String>(search_id))
+        .unwrap_or_default();
+    let boards = ArduinoBoard::ALL
+        .into_iter()
+        .map(SelectedBoard::Builtin)
+        .chain(custom_boards.iter().cloned().map(SelectedBoard::Custom))
+        .collect::<Vec<_>>();
+
+    let mut close = false;
+    let area = egui::Area::new(popup_id)
+        .order(egui::Order::Foreground)
+        .constrain(true)
+        .fixed_pos(button.rect.left_bottom())
+        .show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                let search_response = ui.add(
+                    egui::TextEdit::singleline(&mut search)
+                        .id(search_id)
+                        .hint_text("Search"),
+                );
+                let needle = search.trim().to_lowercase();
+                let matching = |board: &&SelectedBoard| {
+                    needle.is_empty() || board.name().to_lowercase().contains(&needle)
+                };
+
+                // Enter in the search field picks the first match.
+                if search_response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    let pickable = |board: &&SelectedBoard| unsupported(board).is_none();
+                    if let Some(board) = boards.iter().filter(matching).find(pickable) {
+                        *selected = board.clone();
+                        close = true;
+                    }
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for category in BoardCategory::ALL {
+                            let in_category = boards
+                                .iter()
+                                .filter(|board| board.category() == category)
+                                .filter(matching)
+                                .collect::<Vec<_>>();
+                            if in_category.is_empty() {
+                                continue;
+                            }
+
+                            // A search shows every match without having to expand the groups.
+                            egui::CollapsingHeader::new(category.label())
+                                .default_open(true)
+                                .open((!needle.is_empty()).then_some(true))
+                                .show(ui, |ui| {
+                                    for board in in_category {
+                                        let reason = unsupported(board);
+                                        let label = egui::SelectableLabel::new(
+                                            board == selected,
+                                            board.name(),
+                                        );
+                                        let mut response = ui.add_enabled(reason.is_none(), label);
+                                        if let Some(reason) = reason {
+                                            response = response.on_disabled_hover_text(reason);
+                                        }
+                                        if response.clicked() {
+                                            *selected = board.clone();
+                                            close = true;
+                                        }
+                                    }
+                                });
+                        }
+                    });
+            });
+        });
+
+    let clicked_outside = ui.input(|i| {
+        i.pointer.any_click()
+            && i.pointer
+                .interact_pos()
+                .is_some_and(|pos| !area.response.rect.contains(pos) && !button.rect.contains(pos))
+    });
+    if close || clicked_outside || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+        ui.memory_mut(|mem| mem.close_popup());
+        search.clear();
+    }
+    ui.data_mut(|data| data.insert_temp(search_id, search));
+}
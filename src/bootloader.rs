@@ -0,0 +1,116 @@
+//! The bootloaders which can be burned to the AVR boards through an ISP programmer
+
+use std::{env, fs, path::PathBuf};
+
+/// The optiboot bootloader of the Uno and the Nano.
+pub const OPTIBOOT_328: Bootloader = Bootloader {
+    image: "optiboot/optiboot_atmega328.hex",
+    lfuse: 0xFF,
+    hfuse: 0xDE,
+    efuse: 0xFD,
+    lock_bits: 0x0F,
+};
+
+/// The old bootloader of the Nano and the 5V Pro Mini.
+pub const ATMEGABOOT_328: Bootloader = Bootloader {
+    image: "atmega/ATmegaBOOT_168_atmega328.hex",
+    lfuse: 0xFF,
+    hfuse: 0xDA,
+    efuse: 0xFD,
+    lock_bits: 0x0F,
+};
+
+/// The old bootloader of the 3.3V Pro Mini running at 8 MHz.
+pub const ATMEGABOOT_328_8MHZ: Bootloader = Bootloader {
+    image: "atmega/ATmegaBOOT_168_atmega328_pro_8MHz.hex",
+    lfuse: 0xFF,
+    hfuse: 0xDA,
+    efuse: 0xFD,
+    lock_bits: 0x0F,
+};
+
+/// The stk500v2 bootloader of the Mega 2560.
+pub const STK500V2_2560: Bootloader = Bootloader {
+    image: "stk500v2/stk500boot_v2_mega2560.hex",
+    lfuse: 0xFF,
+    hfuse: 0xD8,
+    efuse: 0xFD,
+    lock_bits: 0x0F,
+};
+
+/// The caterina bootloader of the Leonardo.
+pub const CATERINA_LEONARDO: Bootloader = Bootloader {
+    image: "caterina/Caterina-Leonardo.hex",
+    lfuse: 0xFF,
+    hfuse: 0xD8,
+    efuse: 0xCB,
+    lock_bits: 0x2F,
+};
+
+/// The caterina bootloader of the Micro.
+pub const CATERINA_MICRO: Bootloader = Bootloader {
+    image: "caterina/Caterina-Micro.hex",
+    ..CATERINA_LEONARDO
+};
+
+/// The lock bits written before burning, they allow the bootloader section to be overwritten.
+pub const UNLOCK_BITS: u8 = 0x3F;
+
+/// The name of the directory the bootloader images are searched in.
+const DIR_NAME: &str = "bootloaders";
+
+/// Everything required to burn the bootloader of a board
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Bootloader {
+    /// The path of the image inside of a bootloaders directory.
+    pub image: &'static str,
+    /// The low fuse byte.
+    pub lfuse: u8,
+    /// The high fuse byte.
+    pub hfuse: u8,
+    /// The extended fuse byte.
+    pub efuse: u8,
+    /// The lock bits written after the bootloader to protect it.
+    pub lock_bits: u8,
+}
+
+impl Bootloader {
+    /// Find the image of the bootloader, it is searched in a bootloaders directory next to the
+    /// executable, in the config directory and in the avr core of an arduino installation.
+    pub fn find_image(&self) -> Option<PathBuf> {
+        let next_to_exe = env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(DIR_NAME)));
+        let in_config =
+            dirs::config_dir().map(|dir| dir.join("arduino_installer_gui").join(DIR_NAME));
+        next_to_exe
+            .into_iter()
+            .chain(in_config)
+            .chain(arduino_core_dirs())
+            .map(|dir| dir.join(self.image))
+            .find(|path| path.is_file())
+    }
+}
+
+/// The bootloader directories of all installed versions of the arduino avr core, newest first.
+fn arduino_core_dirs() -> Vec<PathBuf> {
+    let arduino15 = if cfg!(target_os = "windows") {
+        dirs::data_local_dir().map(|dir| dir.join("Arduino15"))
+    } else if cfg!(target_os = "macos") {
+        dirs::home_dir().map(|dir| dir.join("Library").join("Arduino15"))
+    } else {
+        dirs::home_dir().map(|dir| dir.join(".arduino15"))
+    };
+    let Some(core) = arduino15.map(|dir| dir.join("packages/arduino/hardware/avr")) else {
+        return Vec::new();
+    };
+
+    let mut dirs: Vec<PathBuf> = fs::read_dir(core)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path().join(DIR_NAME)))
+        .collect();
+    // Version directories sort well enough by name to prefer the newest one.
+    dirs.sort_unstable_by(|a, b| b.cmp(a));
+    dirs
+}
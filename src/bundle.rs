@@ -0,0 +1,259 @@
+//! Firmware bundles, zip files containing the firmware image and a manifest describing it
+
+use std::{
+    env, fs,
+    io::{Cursor, Read},
+    path::{Component, Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use serde::Deserialize;
+use zip::ZipArchive;
+
+use crate::board::BoardSpec;
+
+/// The name of the manifest inside of a bundle.
+const MANIFEST: &str = "manifest.json";
+/// The image used if the manifest does not name one.
+const DEFAULT_IMAGE: &str = "firmware.hex";
+
+/// Counts the bundles extracted by this process, so every bundle gets its own directory.
+static EXTRACTED: AtomicU32 = AtomicU32::new(0);
+
+/// A bundle extracted to a temporary directory, the directory is removed when it is dropped.
+#[derive(Debug)]
+pub struct Bundle {
+    /// The zip file the bundle was extracted from.
+    pub source: PathBuf,
+    /// The temporary directory the bundle was extracted to.
+    dir: PathBuf,
+    /// The extracted firmware image.
+    pub image: PathBuf,
+    /// The manifest of the bundle.
+    pub manifest: Manifest,
+}
+
+/// The description of the firmware in a bundle
+#[derive(Debug, Default, PartialEq, Clone, Deserialize)]
+pub struct Manifest {
+    /// The path of the firmware image inside of the bundle, [`DEFAULT_IMAGE`] if `None`.
+    #[serde(default)]
+    pub image: Option<String>,
+    /// The name of the board the firmware is made for.
+    #[serde(default)]
+    pub board: Option<String>,
+    /// The baud rate used for the upload instead of the one of the board.
+    #[serde(default)]
+    pub baud: Option<u32>,
+    /// The partno passed to avrdude instead of the one of the board.
+    #[serde(default)]
+    pub partno: Option<String>,
+}
+
+impl Manifest {
+    /// Use the baud rate and partno of the manifest for the given spec of its board.
+    pub fn apply(&self, spec: &mut BoardSpec) {
+        if let Some(ref partno) = self.partno {
+            spec.partno = partno.clone().into();
+        }
+        // The baud rate is the one of the bootloader, external programmers keep their own.
+        if let Some(baud) = self.baud.filter(|_| spec.isp.is_none()) {
+            spec.baud = Some(baud);
+            spec.fallback_baud = None;
+        }
+    }
+}
+
+impl Bundle {
+    /// Wether the given path is named like a bundle.
+    pub fn is_bundle(path: &Path) -> bool {
+        path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+    }
+
+    /// Extract the bundle at the given path to a new temporary directory and read its manifest.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path)
+            .map_err(|e| format!("Error: could not read {}: {}", path.display(), e))?;
+        let entries = read_zip(&data)
+            .map_err(|e| format!("Error: {} is not a valid zip file: {}", path.display(), e))?;
+
+        let manifest = entries
+            .iter()
+            .find(|(name, _)| name == MANIFEST)
+            .ok_or_else(|| format!("Error: {} contains no {}", path.display(), MANIFEST))?;
+        let manifest: Manifest = serde_json::from_slice(&manifest.1)
+            .map_err(|e| format!("Error: the {} of the bundle is invalid: {}", MANIFEST, e))?;
+        let image = manifest.image.as_deref().unwrap_or(DEFAULT_IMAGE);
+        if !entries.iter().any(|(name, _)| name == image) {
+            return Err(format!("Error: {} contains no {}", path.display(), image));
+        }
+
+        let dir = env::temp_dir().join(format!(
+            "arduino_installer_gui-{}-{}",
+            process::id(),
+            EXTRACTED.fetch_add(1, Ordering::Relaxed)
+        ));
+        let bundle = Self {
+            source: path.to_owned(),
+            image: dir.join(image),
+            manifest,
+            dir,
+        };
+        // The directory is removed by the drop of the bundle if the extraction fails.
+        for (name, content) in &entries {
+            let target = bundle.dir.join(name);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("Error: could not create {}: {}", parent.display(), e))?;
+            }
+            fs::write(&target, content)
+                .map_err(|e| format!("Error: could not extract {}: {}", target.display(), e))?;
+        }
+        Ok(bundle)
+    }
+}
+
+impl Drop for Bundle {
+    fn drop(&mut self) {
+        // Nothing can be done about a failure, the directory is left to the system.
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Read all files of a zip archive, directories are skipped.
+fn read_zip(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(|e| e.to_string())?;
+        let name = entry.name().to_owned();
+        if entry.is_dir() {
+            continue;
+        }
+        // Entries must not be written outside of the extraction directory.
+        let safe = Path::new(&name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if !safe {
+            return Err(format!("the entry {} has an invalid path", name));
+        }
+
+        // The claimed size is not trusted, a corrupt entry could inflate to any size.
+        let size = entry.size();
+        let mut content = Vec::new();
+        entry
+            .take(size + 1)
+            .read_to_end(&mut content)
+            .map_err(|e| format!("the entry {} is corrupt: {}", name, e))?;
+        if content.len() as u64 != size {
+            return Err(format!("the entry {} has the wrong size", name));
+        }
+        entries.push((name, content));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+    use super::*;
+
+    /// A zip archive of the given entries, each with its name, its compression method and its
+    /// content, a name ending in `/` is a directory.
+    fn zip(entries: &[(&str, CompressionMethod, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for &(name, method, content) in entries {
+            let options = FileOptions::default().compression_method(method);
+            if name.ends_with('/') {
+                writer.add_directory(name, options).unwrap();
+            } else {
+                writer.start_file(name, options).unwrap();
+                writer.write_all(content).unwrap();
+            }
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// Make the headers of the only entry of the archive claim the given uncompressed size.
+    fn claim_size(data: &mut [u8], size: u32) {
+        let find = |signature: &[u8]| {
+            data.windows(4)
+                .position(|window| window == signature)
+                .unwrap()
+        };
+        let local = find(b"PK\x03\x04") + 22;
+        let central = find(b"PK\x01\x02") + 24;
+        data[local..local + 4].copy_from_slice(&size.to_le_bytes());
+        data[central..central + 4].copy_from_slice(&size.to_le_bytes());
+    }
+
+    #[test]
+    fn stored_and_deflated_entries() {
+        let image = b":00000001FF\n".repeat(20);
+        let data = zip(&[
+            (MANIFEST, CompressionMethod::Stored, b"{}"),
+            ("images/", CompressionMethod::Stored, b""),
+            (DEFAULT_IMAGE, CompressionMethod::Deflated, &image),
+        ]);
+        assert_eq!(
+            read_zip(&data),
+            Ok(vec![
+                (MANIFEST.to_owned(), b"{}".to_vec()),
+                (DEFAULT_IMAGE.to_owned(), image),
+            ])
+        );
+    }
+
+    #[test]
+    fn paths_outside_are_rejected() {
+        let data = zip(&[("../evil.hex", CompressionMethod::Stored, b"x")]);
+        assert_eq!(
+            read_zip(&data),
+            Err("the entry ../evil.hex has an invalid path".to_owned())
+        );
+        let data = zip(&[("/etc/evil", CompressionMethod::Stored, b"x")]);
+        assert!(read_zip(&data).is_err());
+    }
+
+    #[test]
+    fn wrong_sizes_are_rejected() {
+        let mut data = zip(&[(DEFAULT_IMAGE, CompressionMethod::Deflated, &[0; 4096])]);
+        claim_size(&mut data, 16);
+        assert_eq!(
+            read_zip(&data),
+            Err("the entry firmware.hex has the wrong size".to_owned())
+        );
+        let mut data = zip(&[(DEFAULT_IMAGE, CompressionMethod::Stored, b"abc")]);
+        claim_size(&mut data, 4);
+        assert!(read_zip(&data).is_err());
+    }
+
+    #[test]
+    fn truncated_archive() {
+        let data = zip(&[(MANIFEST, CompressionMethod::Stored, b"{}")]);
+        assert!(read_zip(&data[..data.len() - 30]).is_err());
+        assert!(read_zip(b"not a zip").is_err());
+    }
+
+    #[test]
+    fn manifest_fields() {
+        let manifest: Manifest =
+            serde_json::from_str(r#"{"board": "Arduino Uno", "baud": 115200, "extra": [1]}"#)
+                .unwrap();
+        assert_eq!(
+            manifest,
+            Manifest {
+                board: Some("Arduino Uno".to_owned()),
+                baud: Some(115_200),
+                ..Manifest::default()
+            }
+        );
+        assert!(serde_json::from_str::<Manifest>(r#"{"image": 1}"#).is_err());
+        assert!(serde_json::from_str::<Manifest>(r#"{"baud": -1}"#).is_err());
+    }
+}
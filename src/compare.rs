@@ -0,0 +1,160 @@
+//! Comparing the flash images of two firmware files
+
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+use rfd::FileDialog;
+
+use crate::{
+    firmware::{self, FirmwareFormat},
+    hex::Block,
+};
+
+/// The window comparing two firmware files.
+#[derive(Debug, Default)]
+pub struct CompareTool {
+    /// Wether the window is shown.
+    pub open: bool,
+    /// The path of the first file.
+    first: String,
+    /// The path of the second file.
+    second: String,
+    /// The report of the last comparison.
+    result: Option<Result<String, String>>,
+}
+
+/// How two flash images differ
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Difference {
+    /// The number of addresses with different or only one byte.
+    pub bytes: u64,
+    /// The first address which differs.
+    pub first: Option<u64>,
+    /// The last address which differs.
+    pub last: Option<u64>,
+}
+
+impl CompareTool {
+    /// Open the window, the first file is the given one if none was entered yet.
+    pub fn show_with(&mut self, selected: Option<&Path>) {
+        if self.first.is_empty() {
+            if let Some(selected) = selected {
+                self.first = selected.display().to_string();
+            }
+        }
+        self.open = true;
+    }
+
+    /// Show the window if it is open.
+    pub fn ui(&mut self, ctx: &egui::Context) {
+        let mut open = self.open;
+        egui::Window::new("Compare firmware")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("CompareFiles").show(ui, |ui| {
+                    for (label, path) in [("Old: ", &mut self.first), ("New: ", &mut self.second)] {
+                        ui.label(label);
+                        ui.text_edit_singleline(path);
+                        if ui.button("Choose").clicked() {
+                            if let Some(file) = FileDialog::new().pick_file() {
+                                *path = file.display().to_string();
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+                if ui.button("Compare").clicked() {
+                    self.result = Some(compare_files(
+                        &PathBuf::from(self.first.trim()),
+                        &PathBuf::from(self.second.trim()),
+                    ));
+                }
+
+                match self.result {
+                    Some(Ok(ref report)) => {
+                        ui.monospace(report);
+                        if ui.button("Copy").clicked() {
+                            ui.output_mut(|o| o.copied_text = report.clone());
+                        }
+                    }
+                    Some(Err(ref e)) => {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                    None => (),
+                }
+            });
+        self.open = open;
+    }
+}
+
+/// Compare the flash images of the given files and describe the difference.
+fn compare_files(first: &Path, second: &Path) -> Result<String, String> {
+    let load = |path: &Path| {
+        let format = FirmwareFormat::detect(path)?;
+        Ok::<_, String>((format, firmware::flash_image(path, format)?))
+    };
+    let (first_format, first_image) = load(first)?;
+    let (second_format, second_image) = load(second)?;
+    let size = |image: &[Block]| -> u64 { image.iter().map(|block| block.data.len() as u64).sum() };
+
+    let mut report = format!(
+        "OLD: {} ({}, {} bytes)\nNEW: {} ({}, {} bytes)\n",
+        first.display(),
+        first_format.label(),
+        size(&first_image),
+        second.display(),
+        second_format.label(),
+        size(&second_image)
+    );
+    let difference = difference(&first_image, &second_image);
+    match (difference.first, difference.last) {
+        (Some(first), Some(last)) => report.push_str(&format!(
+            "The images differ in {} bytes between 0x{:X} and 0x{:X}",
+            difference.bytes, first, last
+        )),
+        _ => report.push_str("The images are identical"),
+    }
+    Ok(report)
+}
+
+/// The difference between the given flash images, which are sorted by address and do not overlap.
+pub fn difference(first: &[Block], second: &[Block]) -> Difference {
+    let mut difference = Difference::default();
+    let mut differs = |address: u64| {
+        difference.bytes += 1;
+        difference.first.get_or_insert(address);
+        difference.last = Some(address);
+    };
+
+    // All addresses covered by either image, in order.
+    let mut ranges: Vec<(u64, u64)> = first
+        .iter()
+        .chain(second)
+        .map(|block| (u64::from(block.address), block.end()))
+        .collect();
+    ranges.sort_unstable();
+    let mut covered: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match covered.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => covered.push((start, end)),
+        }
+    }
+
+    for (start, end) in covered {
+        for address in start..end {
+            if byte_at(first, address) != byte_at(second, address) {
+                differs(address);
+            }
+        }
+    }
+    difference
+}
+
+/// The byte of the image at the given address, `None` if the image does not contain it.
+fn byte_at(image: &[Block], address: u64) -> Option<u8> {
+    let index = image.partition_point(|block| u64::from(block.address) <= address);
+    let block = image.get(index.checked_sub(1)?)?;
+    let offset = usize::try_from(address - u64::from(block.address)).ok()?;
+    block.data.get(offset).copied()
+}
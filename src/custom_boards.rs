@@ -0,0 +1,111 @@
+//! Board definitions loaded from a toml file at startup
+
+use std::{
+    borrow::Cow,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::board::{BoardCategory, BoardSpec, EraseMode};
+
+/// The file name of the custom board definitions.
+const FILE_NAME: &str = "boards.toml";
+
+/// The contents of the custom board definitions file.
+#[derive(Debug, Deserialize)]
+struct BoardsFile {
+    /// All defined boards.
+    #[serde(default)]
+    board: Vec<CustomBoard>,
+}
+
+/// A board defined by the user, which is always flashed with avrdude.
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+pub struct CustomBoard {
+    /// The name shown to the user.
+    pub name: String,
+    /// The name of the programmer passed to avrdude.
+    pub programmer: String,
+    /// The name of the chip the program should be installed to.
+    pub partno: String,
+    /// The baud rate used to talk to the bootloader.
+    pub baud: Option<u32>,
+    /// Wether the chip should be whiped before installing.
+    #[serde(default = "default_chip_erase")]
+    pub chip_erase: bool,
+    /// Additional arguments passed to avrdude.
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    /// The usb vendor and product ids of the serial port of the board.
+    #[serde(default)]
+    pub usb_ids: Vec<(u16, u16)>,
+    /// The category the board is listed in.
+    #[serde(default)]
+    pub category: BoardCategory,
+    /// The clock speed the chip runs with in Hz.
+    #[serde(default)]
+    pub clock_hz: u32,
+}
+
+/// Chip erase is done by default like for the built in boards.
+fn default_chip_erase() -> bool {
+    true
+}
+
+impl CustomBoard {
+    /// The specification required to install a program to the board.
+    pub fn spec(&self) -> BoardSpec {
+        BoardSpec {
+            programmer: Cow::Owned(self.programmer.clone()),
+            partno: Cow::Owned(self.partno.clone()),
+            baud: self.baud,
+            erase: if self.chip_erase {
+                EraseMode::ForceChipErase
+            } else {
+                EraseMode::NoErase
+            },
+            extra_args: self.extra_args.iter().cloned().map(Cow::Owned).collect(),
+            usb_ids: Cow::Owned(self.usb_ids.clone()),
+            clock_hz: self.clock_hz,
+            ..BoardSpec::avrdude("", "", None)
+        }
+    }
+}
+
+/// The path of the custom board definitions.
+///
+/// A path given with `--boards <path>` is used first, otherwise a file next to the executable and
+/// then one in the config directory is searched.
+pub fn definitions_path() -> Option<PathBuf> {
+    let mut args = env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--boards" {
+            return args.next().map(PathBuf::from);
+        }
+    }
+
+    let next_to_exe = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(FILE_NAME)));
+    let in_config = dirs::config_dir().map(|dir| dir.join("arduino_installer_gui").join(FILE_NAME));
+    next_to_exe
+        .into_iter()
+        .chain(in_config)
+        .find(|path| path.is_file())
+}
+
+/// Load the custom board definitions from the given file.
+pub fn load(path: &Path) -> Result<Vec<CustomBoard>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e: io::Error| format!("Error: could not read {}: {}", path.display(), e))?;
+    let file: BoardsFile = toml::from_str(&content).map_err(|e| {
+        format!(
+            "Error: invalid board definitions in {}: {}",
+            path.display(),
+            e
+        )
+    })?;
+    Ok(file.board)
+}
@@ -0,0 +1,153 @@
+//! Downloads of firmware files through curl
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use eframe::egui;
+use sha2::{Digest, Sha256};
+
+use crate::file_info;
+
+/// A download running on a background thread.
+pub struct Download {
+    /// The url which is downloaded.
+    pub url: String,
+    /// The file the content is written to while it is downloaded.
+    partial: PathBuf,
+    /// Receives the downloaded file once curl exited.
+    result: Receiver<Result<PathBuf, String>>,
+}
+
+impl Download {
+    /// Start downloading the given http(s) url into the cache directory, the context is repainted
+    /// when it is done.
+    pub fn start(url: &str, ctx: &egui::Context) -> Result<Self, String> {
+        let url = url.trim();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err("Error: only http and https urls can be downloaded".to_owned());
+        }
+        let Some(cache) = dirs::cache_dir() else {
+            return Err("Error: no cache directory to download the file to".to_owned());
+        };
+
+        // Every url gets its own directory so the file keeps its name.
+        let dir = cache
+            .join("arduino_installer_gui")
+            .join("downloads")
+            .join(&file_info::to_hex(&Sha256::digest(url))[..16]);
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Error: could not create {}: {}", dir.display(), e))?;
+        let target = dir.join(file_name(url));
+        let partial = dir.join("download.part");
+
+        let (sender, result) = mpsc::channel();
+        let job = (url.to_owned(), target, partial.clone(), dir.join("etag"));
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let (url, target, partial, etag) = job;
+            // The receiver is only dropped when the gui closed, so the result is not needed.
+            let _ = sender.send(download(&url, &target, &partial, &etag));
+            ctx.request_repaint();
+        });
+
+        Ok(Self {
+            url: url.to_owned(),
+            partial,
+            result,
+        })
+    }
+
+    /// The downloaded file, if curl exited.
+    pub fn poll(&self) -> Option<Result<PathBuf, String>> {
+        match self.result.try_recv() {
+            Ok(res) => Some(res),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(
+                "Error: the download thread stopped unexpectedly".to_owned(),
+            )),
+        }
+    }
+
+    /// The number of bytes downloaded so far.
+    pub fn downloaded(&self) -> u64 {
+        fs::metadata(&self.partial)
+            .map(|meta| meta.len())
+            .unwrap_or_default()
+    }
+}
+
+/// Download the url to the target, which is reused if the server reports an unchanged ETag.
+fn download(url: &str, target: &Path, partial: &Path, etag: &Path) -> Result<PathBuf, String> {
+    let mut cmd = Command::new("curl");
+    cmd.arg("--silent")
+        .arg("--show-error")
+        .arg("--location")
+        .arg("--connect-timeout")
+        .arg("15")
+        .arg("--max-time")
+        .arg("300")
+        .arg("--etag-save")
+        .arg(etag)
+        .arg("--output")
+        .arg(partial)
+        .arg("--write-out")
+        .arg("%{http_code}");
+    if target.is_file() && etag.is_file() {
+        cmd.arg("--etag-compare").arg(etag);
+    }
+    cmd.arg(url);
+
+    let out = cmd.output().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => {
+            "Error: curl is not installed or could not be found in PATH".to_owned()
+        }
+        _ => format!("Error: could not run curl: {}", e),
+    })?;
+    if !out.status.success() {
+        return Err(format!(
+            "Error: downloading {} failed: {}",
+            url,
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+
+    let status = String::from_utf8_lossy(&out.stdout).trim().to_owned();
+    match status.as_str() {
+        "304" => {
+            let _ = fs::remove_file(partial);
+            Ok(target.to_owned())
+        }
+        code if code.starts_with('2') => {
+            fs::rename(partial, target)
+                .map_err(|e| format!("Error: could not move the download: {}", e))?;
+            Ok(target.to_owned())
+        }
+        code => {
+            let _ = fs::remove_file(partial);
+            // The ETag of an error page must not mark the old file as current.
+            let _ = fs::remove_file(etag);
+            Err(format!(
+                "Error: downloading {} failed with HTTP status {}",
+                url, code
+            ))
+        }
+    }
+}
+
+/// The file name of the given url, without the query and fragment.
+fn file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let path = path.split_once("://").map_or(path, |(_, rest)| rest);
+    match path
+        .split_once('/')
+        .map(|(_, path)| path.rsplit('/').next())
+    {
+        Some(Some(name)) if !name.is_empty() => name.to_owned(),
+        _ => "firmware".to_owned(),
+    }
+}
@@ -0,0 +1,555 @@
+//! A minimal parser for the headers of ELF files
+
+use std::borrow::Cow;
+
+/// The machine of AVR chips.
+pub const EM_AVR: u16 = 83;
+/// The machine of 32 bit ARM chips.
+pub const EM_ARM: u16 = 40;
+/// The machine of Xtensa chips like the ESP8266 and ESP32.
+pub const EM_XTENSA: u16 = 94;
+
+/// The type of a loadable program segment.
+const PT_LOAD: u32 = 1;
+/// The type of a section which occupies no space in the file, like `.bss`.
+const SHT_NOBITS: u32 = 8;
+/// The flag of sections which are writable at runtime.
+const SHF_WRITE: u64 = 0x1;
+/// The flag of sections which occupy memory at runtime.
+const SHF_ALLOC: u64 = 0x2;
+
+/// An ELF file read into memory.
+pub struct ElfFile {
+    /// The content of the file.
+    data: Vec<u8>,
+    /// Wether the file is a 64 bit ELF.
+    is_64: bool,
+    /// Wether the header fields are stored little endian.
+    little_endian: bool,
+}
+
+/// A loadable program segment of an ELF file.
+#[derive(Debug, Clone, Copy)]
+pub struct Segment {
+    /// The physical address the segment is loaded to.
+    pub paddr: u64,
+    /// The offset of the content in the file.
+    pub offset: u64,
+    /// The number of bytes of the segment stored in the file.
+    pub filesz: u64,
+}
+
+/// A section of an ELF file.
+#[derive(Debug, Clone)]
+pub struct Section {
+    /// The name of the section.
+    pub name: String,
+    /// The type of the section.
+    pub kind: u32,
+    /// The flags of the section.
+    pub flags: u64,
+    /// The offset of the content in the file.
+    pub offset: u64,
+    /// The size of the section in bytes.
+    pub size: u64,
+}
+
+/// The sizes of a program like `avr-size` shows them.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct SectionSizes {
+    /// The code and constant data stored in the flash.
+    pub text: u64,
+    /// The initialized variables, stored in the flash and copied to the RAM.
+    pub data: u64,
+    /// The variables initialized with zero, only occupying RAM.
+    pub bss: u64,
+}
+
+impl ElfFile {
+    /// Parse the identification of the given ELF file.
+    pub fn parse(data: Vec<u8>) -> Result<Self, String> {
+        if data.len() < 52 || !data.starts_with(b"\x7fELF") {
+            return Err("not an ELF file".to_owned());
+        }
+        let is_64 = match data[4] {
+            1 => false,
+            2 => true,
+            class => return Err(format!("unknown ELF class {}", class)),
+        };
+        let little_endian = match data[5] {
+            1 => true,
+            2 => false,
+            encoding => return Err(format!("unknown ELF data encoding {}", encoding)),
+        };
+        if is_64 && data.len() < 64 {
+            return Err("truncated ELF header".to_owned());
+        }
+        Ok(Self {
+            data,
+            is_64,
+            little_endian,
+        })
+    }
+
+    /// The machine the file was built for.
+    pub fn machine(&self) -> u16 {
+        self.u16_at(18).unwrap_or_default()
+    }
+
+    /// All loadable segments of the file.
+    pub fn load_segments(&self) -> Result<Vec<Segment>, String> {
+        let (phoff, phentsize, phnum) = if self.is_64 {
+            (self.u64_at(32), self.u16_at(54), self.u16_at(56))
+        } else {
+            (
+                self.u32_at(28).map(u64::from),
+                self.u16_at(42),
+                self.u16_at(44),
+            )
+        };
+        let (Some(phoff), Some(phentsize), Some(phnum)) = (phoff, phentsize, phnum) else {
+            return Err("truncated ELF header".to_owned());
+        };
+
+        let mut segments = Vec::new();
+        for i in 0..u64::from(phnum) {
+            let offset = i
+                .checked_mul(u64::from(phentsize))
+                .and_then(|rel| rel.checked_add(phoff))
+                .and_then(|offset| usize::try_from(offset).ok())
+                .ok_or_else(|| "program header out of range".to_owned())?;
+            // The fields are read relative to the offset, which must not overflow.
+            if offset >= self.data.len() {
+                return Err("truncated program header".to_owned());
+            }
+            let segment = if self.is_64 {
+                self.u32_at(offset)
+                    .zip(self.u64_at(offset + 8))
+                    .zip(self.u64_at(offset + 24).zip(self.u64_at(offset + 32)))
+            } else {
+                self.u32_at(offset)
+                    .zip(self.u32_at(offset + 4).map(u64::from))
+                    .zip(
+                        self.u32_at(offset + 12)
+                            .map(u64::from)
+                            .zip(self.u32_at(offset + 16).map(u64::from)),
+                    )
+            };
+            let Some(((kind, offset), (paddr, filesz))) = segment else {
+                return Err("truncated program header".to_owned());
+            };
+            if kind == PT_LOAD {
+                segments.push(Segment {
+                    paddr,
+                    offset,
+                    filesz,
+                });
+            }
+        }
+        Ok(segments)
+    }
+
+    /// The address execution starts at.
+    pub fn entry(&self) -> u64 {
+        if self.is_64 {
+            self.u64_at(24)
+        } else {
+            self.u32_at(24).map(u64::from)
+        }
+        .unwrap_or_default()
+    }
+
+    /// All sections of the file.
+    pub fn sections(&self) -> Result<Vec<Section>, String> {
+        let (shoff, shentsize, shnum, shstrndx) = if self.is_64 {
+            (
+                self.u64_at(40),
+                self.u16_at(58),
+                self.u16_at(60),
+                self.u16_at(62),
+            )
+        } else {
+            (
+                self.u32_at(32).map(u64::from),
+                self.u16_at(46),
+                self.u16_at(48),
+                self.u16_at(50),
+            )
+        };
+        let (Some(shoff), Some(shentsize), Some(shnum), Some(shstrndx)) =
+            (shoff, shentsize, shnum, shstrndx)
+        else {
+            return Err("truncated ELF header".to_owned());
+        };
+
+        let mut sections = Vec::new();
+        let mut names = Vec::new();
+        for i in 0..u64::from(shnum) {
+            let offset = i
+                .checked_mul(u64::from(shentsize))
+                .and_then(|rel| rel.checked_add(shoff))
+                .and_then(|offset| usize::try_from(offset).ok())
+                .ok_or_else(|| "section header out of range".to_owned())?;
+            if offset >= self.data.len() {
+                return Err("truncated section header".to_owned());
+            }
+            let header = if self.is_64 {
+                self.u32_at(offset).zip(self.u32_at(offset + 4)).zip(
+                    self.u64_at(offset + 8)
+                        .zip(self.u64_at(offset + 24).zip(self.u64_at(offset + 32))),
+                )
+            } else {
+                self.u32_at(offset).zip(self.u32_at(offset + 4)).zip(
+                    self.u32_at(offset + 8).map(u64::from).zip(
+                        self.u32_at(offset + 16)
+                            .map(u64::from)
+                            .zip(self.u32_at(offset + 20).map(u64::from)),
+                    ),
+                )
+            };
+            let Some(((name, kind), (flags, (offset, size)))) = header else {
+                return Err("truncated section header".to_owned());
+            };
+            names.push(name);
+            sections.push(Section {
+                name: String::new(),
+                kind,
+                flags,
+                offset,
+                size,
+            });
+        }
+
+        // The names are stored in the string table section.
+        if let Some(strings) = sections
+            .get(usize::from(shstrndx))
+            .and_then(|table| self.section_data(table))
+        {
+            for (section, name) in sections.iter_mut().zip(names) {
+                let start = strings.get(name as usize..).unwrap_or_default();
+                let end = start.iter().position(|&b| b == 0).unwrap_or(start.len());
+                section.name = String::from_utf8_lossy(&start[..end]).into_owned();
+            }
+        }
+        Ok(sections)
+    }
+
+    /// The content of the given segment stored in the file.
+    pub fn segment_data(&self, segment: &Segment) -> Option<&[u8]> {
+        let start = usize::try_from(segment.offset).ok()?;
+        let end = start.checked_add(usize::try_from(segment.filesz).ok()?)?;
+        self.data.get(start..end)
+    }
+
+    /// The content of the given section, `None` if it is not stored in the file.
+    pub fn section_data(&self, section: &Section) -> Option<&[u8]> {
+        if section.kind == SHT_NOBITS {
+            return None;
+        }
+        let start = usize::try_from(section.offset).ok()?;
+        let end = start.checked_add(usize::try_from(section.size).ok()?)?;
+        self.data.get(start..end)
+    }
+
+    /// The sizes of the text, data and bss of the program.
+    pub fn section_sizes(&self) -> Result<SectionSizes, String> {
+        let mut sizes = SectionSizes::default();
+        for section in self.sections()? {
+            if section.flags & SHF_ALLOC == 0 {
+                continue;
+            }
+            if section.kind == SHT_NOBITS {
+                sizes.bss += section.size;
+            } else if section.flags & SHF_WRITE != 0 {
+                sizes.data += section.size;
+            } else {
+                sizes.text += section.size;
+            }
+        }
+        Ok(sizes)
+    }
+
+    /// The bytes at the given offset, `None` if the file is too short.
+    fn bytes_at<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        let mut bytes: [u8; N] = self
+            .data
+            .get(offset..offset.checked_add(N)?)?
+            .try_into()
+            .ok()?;
+        if !self.little_endian {
+            bytes.reverse();
+        }
+        Some(bytes)
+    }
+
+    /// The half word at the given offset.
+    fn u16_at(&self, offset: usize) -> Option<u16> {
+        self.bytes_at(offset).map(u16::from_le_bytes)
+    }
+
+    /// The word at the given offset.
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        self.bytes_at(offset).map(u32::from_le_bytes)
+    }
+
+    /// The double word at the given offset.
+    fn u64_at(&self, offset: usize) -> Option<u64> {
+        self.bytes_at(offset).map(u64::from_le_bytes)
+    }
+}
+
+/// The name of the given machine shown to the user.
+pub fn machine_name(machine: u16) -> Cow<'static, str> {
+    match machine {
+        EM_AVR => "AVR".into(),
+        EM_ARM => "ARM".into(),
+        EM_XTENSA => "Xtensa".into(),
+        3 => "x86".into(),
+        62 => "x86-64".into(),
+        183 => "AArch64".into(),
+        243 => "RISC-V".into(),
+        _ => format!("machine {}", machine).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the bytes of an ELF file field by field.
+    struct Builder {
+        /// The bytes so far.
+        data: Vec<u8>,
+        /// Wether the file is a 64 bit ELF.
+        is_64: bool,
+        /// Wether the fields are written little endian.
+        little_endian: bool,
+    }
+
+    impl Builder {
+        /// The header of an ELF file with the given class and encoding for the given machine.
+        fn new(is_64: bool, little_endian: bool, machine: u16) -> Self {
+            let mut data = b"\x7fELF".to_vec();
+            data.extend([1 + u8::from(is_64), 2 - u8::from(little_endian), 1]);
+            let mut builder = Self {
+                data,
+                is_64,
+                little_endian,
+            };
+            builder.put(18, 2, machine.into());
+            builder.put(if is_64 { 63 } else { 51 }, 1, 0);
+            builder
+        }
+
+        /// Write the given value with the given number of bytes at the given offset.
+        fn put(&mut self, offset: usize, width: usize, value: u64) {
+            if self.data.len() < offset + width {
+                self.data.resize(offset + width, 0);
+            }
+            let bytes = value.to_le_bytes();
+            let field = &mut self.data[offset..offset + width];
+            field.copy_from_slice(&bytes[..width]);
+            if !self.little_endian {
+                field.reverse();
+            }
+        }
+
+        /// Write an address or offset, which is as wide as the class of the file.
+        fn put_word(&mut self, offset32: usize, offset64: usize, value: u64) {
+            if self.is_64 {
+                self.put(offset64, 8, value);
+            } else {
+                self.put(offset32, 4, value);
+            }
+        }
+
+        /// Append program headers of the given types, physical addresses and sizes.
+        fn segments(mut self, segments: &[(u32, u64, u64)]) -> Self {
+            let phoff = self.data.len() as u64;
+            let phentsize = if self.is_64 { 56 } else { 32 };
+            self.put_word(28, 32, phoff);
+            self.put(if self.is_64 { 54 } else { 42 }, 2, phentsize as u64);
+            self.put(if self.is_64 { 56 } else { 44 }, 2, segments.len() as u64);
+            for (i, &(kind, paddr, filesz)) in segments.iter().enumerate() {
+                let header = phoff as usize + i * phentsize;
+                self.put(header, 4, kind.into());
+                self.put_word(header + 12, header + 24, paddr);
+                self.put_word(header + 16, header + 32, filesz);
+                self.put(header + phentsize - 1, 1, 0);
+            }
+            self
+        }
+
+        /// Append the given sections, each with its name, type, flags and size, followed by the
+        /// string table of their names.
+        fn sections(mut self, sections: &[(&str, u32, u64, u64)]) -> Self {
+            let mut strings = vec![0];
+            let mut names = Vec::new();
+            for (name, ..) in sections {
+                names.push(strings.len() as u64);
+                strings.extend_from_slice(name.as_bytes());
+                strings.push(0);
+            }
+            let strings_name = strings.len() as u64;
+            strings.extend_from_slice(b".shstrtab\0");
+            let strings_offset = self.data.len() as u64;
+            self.data.extend_from_slice(&strings);
+
+            let shoff = self.data.len() as u64;
+            let shentsize = if self.is_64 { 64 } else { 40 };
+            self.put_word(32, 40, shoff);
+            self.put(if self.is_64 { 58 } else { 46 }, 2, shentsize as u64);
+            self.put(
+                if self.is_64 { 60 } else { 48 },
+                2,
+                sections.len() as u64 + 1,
+            );
+            self.put(if self.is_64 { 62 } else { 50 }, 2, sections.len() as u64);
+            let table = (strings_name, 3, 0, strings_offset, strings.len() as u64);
+            let headers = sections
+                .iter()
+                .zip(names)
+                .map(|(&(_, kind, flags, size), name)| (name, kind, flags, 0, size))
+                .chain([table]);
+            for (i, (name, kind, flags, offset, size)) in headers.enumerate() {
+                let header = shoff as usize + i * shentsize;
+                self.put(header, 4, name);
+                self.put(header + 4, 4, kind.into());
+                self.put_word(header + 8, header + 8, flags);
+                self.put_word(header + 16, header + 24, offset);
+                self.put_word(header + 20, header + 32, size);
+                self.put(header + shentsize - 1, 1, 0);
+            }
+            self
+        }
+
+        /// Parse the built file.
+        fn parse(self) -> ElfFile {
+            ElfFile::parse(self.data).unwrap()
+        }
+    }
+
+    /// The physical addresses and sizes of the loadable segments of the given file.
+    fn loaded(elf: &ElfFile) -> Vec<(u64, u64)> {
+        elf.load_segments()
+            .unwrap()
+            .iter()
+            .map(|segment| (segment.paddr, segment.filesz))
+            .collect()
+    }
+
+    #[test]
+    fn all_classes_and_encodings() {
+        for is_64 in [false, true] {
+            for little_endian in [true, false] {
+                let elf = Builder::new(is_64, little_endian, EM_ARM)
+                    .segments(&[
+                        (PT_LOAD, 0x0800_0000, 0x1234),
+                        (6, 0, 0x20),
+                        (PT_LOAD, 0x2000_0000, 0x10),
+                    ])
+                    .parse();
+                assert_eq!(elf.machine(), EM_ARM);
+                assert_eq!(loaded(&elf), [(0x0800_0000, 0x1234), (0x2000_0000, 0x10)]);
+            }
+        }
+    }
+
+    #[test]
+    fn not_an_elf_file() {
+        assert!(ElfFile::parse(b":00000001FF\n".to_vec()).is_err());
+        let mut data = Builder::new(false, true, EM_AVR).data;
+        data[4] = 3;
+        assert_eq!(
+            ElfFile::parse(data).err().as_deref(),
+            Some("unknown ELF class 3")
+        );
+        let mut data = Builder::new(false, true, EM_AVR).data;
+        data[5] = 0;
+        assert!(ElfFile::parse(data).is_err());
+    }
+
+    #[test]
+    fn truncated_headers() {
+        let mut data = Builder::new(true, true, EM_XTENSA).data;
+        data.truncate(60);
+        assert_eq!(
+            ElfFile::parse(data).err().as_deref(),
+            Some("truncated ELF header")
+        );
+
+        let mut builder = Builder::new(false, true, EM_AVR).segments(&[(PT_LOAD, 0, 0x100)]);
+        builder.put(44, 2, 2);
+        assert_eq!(
+            builder.parse().load_segments().err().as_deref(),
+            Some("truncated program header")
+        );
+    }
+
+    #[test]
+    fn sections_and_sizes() {
+        for is_64 in [false, true] {
+            for little_endian in [true, false] {
+                let mut builder = Builder::new(is_64, little_endian, EM_AVR).sections(&[
+                    (".text", 1, SHF_ALLOC, 0x400),
+                    (".data", 1, SHF_ALLOC | SHF_WRITE, 0x20),
+                    (".bss", SHT_NOBITS, SHF_ALLOC | SHF_WRITE, 0x40),
+                    (".comment", 1, 0, 0x11),
+                ]);
+                builder.put_word(24, 24, 0x68);
+                let elf = builder.parse();
+                assert_eq!(elf.entry(), 0x68);
+                let names: Vec<String> = elf
+                    .sections()
+                    .unwrap()
+                    .into_iter()
+                    .map(|section| section.name)
+                    .collect();
+                assert_eq!(names, [".text", ".data", ".bss", ".comment", ".shstrtab"]);
+                assert_eq!(
+                    elf.section_sizes(),
+                    Ok(SectionSizes {
+                        text: 0x400,
+                        data: 0x20,
+                        bss: 0x40
+                    })
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_section_header() {
+        let mut builder = Builder::new(false, true, EM_AVR).sections(&[(".text", 1, SHF_ALLOC, 4)]);
+        builder.put(48, 2, 3);
+        assert_eq!(
+            builder.parse().sections().err().as_deref(),
+            Some("truncated section header")
+        );
+    }
+
+    #[test]
+    fn huge_header_offsets() {
+        let mut builder = Builder::new(true, true, EM_ARM).segments(&[(PT_LOAD, 0, 0x10)]);
+        builder.put(32, 8, u64::MAX - 8);
+        assert_eq!(
+            builder.parse().load_segments().err().as_deref(),
+            Some("truncated program header")
+        );
+
+        let mut builder = Builder::new(true, false, EM_ARM).sections(&[(".text", 1, SHF_ALLOC, 4)]);
+        builder.put(40, 8, u64::MAX - 8);
+        assert_eq!(
+            builder.parse().sections().err().as_deref(),
+            Some("truncated section header")
+        );
+
+        let mut builder = Builder::new(false, true, EM_AVR).segments(&[(PT_LOAD, 0, 0x10)]);
+        builder.put(42, 2, 0xFFFF);
+        builder.put(44, 2, 0xFFFF);
+        assert_eq!(
+            builder.parse().load_segments().err().as_deref(),
+            Some("truncated program header")
+        );
+    }
+}
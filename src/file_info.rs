@@ -0,0 +1,206 @@
+//! Information about the selected firmware file
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eframe::egui;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    firmware::{self, ElfSummary, FirmwareFormat},
+    hex::HexImage,
+};
+
+/// Information about a file, the hash is computed on a background thread.
+pub struct FileInfo {
+    /// The file the information is about.
+    pub path: PathBuf,
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// The time the file was last modified, `None` if the platform does not support it.
+    pub modified: Option<SystemTime>,
+    /// The SHA-256 digest of the file as hex, `None` while it is computed.
+    pub sha256: Option<Result<String, String>>,
+    /// Receives the digest once it is computed.
+    pending: Option<Receiver<Result<String, String>>>,
+    /// The parsed content if the file is an Intel HEX file, `None` for other files or while it is
+    /// parsed.
+    pub hex: Option<Result<HexImage, String>>,
+    /// Receives the parsed Intel HEX file once it is parsed.
+    pending_hex: Option<Receiver<Result<HexImage, String>>>,
+    /// The sections of the file if it is an ELF file, `None` for other files or while it is read.
+    pub elf: Option<Result<ElfSummary, String>>,
+    /// Receives the sections of the ELF file once they are read.
+    pending_elf: Option<Receiver<Result<ElfSummary, String>>>,
+}
+
+impl FileInfo {
+    /// Read the information about the given file and start hashing it, Intel HEX files are also
+    /// validated and the sections of ELF files read, the version string is searched in the given
+    /// section. The context is repainted when each of them is done.
+    pub fn load(path: &Path, version_section: &str, ctx: &egui::Context) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        let hashed = path.to_owned();
+        let pending = spawn(ctx, move || {
+            hash_file(&hashed)
+                .map_err(|e| format!("Error: could not hash {}: {}", hashed.display(), e))
+        });
+
+        let format = FirmwareFormat::detect(path);
+        let pending_hex = (format == Ok(FirmwareFormat::IntelHex)).then(|| {
+            let parsed = path.to_owned();
+            spawn(ctx, move || {
+                fs::read_to_string(&parsed)
+                    .map_err(|e| format!("Error: could not read {}: {}", parsed.display(), e))
+                    .and_then(|content| {
+                        HexImage::parse(&content).map_err(|e| {
+                            format!("Error: invalid Intel HEX file {}: {}", parsed.display(), e)
+                        })
+                    })
+            })
+        });
+        let pending_elf = (format == Ok(FirmwareFormat::Elf)).then(|| {
+            let (parsed, section) = (path.to_owned(), version_section.to_owned());
+            spawn(ctx, move || firmware::elf_summary(&parsed, &section))
+        });
+
+        Ok(Self {
+            path: path.to_owned(),
+            size: meta.len(),
+            modified: meta.modified().ok(),
+            sha256: None,
+            pending: Some(pending),
+            hex: None,
+            pending_hex,
+            elf: None,
+            pending_elf,
+        })
+    }
+
+    /// Receive the digest, the parsed Intel HEX file and the ELF sections if they are done.
+    pub fn poll(&mut self) {
+        if let Some(digest) = self.pending.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.sha256 = Some(digest);
+            self.pending = None;
+        }
+        if let Some(image) = self.pending_hex.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.hex = Some(image);
+            self.pending_hex = None;
+        }
+        if let Some(summary) = self.pending_elf.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.elf = Some(summary);
+            self.pending_elf = None;
+        }
+    }
+
+    /// Wether the Intel HEX file is still being parsed.
+    pub fn is_parsing_hex(&self) -> bool {
+        self.pending_hex.is_some()
+    }
+
+    /// Wether the file was modified on disk since the information was read.
+    pub fn is_outdated(&self) -> bool {
+        stamp(&self.path) != Some((self.size, self.modified))
+    }
+
+    /// A one line summary used in logs.
+    pub fn summary(&self) -> String {
+        let hash = match self.sha256 {
+            Some(Ok(ref hash)) => hash.as_str(),
+            Some(Err(_)) => "unknown",
+            None => "still computing",
+        };
+        format!(
+            "FILE: {} ({} bytes, modified {}, SHA-256 {})",
+            self.path.display(),
+            self.size,
+            self.modified.map(format_time).unwrap_or_default(),
+            hash
+        )
+    }
+}
+
+/// The size and modification time of a file.
+pub type Stamp = (u64, Option<SystemTime>);
+
+/// The size and modification time of the given file, `None` if it can't be read.
+pub fn stamp(path: &Path) -> Option<Stamp> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.len(), meta.modified().ok()))
+}
+
+/// Run the given function on a background thread, the context is repainted when it is done.
+fn spawn<T: Send + 'static>(
+    ctx: &egui::Context,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Receiver<T> {
+    let (sender, receiver) = mpsc::channel();
+    let ctx = ctx.clone();
+    thread::spawn(move || {
+        // The receiver is dropped when another file got selected in the meantime.
+        let _ = sender.send(f());
+        ctx.request_repaint();
+    });
+    receiver
+}
+
+/// Compute the SHA-256 digest of the given file.
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut sha = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut sha)?;
+    Ok(to_hex(&sha.finalize()))
+}
+
+/// Format the given digest as lowercase hex.
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Format the given time as UTC like "2023-04-01 13:37:00 UTC".
+pub fn format_time(time: SystemTime) -> String {
+    let [year, month, day, hour, minute, second] = civil_time(time);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Format the given time as UTC for a file name like "2023-04-01_13-37-00", which sorts like the
+/// times do.
+pub fn file_time(time: SystemTime) -> String {
+    let [year, month, day, hour, minute, second] = civil_time(time);
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// The year, month, day, hour, minute and second of the given time in UTC.
+fn civil_time(time: SystemTime) -> [i64; 6] {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+
+    // Converts the days since the epoch into a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let rest = rest as i64;
+    [year, month, day, rest / 3600, rest % 3600 / 60, rest % 60]
+}
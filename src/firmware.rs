@@ -0,0 +1,226 @@
+//! The formats of the firmware files which can be installed
+
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+};
+
+use crate::{
+    elf::{ElfFile, SectionSizes, EM_AVR},
+    hex::{self, Block, HexImage},
+};
+
+/// The magic bytes at the start of every ELF file.
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+/// The number of bytes read to detect the format of a file.
+const DETECT_LEN: usize = 512;
+
+/// The address from which on an AVR ELF contains the data, EEPROM and fuse sections, which are not
+/// written to the flash.
+const AVR_DATA_START: u64 = 0x80_0000;
+
+/// Enumeration of the firmware file formats avrdude can read
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FirmwareFormat {
+    /// An ELF executable
+    Elf,
+    /// An Intel HEX file
+    IntelHex,
+    /// A raw binary image written to the start of the flash
+    Raw,
+    /// A format avrdude has to detect on its own
+    Auto,
+}
+
+impl FirmwareFormat {
+    /// The format of the given file according to its content, empty files and files which look
+    /// like an Intel HEX file without being one are an error.
+    pub fn detect(path: &Path) -> Result<Self, String> {
+        let mut start = Vec::with_capacity(DETECT_LEN);
+        File::open(path)
+            .and_then(|file| file.take(DETECT_LEN as u64).read_to_end(&mut start))
+            .map_err(|e: io::Error| format!("Error: could not read {}: {}", path.display(), e))?;
+
+        if start.is_empty() {
+            return Err(format!("Error: {} is empty", path.display()));
+        }
+        if start.starts_with(ELF_MAGIC) {
+            return Ok(Self::Elf);
+        }
+        if start.starts_with(b":") {
+            // Only the first line is checked, it may be cut off if it is longer than the read bytes.
+            let first = start.split(|&b| b == b'\n').next().unwrap_or_default();
+            let first = String::from_utf8_lossy(first);
+            if first.len() == start.len() || hex::valid_record(first.trim_end()) {
+                return Ok(Self::IntelHex);
+            }
+            return Err(format!(
+                "Error: {} starts like an Intel HEX file but its first record is invalid",
+                path.display()
+            ));
+        }
+        Ok(Self::Raw)
+    }
+
+    /// The format of the given file according to its extension.
+    pub fn from_extension(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("elf") => Self::Elf,
+            Some("hex" | "ihex" | "ihx" | "eep") => Self::IntelHex,
+            Some("bin") => Self::Raw,
+            _ => Self::Auto,
+        }
+    }
+
+    /// The name shown to the user.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Elf => "ELF",
+            Self::IntelHex => "Intel HEX",
+            Self::Raw => "raw binary",
+            Self::Auto => "unknown format",
+        }
+    }
+
+    /// The format suffix of an avrdude memory operation.
+    pub fn avrdude_suffix(self) -> &'static str {
+        match self {
+            Self::Elf => "e",
+            Self::IntelHex => "i",
+            Self::Raw => "r",
+            Self::Auto => "a",
+        }
+    }
+}
+
+/// The sections of an ELF file which are interesting before flashing it
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ElfSummary {
+    /// The address execution starts at.
+    pub entry: u64,
+    /// The sizes of the program like `avr-size` shows them.
+    pub sizes: SectionSizes,
+    /// The version string embedded in the firmware, `None` if it has no version section.
+    pub version: Option<String>,
+}
+
+/// The sections searched for a version string after the configured one.
+const VERSION_SECTIONS: [&str; 2] = [".version", ".note"];
+
+/// Read the entry point, the section sizes and the version string from the sections of the given
+/// ELF file, the version is searched in the given section before common version sections.
+pub fn elf_summary(path: &Path, version_section: &str) -> Result<ElfSummary, String> {
+    let invalid = |e| format!("Error: invalid ELF file {}: {}", path.display(), e);
+    let data =
+        fs::read(path).map_err(|e| format!("Error: could not read {}: {}", path.display(), e))?;
+    let elf = ElfFile::parse(data).map_err(invalid)?;
+    let sections = elf.sections().map_err(invalid)?;
+
+    let version = std::iter::once(version_section.trim())
+        .chain(VERSION_SECTIONS)
+        .filter(|name| !name.is_empty())
+        .find_map(|name| {
+            let section = sections.iter().find(|section| section.name == name)?;
+            printable(elf.section_data(section)?)
+        });
+    Ok(ElfSummary {
+        entry: elf.entry(),
+        sizes: elf.section_sizes().map_err(invalid)?,
+        version,
+    })
+}
+
+/// The printable parts of the given bytes joined by spaces, `None` if there are none.
+fn printable(bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes);
+    let parts: Vec<&str> = text
+        .split(|c: char| c.is_control() || c == char::REPLACEMENT_CHARACTER)
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+/// The ELF machine the given ELF file was built for.
+pub fn elf_machine(path: &Path) -> Result<u16, String> {
+    let mut header = Vec::with_capacity(64);
+    File::open(path)
+        .and_then(|file| file.take(64).read_to_end(&mut header))
+        .map_err(|e: io::Error| format!("Error: could not read {}: {}", path.display(), e))?;
+    ElfFile::parse(header)
+        .map(|elf| elf.machine())
+        .map_err(|e| format!("Error: invalid ELF file {}: {}", path.display(), e))
+}
+
+/// The bytes the given program writes to the flash, sorted by their address.
+pub fn flash_image(path: &Path, format: FirmwareFormat) -> Result<Vec<Block>, String> {
+    let read_error = |e: io::Error| format!("Error: could not read {}: {}", path.display(), e);
+    match format {
+        FirmwareFormat::Elf => {
+            let invalid = |e| format!("Error: invalid ELF file {}: {}", path.display(), e);
+            let elf = ElfFile::parse(fs::read(path).map_err(read_error)?).map_err(invalid)?;
+            let mut blocks = Vec::new();
+            for segment in elf.load_segments().map_err(invalid)? {
+                if segment.filesz == 0
+                    || (elf.machine() == EM_AVR && segment.paddr >= AVR_DATA_START)
+                {
+                    continue;
+                }
+                let data = elf
+                    .segment_data(&segment)
+                    .ok_or_else(|| invalid("segment out of range".to_owned()))?;
+                let address = u32::try_from(segment.paddr)
+                    .map_err(|_| invalid("segment address out of range".to_owned()))?;
+                blocks.push(Block {
+                    address,
+                    data: data.to_vec(),
+                });
+            }
+            blocks.sort_by_key(|block| block.address);
+            Ok(blocks)
+        }
+        FirmwareFormat::IntelHex => {
+            let content = fs::read_to_string(path).map_err(read_error)?;
+            HexImage::parse(&content)
+                .map(|image| image.blocks)
+                .map_err(|e| format!("Error: invalid Intel HEX file {}: {}", path.display(), e))
+        }
+        FirmwareFormat::Raw | FirmwareFormat::Auto => Ok(vec![Block {
+            address: 0,
+            data: fs::read(path).map_err(read_error)?,
+        }]),
+    }
+}
+
+/// The number of bytes the given program occupies in the flash.
+pub fn program_size(path: &Path, format: FirmwareFormat) -> Result<u64, String> {
+    let read_error = |e: io::Error| format!("Error: could not read {}: {}", path.display(), e);
+    match format {
+        FirmwareFormat::Elf => {
+            let elf = ElfFile::parse(fs::read(path).map_err(read_error)?)
+                .map_err(|e| format!("Error: invalid ELF file {}: {}", path.display(), e))?;
+            let segments = elf
+                .load_segments()
+                .map_err(|e| format!("Error: invalid ELF file {}: {}", path.display(), e))?;
+            Ok(segments
+                .iter()
+                .filter(|segment| elf.machine() != EM_AVR || segment.paddr < AVR_DATA_START)
+                .map(|segment| segment.filesz)
+                .sum())
+        }
+        FirmwareFormat::IntelHex => {
+            let content = fs::read_to_string(path).map_err(read_error)?;
+            HexImage::parse(&content)
+                .map(|image| image.size())
+                .map_err(|e| format!("Error: invalid Intel HEX file {}: {}", path.display(), e))
+        }
+        FirmwareFormat::Raw | FirmwareFormat::Auto => fs::metadata(path)
+            .map(|meta| meta.len())
+            .map_err(read_error),
+    }
+}
@@ -0,0 +1,645 @@
+//! Reading, decoding and writing the fuse and lock bytes of classic AVR chips
+
+use eframe::egui;
+
+use crate::board::UPDI_PARTS;
+
+/// The fuses of the Uno and the Nano as shipped with optiboot.
+pub const OPTIBOOT_328_PRESETS: &[FusePreset] = &[
+    FusePreset::new("Uno factory", 0xFF, 0xDE, 0xFD),
+    FusePreset::new("Internal 8 MHz", 0xE2, 0xDE, 0xFD),
+];
+
+/// The fuses of the boards shipped with the old 2 KB bootloader.
+pub const ATMEGABOOT_328_PRESETS: &[FusePreset] = &[
+    FusePreset::new("Old Nano factory", 0xFF, 0xDA, 0xFD),
+    FusePreset::new("Internal 8 MHz", 0xE2, 0xDA, 0xFD),
+];
+
+/// The fuses of the Mega 2560.
+pub const MEGA_2560_PRESETS: &[FusePreset] = &[FusePreset::new("Mega factory", 0xFF, 0xD8, 0xFD)];
+
+/// The fuses of the Leonardo and the Micro.
+pub const CATERINA_PRESETS: &[FusePreset] =
+    &[FusePreset::new("Leonardo factory", 0xFF, 0xD8, 0xCB)];
+
+/// The fuses of a bare ATtiny85.
+pub const ATTINY85_PRESETS: &[FusePreset] = &[
+    FusePreset::new("Factory (internal 1 MHz)", 0x62, 0xDF, 0xFF),
+    FusePreset::new("Internal 8 MHz", 0xE2, 0xDF, 0xFF),
+    FusePreset::new("Internal 16 MHz PLL", 0xF1, 0xDF, 0xFF),
+];
+
+/// The fuse bytes of most chips.
+const THREE_FUSES: &[&str] = &["lfuse", "hfuse", "efuse"];
+
+/// The fuse bytes of the older chips without an extended fuse.
+const TWO_FUSES: &[&str] = &["lfuse", "hfuse"];
+
+/// The partnos of the chips without an extended fuse.
+const NO_EFUSE: &[&str] = &["atmega8", "atmega16", "atmega32", "atmega8535", "attiny13"];
+
+/// The fields of the low fuse of the ATmega328P and the ATmega2560, which select the clock.
+const CLOCK_FIELDS: &[FuseField] = &[
+    FuseField::flag(
+        "CKDIV8",
+        7,
+        &[
+            (0, "The clock is divided by 8"),
+            (1, "The clock is not divided"),
+        ],
+    ),
+    FuseField::flag(
+        "CKOUT",
+        6,
+        &[
+            (0, "The clock is output on CLKO"),
+            (1, "The clock is not output"),
+        ],
+    ),
+    FuseField::new(
+        "SUT",
+        "Start-up time, depending on the clock source",
+        4,
+        2,
+        &[
+            (0b00, "Shortest"),
+            (0b01, "Short"),
+            (0b10, "Long"),
+            (0b11, "Longest"),
+        ],
+    ),
+    FuseField::new(
+        "CKSEL",
+        "Clock source",
+        0,
+        4,
+        &[
+            (0x0, "External clock"),
+            (0x2, "Internal 8 MHz RC oscillator"),
+            (0x3, "Internal 128 kHz oscillator"),
+            (0x4, "Low frequency crystal (CKSEL0=0)"),
+            (0x5, "Low frequency crystal (CKSEL0=1)"),
+            (0x6, "Full swing crystal (CKSEL0=0)"),
+            (0x7, "Full swing crystal (CKSEL0=1)"),
+            (0x8, "Low power crystal 0.4-0.9 MHz (CKSEL0=0)"),
+            (0x9, "Low power crystal 0.4-0.9 MHz (CKSEL0=1)"),
+            (0xA, "Low power crystal 0.9-3 MHz (CKSEL0=0)"),
+            (0xB, "Low power crystal 0.9-3 MHz (CKSEL0=1)"),
+            (0xC, "Low power crystal 3-8 MHz (CKSEL0=0)"),
+            (0xD, "Low power crystal 3-8 MHz (CKSEL0=1)"),
+            (0xE, "Low power crystal 8-16 MHz (CKSEL0=0)"),
+            (0xF, "Low power crystal 8-16 MHz (CKSEL0=1)"),
+        ],
+    ),
+];
+
+/// The SPIEN bit of the high fuse.
+const SPIEN: FuseField = FuseField::flag(
+    "SPIEN",
+    5,
+    &[
+        (0, "Programming over ISP is enabled"),
+        (1, "Programming over ISP is disabled"),
+    ],
+);
+
+/// The WDTON bit of the high fuse.
+const WDTON: FuseField = FuseField::flag(
+    "WDTON",
+    4,
+    &[
+        (0, "The watchdog is always on"),
+        (1, "The watchdog is controlled by the program"),
+    ],
+);
+
+/// The EESAVE bit of the high fuse.
+const EESAVE: FuseField = FuseField::flag(
+    "EESAVE",
+    3,
+    &[
+        (0, "The EEPROM is kept by a chip erase"),
+        (1, "The EEPROM is cleared by a chip erase"),
+    ],
+);
+
+/// The BOOTRST bit of the high fuse.
+const BOOTRST: FuseField = FuseField::flag(
+    "BOOTRST",
+    0,
+    &[
+        (0, "Reset starts the bootloader"),
+        (1, "Reset starts the application"),
+    ],
+);
+
+/// The fields of the high fuse of the ATmega328P.
+const HIGH_FUSE_328P: &[FuseField] = &[
+    FuseField::flag(
+        "RSTDISBL",
+        7,
+        &[
+            (0, "The reset pin is an io pin, ISP no longer works"),
+            (1, "The reset pin resets the chip"),
+        ],
+    ),
+    FuseField::flag(
+        "DWEN",
+        6,
+        &[
+            (0, "debugWIRE is enabled, ISP no longer works"),
+            (1, "debugWIRE is disabled"),
+        ],
+    ),
+    SPIEN,
+    WDTON,
+    EESAVE,
+    FuseField::new(
+        "BOOTSZ",
+        "Bootloader size",
+        1,
+        2,
+        &[
+            (0b11, "512 bytes"),
+            (0b10, "1 KB"),
+            (0b01, "2 KB"),
+            (0b00, "4 KB"),
+        ],
+    ),
+    BOOTRST,
+];
+
+/// The fields of the high fuse of the ATmega2560.
+const HIGH_FUSE_2560: &[FuseField] = &[
+    FuseField::flag(
+        "OCDEN",
+        7,
+        &[
+            (0, "On chip debugging is enabled"),
+            (1, "On chip debugging is disabled"),
+        ],
+    ),
+    FuseField::flag(
+        "JTAGEN",
+        6,
+        &[(0, "JTAG is enabled"), (1, "JTAG is disabled")],
+    ),
+    SPIEN,
+    WDTON,
+    EESAVE,
+    FuseField::new(
+        "BOOTSZ",
+        "Bootloader size",
+        1,
+        2,
+        &[
+            (0b11, "1 KB"),
+            (0b10, "2 KB"),
+            (0b01, "4 KB"),
+            (0b00, "8 KB"),
+        ],
+    ),
+    BOOTRST,
+];
+
+/// The fields of the extended fuse of the ATmega328P and the ATmega2560.
+const BROWN_OUT_FIELDS: &[FuseField] = &[FuseField::new(
+    "BODLEVEL",
+    "Brown-out detection",
+    0,
+    3,
+    &[
+        (0b111, "Disabled"),
+        (0b110, "1.8 V"),
+        (0b101, "2.7 V"),
+        (0b100, "4.3 V"),
+    ],
+)];
+
+/// The name of the lock byte.
+const LOCK: &[&str] = &["lock"];
+
+/// The lock bits protecting the flash and the EEPROM from being read out over ISP.
+const MEMORY_LOCK: FuseField = FuseField::new(
+    "LB",
+    "Memory lock",
+    0,
+    2,
+    &[
+        (0b11, "No lock"),
+        (0b10, "Programming the flash and the EEPROM is disabled"),
+        (
+            0b00,
+            "Programming and reading the flash and the EEPROM is disabled",
+        ),
+    ],
+);
+
+/// The lock bits of the ATmega chips, which can also protect the sections from the program.
+const ATMEGA_LOCK_FIELDS: &[FuseField] = &[
+    FuseField::new(
+        "BLB1",
+        "Boot section",
+        4,
+        2,
+        &[
+            (0b11, "No restrictions"),
+            (0b10, "SPM can't write the boot section"),
+            (
+                0b01,
+                "LPM in the application section can't read the boot section",
+            ),
+            (
+                0b00,
+                "SPM can't write and the application section can't read the boot section",
+            ),
+        ],
+    ),
+    FuseField::new(
+        "BLB0",
+        "Application section",
+        2,
+        2,
+        &[
+            (0b11, "No restrictions"),
+            (0b10, "SPM can't write the application section"),
+            (
+                0b01,
+                "LPM in the boot section can't read the application section",
+            ),
+            (
+                0b00,
+                "SPM can't write and the boot section can't read the application section",
+            ),
+        ],
+    ),
+    MEMORY_LOCK,
+];
+
+/// The lock bits of the ATtiny chips.
+const ATTINY_LOCK_FIELDS: &[FuseField] = &[MEMORY_LOCK];
+
+/// The fields of the fuses of the chips which can be decoded, by partno and in the order of the
+/// fuses of their layout.
+const FUSE_TABLES: &[(&str, &[&[FuseField]])] = &[
+    (
+        "atmega328p",
+        &[CLOCK_FIELDS, HIGH_FUSE_328P, BROWN_OUT_FIELDS],
+    ),
+    (
+        "atmega2560",
+        &[CLOCK_FIELDS, HIGH_FUSE_2560, BROWN_OUT_FIELDS],
+    ),
+];
+
+/// The fuses of a chip
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FuseLayout {
+    /// The avrdude names of the fuse bytes, in the order they are read.
+    pub names: &'static [&'static str],
+    /// The fields of each fuse byte, `None` if only the raw values can be shown.
+    fields: Option<&'static [&'static [FuseField]]>,
+}
+
+/// A group of bits of a fuse byte
+#[derive(Debug, PartialEq, Eq)]
+pub struct FuseField {
+    /// The name of the field in the datasheet.
+    name: &'static str,
+    /// What the field controls.
+    label: &'static str,
+    /// The position of the lowest bit of the field.
+    shift: u8,
+    /// The number of bits of the field.
+    width: u8,
+    /// The meaning of the values of the field, values which are missing are reserved.
+    values: &'static [(u8, &'static str)],
+}
+
+impl FuseField {
+    /// Create a field of the given bits.
+    const fn new(
+        name: &'static str,
+        label: &'static str,
+        shift: u8,
+        width: u8,
+        values: &'static [(u8, &'static str)],
+    ) -> Self {
+        Self {
+            name,
+            label,
+            shift,
+            width,
+            values,
+        }
+    }
+
+    /// Create a field of a single bit, it is programmed when the bit is 0.
+    const fn flag(name: &'static str, bit: u8, values: &'static [(u8, &'static str); 2]) -> Self {
+        Self::new(name, name, bit, 1, values)
+    }
+
+    /// The mask of the bits of the field, before shifting it.
+    fn mask(&self) -> u8 {
+        (1 << self.width) - 1
+    }
+
+    /// The value of the field in the given fuse byte.
+    fn get(&self, fuse: u8) -> u8 {
+        (fuse >> self.shift) & self.mask()
+    }
+
+    /// The given fuse byte with the field set to the given value.
+    fn set(&self, fuse: u8, value: u8) -> u8 {
+        fuse & !(self.mask() << self.shift) | (value & self.mask()) << self.shift
+    }
+
+    /// The meaning of the given value of the field.
+    fn describe(&self, value: u8) -> String {
+        match self.values.iter().find(|&&(v, _)| v == value) {
+            Some((_, meaning)) => (*meaning).to_owned(),
+            None => format!(
+                "Reserved (0b{:0width$b})",
+                value,
+                width = usize::from(self.width)
+            ),
+        }
+    }
+}
+
+/// Named fuse values known to be safe for a board
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FusePreset {
+    /// The name shown to the user.
+    pub name: &'static str,
+    /// The low, high and extended fuse byte, the extended one is ignored by chips without it.
+    pub values: [u8; 3],
+}
+
+impl FusePreset {
+    /// Create a preset with the given low, high and extended fuse byte.
+    const fn new(name: &'static str, lfuse: u8, hfuse: u8, efuse: u8) -> Self {
+        Self {
+            name,
+            values: [lfuse, hfuse, efuse],
+        }
+    }
+}
+
+/// The warning shown before fuses are written.
+pub const FUSE_WARNING: &str =
+    "Wrong fuses can make the chip unusable, for example a clock source \
+    which is not connected needs an external clock to recover it.";
+
+/// The warning shown before lock bits are written.
+pub const LOCK_WARNING: &str = "Lock bits can't be cleared again without a chip erase, which also \
+    erases the whole flash and usually the EEPROM. Until then the locked memories can't be \
+    written or read back, not even by the bootloader.";
+
+/// The fuse values entered by the user and written through an ISP programmer
+#[derive(Debug, Default)]
+pub struct FuseEditor {
+    /// The hex values entered for the fuses, in the order of the layout.
+    values: [String; 3],
+    /// The values the user is asked to confirm writing.
+    confirm: Option<[u8; 3]>,
+}
+
+impl FuseLayout {
+    /// The lock byte of the chip with the given partno.
+    pub fn lock(partno: &str) -> Result<Self, String> {
+        let partno = partno.to_ascii_lowercase();
+        if UPDI_PARTS.contains(&partno.as_str()) {
+            return Err(format!(
+                "Error: the lock bits of UPDI chips like the {} are not supported",
+                partno
+            ));
+        }
+        let fields: Option<&'static [&'static [FuseField]]> = if partno.starts_with("atmega") {
+            Some(&[ATMEGA_LOCK_FIELDS])
+        } else if partno.starts_with("attiny") {
+            Some(&[ATTINY_LOCK_FIELDS])
+        } else {
+            None
+        };
+        Ok(Self {
+            names: LOCK,
+            fields,
+        })
+    }
+
+    /// Wether this is the lock byte instead of the fuses.
+    pub fn is_lock(&self) -> bool {
+        self.names == LOCK
+    }
+
+    /// The fuses of the chip with the given partno.
+    pub fn of(partno: &str) -> Result<Self, String> {
+        let partno = partno.to_ascii_lowercase();
+        if UPDI_PARTS.contains(&partno.as_str()) {
+            return Err(format!(
+                "Error: reading the fuses of UPDI chips like the {} is not supported",
+                partno
+            ));
+        }
+        let names = if NO_EFUSE.contains(&partno.as_str()) {
+            TWO_FUSES
+        } else {
+            THREE_FUSES
+        };
+        let fields = FUSE_TABLES
+            .iter()
+            .find(|(table_partno, _)| *table_partno == partno)
+            .map(|&(_, fields)| fields);
+        Ok(Self { names, fields })
+    }
+
+    /// Describe the settings of the given fuse values, empty if the chip has no decode table.
+    pub fn decode(&self, values: &[u8]) -> Vec<String> {
+        let Some(fields) = self.fields else {
+            return Vec::new();
+        };
+        fields
+            .iter()
+            .zip(values)
+            .flat_map(|(fields, &fuse)| {
+                fields.iter().map(move |field| {
+                    format!("{}: {}", field.label, field.describe(field.get(fuse)))
+                })
+            })
+            .collect()
+    }
+}
+
+impl FuseEditor {
+    /// Show the presets and the values of the given layout, returns the values once the user
+    /// confirmed writing them. Writing is disabled with the given reason if it is not possible.
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        layout: &FuseLayout,
+        presets: &[FusePreset],
+        blocked: Option<&str>,
+    ) -> Option<[u8; 3]> {
+        if !presets.is_empty() {
+            ui.horizontal(|ui| {
+                ui.label("Presets: ");
+                for preset in presets {
+                    let hover = layout
+                        .names
+                        .iter()
+                        .zip(preset.values)
+                        .map(|(name, value)| format!("{}=0x{:02X}", name, value))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    if ui.button(preset.name).on_hover_text(hover).clicked() {
+                        self.set_values(&preset.values);
+                    }
+                }
+            });
+        }
+        egui::Grid::new(("FuseEditor", layout.names)).show(ui, |ui| {
+            for (name, value) in layout.names.iter().zip(&mut self.values) {
+                ui.label(format!("{}: ", name));
+                ui.add(egui::TextEdit::singleline(value).hint_text("0xFF"));
+                ui.end_row();
+            }
+        });
+
+        if let Some(fields) = layout.fields {
+            egui::CollapsingHeader::new("Calculator").show(ui, |ui| {
+                self.calculator_ui(ui, layout, fields);
+            });
+        }
+
+        let parsed = self.values(layout);
+        let entered = self.values.iter().any(|value| !value.trim().is_empty());
+        if let (Err(ref e), true) = (&parsed, entered) {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+        if let Some(reason) = blocked {
+            ui.colored_label(egui::Color32::YELLOW, reason);
+        }
+        let write = ui.add_enabled(
+            blocked.is_none() && parsed.is_ok(),
+            egui::Button::new(if layout.is_lock() {
+                "Write lock bits..."
+            } else {
+                "Write fuses..."
+            }),
+        );
+        if write.clicked() {
+            self.confirm = parsed.ok();
+        }
+
+        let values = self.confirm?;
+        let mut confirmed = None;
+        let title = if layout.is_lock() {
+            "Write lock bits?"
+        } else {
+            "Write fuses?"
+        };
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ui.ctx(), |ui| {
+                ui.label("These bytes are written:");
+                for (name, value) in layout.names.iter().zip(values) {
+                    ui.monospace(format!("{} = 0x{:02X}", name, value));
+                }
+                if layout.is_lock() {
+                    ui.label(LOCK_WARNING);
+                } else {
+                    ui.label(FUSE_WARNING);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Write").clicked() {
+                        self.confirm = None;
+                        confirmed = Some(values);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm = None;
+                    }
+                });
+            });
+        confirmed
+    }
+
+    /// Show the fields of each fuse, changing a field changes the entered value of its fuse.
+    fn calculator_ui(&mut self, ui: &mut egui::Ui, layout: &FuseLayout, fields: &[&[FuseField]]) {
+        for ((name, fields), entered) in layout.names.iter().zip(fields).zip(&mut self.values) {
+            // Values which can't be parsed are shown as the erased state of the fuse.
+            let before = parse_byte(entered).unwrap_or(0xFF);
+            let mut fuse = before;
+            ui.label(egui::RichText::new(*name).strong());
+            egui::Grid::new(("FuseCalculator", *name)).show(ui, |ui| {
+                for field in fields.iter() {
+                    let value = field.get(fuse);
+                    if field.width == 1 {
+                        // A programmed bit is 0.
+                        let mut programmed = value == 0;
+                        ui.checkbox(&mut programmed, field.name);
+                        ui.label(field.describe(u8::from(!programmed)));
+                        fuse = field.set(fuse, u8::from(!programmed));
+                    } else {
+                        ui.label(field.label);
+                        let mut selected = value;
+                        egui::ComboBox::from_id_source(("FuseField", *name, field.name))
+                            .selected_text(field.describe(value))
+                            .width(260.0)
+                            .show_ui(ui, |ui| {
+                                for &(option, meaning) in field.values {
+                                    ui.selectable_value(&mut selected, option, meaning);
+                                }
+                            });
+                        fuse = field.set(fuse, selected);
+                    }
+                    ui.end_row();
+                }
+            });
+            if fuse != before {
+                *entered = format!("0x{:02X}", fuse);
+            }
+        }
+    }
+
+    /// Replace the entered values with the given ones.
+    pub fn set_values(&mut self, values: &[u8]) {
+        for (entered, value) in self.values.iter_mut().zip(values) {
+            *entered = format!("0x{:02X}", value);
+        }
+    }
+
+    /// The entered values of the fuses of the given layout, the unused ones are 0xFF.
+    pub fn values(&self, layout: &FuseLayout) -> Result<[u8; 3], String> {
+        let mut values = [0xFF; 3];
+        for ((name, entered), value) in layout.names.iter().zip(&self.values).zip(&mut values) {
+            *value = parse_byte(entered)
+                .ok_or_else(|| format!("Error: {} is not a hex byte like 0xFF", name))?;
+        }
+        Ok(values)
+    }
+}
+
+/// Parse a hex byte entered by the user, with or without `0x`.
+fn parse_byte(entered: &str) -> Option<u8> {
+    let digits = entered.trim();
+    let digits = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+        .unwrap_or(digits);
+    u8::from_str_radix(digits, 16).ok()
+}
+
+/// Parse the values avrdude printed for `-U <fuse>:r:-:h`, one `0x..` value per line.
+pub fn parse_values(stdout: &str) -> Vec<u8> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let digits = line.trim().strip_prefix("0x")?;
+            u8::from_str_radix(digits, 16).ok()
+        })
+        .collect()
+}
@@ -0,0 +1,241 @@
+//! Parsing and validating Intel HEX files
+
+/// A data record.
+const DATA: u8 = 0x00;
+/// The end of file record.
+const END_OF_FILE: u8 = 0x01;
+/// A record setting bits 4 to 19 of the following addresses.
+const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+/// The start address of a 80x86 program.
+const START_SEGMENT_ADDRESS: u8 = 0x03;
+/// A record setting bits 16 to 31 of the following addresses.
+const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+/// The start address of a 32 bit program.
+const START_LINEAR_ADDRESS: u8 = 0x05;
+
+/// The content of an Intel HEX file
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct HexImage {
+    /// The contiguous blocks of data, sorted by their address.
+    pub blocks: Vec<Block>,
+}
+
+/// Contiguous data at an address
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+    /// The address of the first byte.
+    pub address: u32,
+    /// The data.
+    pub data: Vec<u8>,
+}
+
+impl Block {
+    /// The address after the last byte.
+    pub fn end(&self) -> u64 {
+        u64::from(self.address) + self.data.len() as u64
+    }
+}
+
+impl HexImage {
+    /// Parse and validate a whole Intel HEX file, the errors name the record like
+    /// "record 1432: checksum mismatch".
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut base = 0u32;
+        let mut end_of_file = false;
+
+        let records = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        for (i, line) in records.enumerate() {
+            let error = |message: &str| format!("record {}: {}", i + 1, message);
+            if end_of_file {
+                return Err(error("record after the end of file record"));
+            }
+            let bytes = decode_record(line).map_err(|e| error(&e))?;
+            let (kind, data) = (bytes[3], &bytes[4..bytes.len() - 1]);
+            let offset = u32::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+
+            match kind {
+                DATA => {
+                    let address = base.wrapping_add(offset);
+                    // Most files are written in order, so the data usually extends the last block.
+                    match blocks.last_mut() {
+                        Some(last) if last.end() == u64::from(address) => {
+                            last.data.extend_from_slice(data)
+                        }
+                        _ => blocks.push(Block {
+                            address,
+                            data: data.to_vec(),
+                        }),
+                    }
+                }
+                END_OF_FILE => end_of_file = true,
+                EXTENDED_SEGMENT_ADDRESS | EXTENDED_LINEAR_ADDRESS => {
+                    let [high, low] = data else {
+                        return Err(error("address records need two bytes of data"));
+                    };
+                    let value = u32::from(u16::from_be_bytes([*high, *low]));
+                    base = if kind == EXTENDED_SEGMENT_ADDRESS {
+                        value << 4
+                    } else {
+                        value << 16
+                    };
+                }
+                START_SEGMENT_ADDRESS | START_LINEAR_ADDRESS => (),
+                _ => return Err(error(&format!("unknown record type {:02X}", kind))),
+            }
+        }
+        if !end_of_file {
+            return Err("the end of file record is missing, the file may be truncated".to_owned());
+        }
+
+        blocks.sort_by_key(|block| block.address);
+        for pair in blocks.windows(2) {
+            if pair[0].end() > u64::from(pair[1].address) {
+                return Err(format!(
+                    "the data at 0x{:X} overlaps the data before it",
+                    pair[1].address
+                ));
+            }
+        }
+        // Blocks which were written out of order may touch after sorting.
+        let mut merged: Vec<Block> = Vec::with_capacity(blocks.len());
+        for block in blocks {
+            match merged.last_mut() {
+                Some(last) if last.end() == u64::from(block.address) => {
+                    last.data.extend_from_slice(&block.data)
+                }
+                _ => merged.push(block),
+            }
+        }
+        Ok(Self { blocks: merged })
+    }
+
+    /// The number of data bytes.
+    pub fn size(&self) -> u64 {
+        self.blocks
+            .iter()
+            .map(|block| block.data.len() as u64)
+            .sum()
+    }
+
+    /// The address of the first and after the last data byte, `None` if there is no data.
+    pub fn address_range(&self) -> Option<(u32, u64)> {
+        Some((self.blocks.first()?.address, self.blocks.last()?.end()))
+    }
+}
+
+/// Decode the bytes of a record and check its length and checksum.
+fn decode_record(line: &str) -> Result<Vec<u8>, String> {
+    let Some(digits) = line.strip_prefix(':') else {
+        return Err("missing start code".to_owned());
+    };
+    if digits.len() < 10 || digits.len() % 2 != 0 {
+        return Err("invalid length".to_owned());
+    }
+    let bytes = (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()
+        .ok_or("invalid hex digits")?;
+    // Length, address, type, data and checksum.
+    if bytes.len() != usize::from(bytes[0]) + 5 {
+        return Err("length mismatch".to_owned());
+    }
+    if bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+        return Err("checksum mismatch".to_owned());
+    }
+    Ok(bytes)
+}
+
+/// Wether the given line is a well formed Intel HEX record with a matching checksum.
+pub fn valid_record(line: &str) -> bool {
+    decode_record(line).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A record of the given type at the given offset with a correct checksum.
+    fn record(kind: u8, offset: u16, data: &[u8]) -> String {
+        let mut bytes = vec![data.len() as u8];
+        bytes.extend_from_slice(&offset.to_be_bytes());
+        bytes.push(kind);
+        bytes.extend_from_slice(data);
+        let sum = bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        bytes.push(sum.wrapping_neg());
+        let digits: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        format!(":{}", digits)
+    }
+
+    /// A file of the given records followed by the end of file record.
+    fn file(records: &[String]) -> String {
+        let mut text = records.join("\n");
+        text.push_str("\n:00000001FF\n");
+        text
+    }
+
+    #[test]
+    fn contiguous_records_are_merged() {
+        let text = file(&[record(DATA, 0, &[1, 2]), record(DATA, 2, &[3])]);
+        let image = HexImage::parse(&text).unwrap();
+        assert_eq!(
+            image.blocks,
+            [Block {
+                address: 0,
+                data: vec![1, 2, 3]
+            }]
+        );
+        assert_eq!(image.size(), 3);
+        assert_eq!(image.address_range(), Some((0, 3)));
+    }
+
+    #[test]
+    fn extended_linear_address() {
+        let text = file(&[
+            record(EXTENDED_LINEAR_ADDRESS, 0, &[0x00, 0x01]),
+            record(DATA, 0x10, &[0xAA]),
+        ]);
+        let image = HexImage::parse(&text).unwrap();
+        assert_eq!(image.address_range(), Some((0x10010, 0x10011)));
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let text = file(&[record(DATA, 0, &[1]), ":0100000000FE".to_owned()]);
+        assert_eq!(
+            HexImage::parse(&text),
+            Err("record 2: checksum mismatch".to_owned())
+        );
+        assert!(!valid_record(":0100000000FE"));
+        assert!(valid_record(":0100000000FF"));
+    }
+
+    #[test]
+    fn length_mismatch() {
+        assert_eq!(
+            HexImage::parse(&file(&[":020000000000".to_owned()])),
+            Err("record 1: length mismatch".to_owned())
+        );
+        assert_eq!(
+            HexImage::parse(&file(&[":0000".to_owned()])),
+            Err("record 1: invalid length".to_owned())
+        );
+    }
+
+    #[test]
+    fn overlapping_data() {
+        let text = file(&[record(DATA, 0, &[1, 2, 3]), record(DATA, 2, &[4])]);
+        assert_eq!(
+            HexImage::parse(&text),
+            Err("the data at 0x2 overlaps the data before it".to_owned())
+        );
+    }
+
+    #[test]
+    fn missing_end_of_file() {
+        assert!(HexImage::parse(&record(DATA, 0, &[1]))
+            .unwrap_err()
+            .contains("end of file record is missing"));
+    }
+}
@@ -4,13 +4,91 @@ use eframe::egui;
 use egui::{FontFamily, FontId, TextStyle};
 use std::{
     borrow::Cow,
-    io,
+    collections::BTreeSet,
+    fs, io,
     path::{Path, PathBuf},
-    process::{Command, Output},
+    process::Output,
+    time::{Duration, Instant, SystemTime},
 };
 
 use rfd::FileDialog;
-use serialport::SerialPortInfo;
+use serialport::{SerialPortInfo, SerialPortType};
+
+use avrdude::{Avrdude, Detection};
+use backup::Backup;
+use batch::{Batch, PortState, Worker};
+use board::{
+    format_size, ArduinoBoard, BoardSpec, Chip, EraseMode, EspMethod, PicoTarget, Programmer,
+    SelectedBoard, Stm32Method, Uploader, Verbosity, UPDI_PARTS,
+};
+use board_picker::board_picker;
+use bundle::{Bundle, Manifest};
+use compare::CompareTool;
+use custom_boards::CustomBoard;
+use download::Download;
+use file_info::{format_time, FileInfo, Stamp};
+use firmware::FirmwareFormat;
+use fuses::{FuseEditor, FuseLayout};
+use production::Production;
+use progress::Progress;
+use project::{BuildLayout, BuildTarget};
+use run_log::RunInfo;
+use settings::Settings;
+use summary::Summary;
+use upload::{
+    find_uf2_drives, Hook, JobKind, MemoryFile, OtaTarget, ResetPulse, RunningUpload,
+    SignatureCheck, UploadJob, WrongChip, HOOK_PLACEHOLDERS,
+};
+
+mod avrdude;
+mod backup;
+mod batch;
+mod board;
+mod board_picker;
+mod bootloader;
+mod bundle;
+mod compare;
+mod custom_boards;
+mod download;
+mod elf;
+mod file_info;
+mod firmware;
+mod fuses;
+mod hex;
+mod production;
+mod progress;
+mod project;
+mod run_log;
+mod settings;
+mod stk500;
+mod summary;
+mod touch;
+mod upload;
+
+/// How long the selected file has to stay unchanged before it is reloaded.
+const FILE_SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// The most attempts avrdude is run with before a failure is reported.
+const MAX_ATTEMPTS: u8 = 5;
+
+/// How long the note about copied text is shown.
+const COPIED_NOTE: Duration = Duration::from_secs(2);
+
+/// The number of finished jobs whose timings are shown.
+const KEPT_TIMINGS: usize = 5;
+
+/// Why the ports can't be scanned while a job runs.
+const RESCAN_BLOCKED: &str = "The ports are not scanned while a job runs, scanning disturbs the \
+    transfer of some usb serial adapters";
+
+/// How often the ports are scanned while the window has the focus and auto rescan is on.
+const AUTO_RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The number of jobs kept in the history of the session.
+const KEPT_HISTORY: usize = 100;
+
+/// The file dialog filter showing all files.
+const ALL_FILES: (&str, &[&str]) = ("All files", &["*"]);
 
 /// The text styles applied to the shown text
 const TEXT_STYLE: [(TextStyle, FontId); 5] = [
@@ -48,197 +126,4560 @@ fn main() {
 struct ArduinoInstallerGui {
     /// The file path the user selected of the file that should be installed.
     file_path: Option<PathBuf>,
+    /// The path entered by the user, the selected file is used if it is valid.
+    path_input: String,
+    /// The project directory the newest build artifact is selected from.
+    project_dir: Option<PathBuf>,
+    /// The build system of the project directory, `None` if it is searched for the newest file.
+    project_layout: Option<BuildLayout>,
+    /// The environment or FQBN whose firmware is selected.
+    project_target: Option<String>,
+    /// The problem with the entered path, `None` if it is valid.
+    path_error: Option<Cow<'static, str>>,
+    /// The bundle the selected file was extracted from, `None` if a plain file is selected.
+    bundle: Option<Bundle>,
+    /// The board named by the bundle, if it differs from the board the user picked.
+    bundle_conflict: Option<SelectedBoard>,
+    /// The window comparing two firmware files.
+    compare: CompareTool,
+    /// The file written to the EEPROM together with the program, `None` to keep the EEPROM.
+    eeprom_path: Option<PathBuf>,
+    /// The format detected from the content of the selected file.
+    file_format: Option<Result<FirmwareFormat, String>>,
+    /// The size, modification time and hash of the selected file.
+    file_info: Option<FileInfo>,
+    /// Wether the selected file changed on disk since it was selected.
+    file_changed: bool,
+    /// Since when the selected file has the given size and modification time, it is only reloaded
+    /// once they stopped changing.
+    file_settling: Option<(Instant, Option<Stamp>)>,
+    /// Wether the selected file is flashed again whenever it changed on disk.
+    auto_flash: bool,
+    /// The url of a firmware the user is entering, `None` if no url is entered.
+    url_input: Option<String>,
+    /// The firmware which is currently downloaded.
+    download: Option<Download>,
     /// The selected board, which the program should be installed on.
-    selected_board: ArduinoBoard,
+    selected_board: SelectedBoard,
+    /// Wether the user picked the board, a picked board is not replaced by the one of a bundle or
+    /// a detected one.
+    board_picked: bool,
+    /// The board which was detected automatically from the usb ids of its port.
+    detected_board: Option<SelectedBoard>,
+    /// The boards loaded from the custom board definitions.
+    custom_boards: Vec<CustomBoard>,
+    /// The programmer used instead of the bootloader of the board.
+    programmer: Programmer,
+    /// The partno picked for boards without a fixed chip.
+    picked_partno: Option<&'static str>,
+    /// The way STM32 boards are flashed.
+    stm32_method: Stm32Method,
+    /// The way ESP boards are flashed.
+    esp_method: EspMethod,
+    /// The device updated over the air.
+    ota: OtaOptions,
+    /// The target the Raspberry Pi Pico is flashed through.
+    pico_target: PicoTarget,
+    /// The mounted drives of Picos in BOOTSEL mode.
+    uf2_drives: Vec<PathBuf>,
     /// The selected port over which the board is connected.
     selected_port: Option<SerialPortInfo>,
     /// All available ports
     available_ports: Vec<SerialPortInfo>,
+    /// The network ports added by the user, they can't be scanned so they are kept on rescans.
+    network_ports: Vec<String>,
+    /// The network port the user is entering, `None` if no port is entered.
+    network_port_input: Option<String>,
+    /// Wether only the ports with the usb ids of the selected board are shown.
+    only_matching_ports: bool,
+    /// The names of the ports checked to be flashed one after another.
+    batch_ports: BTreeSet<String>,
+    /// Wether a batch flash skips the remaining ports after the first failure.
+    batch_stop_on_failure: bool,
+    /// The running or finished batch flash, `None` if none was started or its summary was closed.
+    batch: Option<Batch>,
     /// The last error that happened when scanning the ports.
     port_scan_error: Option<String>,
+    /// When the ports were scanned last, `None` if they were never scanned.
+    last_port_scan: Option<Instant>,
     /// The general last error that happened.
     general_error: Option<Cow<'static, str>>,
-    /// The output of the issued command.
-    output: Option<String>,
+    /// The result of the last job, shown until the next job starts.
+    result: Option<JobResult>,
     /// The command issed to install the program.
     used_command: Option<String>,
+    /// The commands of the last job as they are pasted into a shell, `None` if it ran none.
+    command_lines: Option<String>,
+    /// What was copied to the clipboard last and when, shown for a moment.
+    copied: Option<(&'static str, Instant)>,
+    /// The timings of the last finished jobs, the most recent one first.
+    timings: Vec<Timing>,
+    /// The production mode flashing each new board, `None` if it is off.
+    production: Option<Production>,
+    /// The flashes counted in this session.
+    stats: SessionStats,
+    /// The jobs run in this session, the most recent one first.
+    history: Vec<HistoryEntry>,
+    /// What is written to the log of the running job, `None` if no log is written.
+    run_info: Option<RunInfo>,
+    /// Why the log of the last job could not be written.
+    log_warning: Option<String>,
+    /// The upload which is currently running.
+    running: Option<RunningUpload>,
+    /// Wether the user is asked to confirm burning the bootloader.
+    confirm_burn: bool,
+    /// Wether the user is asked to confirm erasing the chip.
+    confirm_erase: bool,
+    /// The fuse values entered to be written.
+    fuse_editor: FuseEditor,
+    /// The lock byte entered to be written.
+    lock_editor: FuseEditor,
+    /// The fuses written in the same run as each flash, `None` to keep the fuses.
+    fuses_with_flash: Option<(FuseLayout, [u8; 3])>,
+    /// The fuses the user is asked to confirm writing with each flash.
+    confirm_fuses_with_flash: Option<(FuseLayout, [u8; 3])>,
+    /// The lock byte written after each successful flash, `None` to keep the lock bits.
+    lock_after_flash: Option<u8>,
+    /// The lock byte the user is asked to confirm writing after each flash.
+    confirm_lock_after_flash: Option<u8>,
+    /// The backups of the selected port offered to be restored, `None` if they are not shown.
+    restore_list: Option<Vec<Backup>>,
+    /// The backup the user is asked to confirm restoring.
+    confirm_restore: Option<PathBuf>,
+    /// The connected ports matching the selected board the user is asked to confirm flashing.
+    confirm_matching: Option<Vec<SerialPortInfo>>,
+    /// The read job and its existing destination the user is asked to overwrite.
+    confirm_read: Option<(JobKind, PathBuf)>,
+    /// Wether jobs are only validated and their commands shown instead of running them.
+    dry_run: bool,
+    /// The options overriding the board defaults.
+    advanced: AdvancedOptions,
+    /// The settings kept between runs.
+    settings: Settings,
+    /// The avrdude executable found for the settings, `None` until it is searched at startup.
+    avrdude: Option<Avrdude>,
 }
 
-impl ArduinoInstallerGui {
-    /// Create a new instance of the gui state.
-    fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let mut styles = cc.egui_ctx.style().as_ref().clone();
-        styles.text_styles = TEXT_STYLE.into();
-        cc.egui_ctx.set_style(styles);
+/// The device entered by the user for over the air updates.
+#[derive(Default)]
+struct OtaOptions {
+    /// The ip address or host name of the device.
+    host: String,
+    /// The port of the update service, the one of the board is used if empty.
+    port: String,
+    /// The password of the update service, empty if none is set.
+    password: String,
+}
 
-        let mut me = Self::default();
-        portscan(&mut me.available_ports, &mut me.port_scan_error);
-        me
+impl OtaOptions {
+    /// The target described by the options, the board port is used as the default.
+    fn target(&self, board_port: u16) -> Result<OtaTarget, Cow<'static, str>> {
+        let host = self.host.trim();
+        if host.is_empty() {
+            return Err("Error: no ip address entered".into());
+        }
+        let port = match non_empty(&self.port) {
+            Some(port) => port
+                .trim()
+                .parse()
+                .map_err(|_| "Error: the ota port is not a number")?,
+            None => board_port,
+        };
+        Ok(OtaTarget {
+            host: host.to_owned(),
+            port,
+            password: self.password.clone(),
+        })
+    }
+
+    /// Show the options, the board port is shown as the default.
+    fn ui(&mut self, ui: &mut egui::Ui, board_port: u16) {
+        egui::Grid::new("OtaOptions").show(ui, |ui| {
+            ui.label("IP address: ");
+            ui.text_edit_singleline(&mut self.host);
+            ui.end_row();
+
+            ui.label("Port: ");
+            ui.add(egui::TextEdit::singleline(&mut self.port).hint_text(board_port.to_string()));
+            ui.end_row();
+
+            ui.label("Password: ");
+            ui.add(egui::TextEdit::singleline(&mut self.password).password(true));
+            ui.end_row();
+        });
     }
 }
 
-/// Scan for available ports
-fn portscan(available_ports: &mut Vec<SerialPortInfo>, port_scan_error: &mut Option<String>) {
-    match serialport::available_ports() {
-        Ok(ports) => {
-            *available_ports = ports;
-            *port_scan_error = None;
+/// Options overriding the defaults of the selected board.
+#[derive(Default)]
+struct AdvancedOptions {
+    /// The programmer passed to avrdude instead of the one of the board, if not empty.
+    programmer: String,
+    /// The partno passed to avrdude instead of the one of the board, if not empty.
+    partno: String,
+    /// The baud rate used instead of the one of the board.
+    baud: BaudOverride,
+    /// The baud rate entered by the user for [`BaudOverride::Custom`].
+    custom_baud: String,
+    /// Wether programs are flashed even if they are larger than the flash of the board.
+    ignore_size: bool,
+    /// Wether Intel HEX files are flashed even if they are invalid.
+    ignore_invalid_hex: bool,
+    /// The erase mode used instead of the one of the board, `None` to use the board default.
+    erase: Option<EraseMode>,
+    /// The bit clock passed to ISP programmers with `-B`, avrdudes default is used if empty.
+    bitclock: String,
+}
+
+/// The result of a job which exited
+struct JobResult {
+    /// What the job did.
+    kind: JobKind,
+    /// How the job ended.
+    outcome: Outcome,
+    /// The merged output of the commands of the job.
+    output: String,
+    /// How long the job ran.
+    elapsed: Duration,
+    /// The fuse values printed by a fuse read, in the order of the fuses of its layout.
+    fuses: Vec<u8>,
+    /// The number of attempts the job took.
+    attempts: u8,
+    /// The facts avrdude reported, `None` if another uploader ran.
+    summary: Option<Summary>,
+    /// The slower bit clock the chip only answered with, `None` if the chosen one worked.
+    slow_bitclock: Option<String>,
+    /// Wether the flash was written without verifying it.
+    verification_skipped: bool,
+}
+
+/// How long a finished job took
+#[derive(Debug, Clone, Copy)]
+struct Timing {
+    /// The name of the job.
+    name: &'static str,
+    /// How long the job ran.
+    elapsed: Duration,
+    /// The number of bytes written or saved, `None` if the job failed or it is unknown.
+    bytes: Option<u64>,
+}
+
+impl Timing {
+    /// The timing of the given result.
+    fn of(result: &JobResult) -> Self {
+        Self {
+            name: result.kind.name(),
+            elapsed: result.elapsed,
+            bytes: match result.outcome {
+                Outcome::Succeeded(bytes) => bytes,
+                _ => None,
+            },
         }
-        Err(e) => {
-            *available_ports = Vec::new();
-            *port_scan_error = Some(format!("ERROR: {}", e));
+    }
+}
+
+/// The counts of the flashes since the program started or they were reset
+struct SessionStats {
+    /// When the counting started.
+    since: SystemTime,
+    /// The number of flashes which ran.
+    attempted: u32,
+    /// The number of flashes which succeeded.
+    succeeded: u32,
+    /// The number of flashes which failed, cancelled ones are neither failed nor succeeded.
+    failed: u32,
+    /// How long all flashes took together.
+    total: Duration,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            since: SystemTime::now(),
+            attempted: 0,
+            succeeded: 0,
+            failed: 0,
+            total: Duration::ZERO,
         }
     }
 }
 
-impl eframe::App for ArduinoInstallerGui {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.visuals_mut().override_text_color = Some(egui::Color32::WHITE);
-            ui.heading("Arduino Installer gui");
+impl SessionStats {
+    /// Count the given result if it is the result of a flash.
+    fn record(&mut self, result: &JobResult) {
+        if result.kind != JobKind::Flash || result.outcome == Outcome::DryRun {
+            return;
+        }
+        self.attempted += 1;
+        self.total += result.elapsed;
+        match result.outcome {
+            Outcome::Succeeded(_) => self.succeeded += 1,
+            Outcome::Cancelled(_) => (),
+            _ => self.failed += 1,
+        }
+    }
+
+    /// A line like "12 flashes, 11 succeeded, 1 failed, 8.3 s on average".
+    fn describe(&self) -> String {
+        let average = self.total.as_secs_f32() / self.attempted.max(1) as f32;
+        format!(
+            "{} flashes, {} succeeded, {} failed, {:.1} s on average",
+            self.attempted, self.succeeded, self.failed, average
+        )
+    }
+}
+
+/// A job run in this session, which can be run again with the same selections
+#[derive(Clone)]
+struct HistoryEntry {
+    /// When the job was started.
+    time: SystemTime,
+    /// What the job did.
+    kind: JobKind,
+    /// The board selected for the job.
+    board: SelectedBoard,
+    /// The port selected for the job, `None` if the uploader finds the board on its own.
+    port: Option<SerialPortInfo>,
+    /// The file written or compared, `None` if the job used none.
+    file: Option<PathBuf>,
+    /// How the job ended, `None` while it runs.
+    result: Option<&'static str>,
+    /// Wether the job succeeded.
+    succeeded: bool,
+}
+
+impl HistoryEntry {
+    /// Wether the entry is a flash which succeeded, which Ctrl+R runs again.
+    fn is_successful_flash(&self) -> bool {
+        self.kind == JobKind::Flash && self.succeeded && self.file.is_some()
+    }
+
+    /// A line like "2023-04-01 13:37:00 UTC Flash, Arduino Uno on /dev/ttyUSB0, blink.hex:
+    /// succeeded".
+    fn describe(&self) -> String {
+        let port = match self.port {
+            Some(ref port) => format!(" on {}", port.port_name),
+            None => String::new(),
+        };
+        let file = match self.file {
+            Some(ref file) => format!(
+                ", {}",
+                file.file_name().unwrap_or_default().to_string_lossy()
+            ),
+            None => String::new(),
+        };
+        format!(
+            "{} {}, {}{}{}: {}",
+            format_time(self.time),
+            self.kind.name(),
+            self.board.name(),
+            port,
+            file,
+            self.result.unwrap_or("running")
+        )
+    }
+}
+
+/// The ways a job can end
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// The job succeeded, with the number of bytes written to the flash or saved to the file if
+    /// known
+    Succeeded(Option<u64>),
+    /// The uploader exited with an error, with the exit code if it was not killed by a signal
+    Failed(Option<i32>),
+    /// The uploader could not be started
+    NotStarted(String),
+    /// The job failed before or between the commands
+    Error(String),
+    /// The chip does not match the selected board, so nothing was written
+    WrongChip(String),
+    /// The user cancelled the job, the flag tells wether the chip may contain partial firmware
+    Cancelled(bool),
+    /// The job was validated and its commands were shown without running them
+    DryRun,
+}
+
+impl Outcome {
+    /// A short word for the outcome shown in the history.
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Succeeded(_) => "succeeded",
+            Self::Failed(_) | Self::Error(_) => "failed",
+            Self::NotStarted(_) => "not started",
+            Self::WrongChip(_) => "wrong chip",
+            Self::Cancelled(_) => "cancelled",
+            Self::DryRun => "dry run",
+        }
+    }
+
+    /// `Ok` if the job succeeded, a short reason like "failed (exit code 1)" otherwise.
+    fn to_result(&self) -> Result<(), String> {
+        match self {
+            Self::Succeeded(_) => Ok(()),
+            Self::Failed(Some(code)) => Err(format!("failed (exit code {})", code)),
+            Self::NotStarted(e) | Self::Error(e) => Err(e.clone()),
+            Self::WrongChip(e) => Err(format!("skipped, {}", e)),
+            outcome => Err(outcome.label().to_owned()),
+        }
+    }
+}
+
+impl JobResult {
+    /// The result of the given job which exited with the given result.
+    fn new(running: &RunningUpload, res: io::Result<Output>) -> Self {
+        let output = running.log.text();
+        // The built-in uploader reports like avrdude does.
+        let summary = matches!(running.uploader, Uploader::Avrdude | Uploader::Stk500)
+            .then(|| Summary::parse(&output));
+        let fuses = match (running.kind, &res) {
+            (JobKind::ReadFuses(_), Ok(out)) => {
+                fuses::parse_values(&String::from_utf8_lossy(&out.stdout))
+            }
+            _ => Vec::new(),
+        };
+        let outcome = match res {
+            Ok(out) if out.status.success() && running.kind.read_memory().is_some() => {
+                Outcome::Succeeded(saved_bytes(&output))
+            }
+            Ok(out) if out.status.success() => {
+                Outcome::Succeeded(summary.and_then(|summary| summary.written))
+            }
+            Ok(out) => Outcome::Failed(out.status.code()),
+            // An interrupted write leaves the program half written.
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => Outcome::Cancelled(
+                matches!(running.uploader, Uploader::Avrdude | Uploader::Stk500)
+                    && running.kind.writes(),
+            ),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Outcome::NotStarted(e.to_string()),
+            Err(e) if WrongChip::is(&e) => Outcome::WrongChip(e.to_string()),
+            Err(e) => Outcome::Error(e.to_string()),
+        };
+        Self {
+            kind: running.kind,
+            outcome,
+            elapsed: running.started.elapsed(),
+            fuses,
+            attempts: running.log.attempt(),
+            slow_bitclock: running.log.slow_bitclock(),
+            verification_skipped: running.verification_skipped,
+            summary,
+            output,
+        }
+    }
+
+    /// The result of the given job which was only validated.
+    fn dry_run(kind: JobKind) -> Self {
+        Self {
+            kind,
+            outcome: Outcome::DryRun,
+            output: String::new(),
+            elapsed: Duration::ZERO,
+            fuses: Vec::new(),
+            attempts: 0,
+            summary: None,
+            slow_bitclock: None,
+            verification_skipped: false,
+        }
+    }
+
+    /// Show the result as a banner above the output, returns wether the user chose to use the
+    /// slower bit clock the job succeeded with for all jobs.
+    fn ui(&self, ui: &mut egui::Ui) -> bool {
+        let name = self.kind.name();
+        let mismatch = self
+            .output
+            .lines()
+            .find(|line| line.contains("mismatch"))
+            .map(|line| line.trim_start_matches("avrdude:").trim());
+        let (color, banner) = match self.outcome {
+            Outcome::Succeeded(_) if self.kind == JobKind::Verify => (
+                egui::Color32::GREEN,
+                "Verification passed, the board contains the selected file".to_owned(),
+            ),
+            Outcome::Failed(_) if self.kind == JobKind::Verify && mismatch.is_some() => (
+                egui::Color32::RED,
+                format!(
+                    "Verification failed, the contents differ ({})",
+                    mismatch.unwrap_or_default()
+                ),
+            ),
+            Outcome::Succeeded(_) if self.kind == JobKind::TestCommunication => (
+                egui::Color32::GREEN,
+                match self.summary.and_then(|summary| summary.signature) {
+                    Some(signature) => format!(
+                        "The programmer answered, the device signature is {:02X} {:02X} {:02X} \
+                        ({}), nothing was written",
+                        signature[0],
+                        signature[1],
+                        signature[2],
+                        Chip::with_signature(signature).map_or("unknown chip", |chip| chip.name)
+                    ),
+                    None => "The programmer answered, nothing was written".to_owned(),
+                },
+            ),
+            Outcome::Failed(_) if self.kind == JobKind::TestCommunication => (
+                egui::Color32::RED,
+                "Communication test failed, the programmer or the chip did not answer, check the \
+                port, the cable and the board"
+                    .to_owned(),
+            ),
+            Outcome::Succeeded(Some(bytes)) if self.kind.read_memory().is_some() => (
+                egui::Color32::GREEN,
+                format!("{} succeeded, {} bytes saved", name, group_digits(bytes)),
+            ),
+            Outcome::Succeeded(Some(bytes)) => (
+                egui::Color32::GREEN,
+                format!("{} succeeded, {} bytes written", name, group_digits(bytes)),
+            ),
+            Outcome::Succeeded(None) => (egui::Color32::GREEN, format!("{} succeeded", name)),
+            Outcome::Failed(Some(code)) => (
+                egui::Color32::RED,
+                format!("{} failed (exit code {})", name, code),
+            ),
+            Outcome::Failed(None) => (
+                egui::Color32::RED,
+                format!("{} failed, the uploader was killed", name),
+            ),
+            Outcome::NotStarted(ref e) => (
+                egui::Color32::from_rgb(255, 140, 0),
+                format!("{} could not be started: {}", name, e),
+            ),
+            Outcome::Error(ref e) => (egui::Color32::RED, format!("{} failed: {}", name, e)),
+            Outcome::WrongChip(ref e) => (
+                egui::Color32::RED,
+                format!("{} skipped, nothing was written: {}", name, e),
+            ),
+            Outcome::Cancelled(true) => (
+                egui::Color32::YELLOW,
+                format!(
+                    "{} cancelled, the chip may contain partial firmware, flash it again before \
+                    using it",
+                    name
+                ),
+            ),
+            Outcome::Cancelled(false) => (egui::Color32::YELLOW, format!("{} cancelled", name)),
+            Outcome::DryRun if self.kind == JobKind::Flash => (
+                egui::Color32::LIGHT_BLUE,
+                "Dry run \u{2014} nothing was flashed".to_owned(),
+            ),
+            Outcome::DryRun => (
+                egui::Color32::LIGHT_BLUE,
+                format!(
+                    "Dry run \u{2014} the {} was not started",
+                    name.to_lowercase()
+                ),
+            ),
+        };
+        let banner = match self.outcome {
+            Outcome::Succeeded(bytes) if self.verification_skipped => format!(
+                "{} (verification skipped), {}",
+                banner,
+                completed(self.elapsed, bytes)
+            ),
+            Outcome::Succeeded(bytes) => format!("{}, {}", banner, completed(self.elapsed, bytes)),
+            _ => banner,
+        };
+        let banner = match (
+            &self.outcome,
+            self.summary.and_then(|summary| summary.failed),
+        ) {
+            (Outcome::Failed(_), Some(operation)) => format!("{} while {}", banner, operation),
+            _ => banner,
+        };
+        let banner = match self.attempts {
+            0 | 1 => banner,
+            attempts => format!("{} after {} attempts", banner, attempts),
+        };
+        ui.colored_label(color, egui::RichText::new(banner).strong());
+        if let Some(summary) = self.summary {
+            match summary.describe() {
+                Some(line) => ui.label(line),
+                None => ui.label("No details were recognized, see the full output below"),
+            };
+        }
+        let mut use_bitclock = false;
+        if let Some(ref bitclock) = self.slow_bitclock {
             ui.horizontal(|ui| {
-                ui.label("File: ");
-                if let Some(ref path) = self.file_path {
-                    ui.label(path.to_string_lossy().as_ref());
-                }
-                if ui.button("Choose a file").clicked() {
-                    let file = FileDialog::new()
-                        .add_filter("elf file", &["elf"])
-                        .pick_file();
-                    self.file_path = file;
-                }
+                ui.label(format!(
+                    "The chip only answered with the slower bit clock -B {}",
+                    bitclock
+                ));
+                use_bitclock = ui
+                    .button(format!("Use -B {} for all jobs", bitclock))
+                    .clicked();
             });
+        }
+
+        if let (JobKind::ReadFuses(layout), Outcome::Succeeded(_)) = (self.kind, &self.outcome) {
+            self.fuses_ui(ui, &layout);
+        }
+
+        if let Some(progress) = Progress::parse(&self.output) {
+            let (fraction, fill, text) = match self.outcome {
+                Outcome::Succeeded(_) => (1.0, egui::Color32::DARK_GREEN, "Done"),
+                _ => (progress.fraction, egui::Color32::DARK_RED, "Failed"),
+            };
+            ui.add(egui::ProgressBar::new(fraction).fill(fill).text(text));
+        }
+        if !self.output.is_empty() {
+            output_view(ui, &self.output);
+        }
+        use_bitclock
+    }
+
+    /// Show the fuse values of a fuse read as hex and decoded for the chip if possible.
+    fn fuses_ui(&self, ui: &mut egui::Ui, layout: &FuseLayout) {
+        if self.fuses.len() != layout.names.len() {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!(
+                    "avrdude printed {} of {} fuse values",
+                    self.fuses.len(),
+                    layout.names.len()
+                ),
+            );
+        }
+        egui::Grid::new("FuseValues").show(ui, |ui| {
+            for (name, value) in layout.names.iter().zip(&self.fuses) {
+                ui.label(format!("{}: ", name));
+                ui.monospace(format!("0x{:02X}", value));
+                ui.end_row();
+            }
+        });
+        for fact in layout.decode(&self.fuses) {
+            ui.label(fact);
+        }
+    }
+}
+
+/// The number of bytes saved by a job reading a memory to a file.
+fn saved_bytes(output: &str) -> Option<u64> {
+    output.lines().find_map(|line| {
+        let count = line.strip_prefix("Saved ")?.split(' ').next()?;
+        count.parse().ok()
+    })
+}
+
+/// The baud rate override chosen by the user
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+enum BaudOverride {
+    /// The baud rate of the board is used
+    #[default]
+    Board,
+    /// One of the common baud rates
+    Fixed(u32),
+    /// A baud rate entered by the user
+    Custom,
+}
+
+impl BaudOverride {
+    /// The common baud rates offered directly.
+    const COMMON: [u32; 4] = [9600, 19_200, 57_600, 115_200];
+}
+
+impl AdvancedOptions {
+    /// Apply the options to the given spec.
+    fn apply(&self, spec: &mut BoardSpec) {
+        if let Some(programmer) = non_empty(&self.programmer) {
+            match spec.isp {
+                Some(ref mut isp) => isp.name = programmer.to_owned().into(),
+                None => spec.programmer = programmer.to_owned().into(),
+            }
+        }
+        if let Some(partno) = non_empty(&self.partno) {
+            spec.partno = partno.to_owned().into();
+        }
+        let baud = match spec.isp {
+            Some(ref mut isp) => &mut isp.baud,
+            None => &mut spec.baud,
+        };
+        match self.baud {
+            BaudOverride::Board => (),
+            BaudOverride::Fixed(fixed) => *baud = Some(fixed),
+            BaudOverride::Custom => *baud = self.custom_baud.trim().parse().ok(),
+        }
+        // A baud rate chosen by the user is never replaced.
+        if self.baud != BaudOverride::Board {
+            spec.fallback_baud = None;
+        }
+        if let Some(erase) = self.erase {
+            spec.erase = erase;
+        }
+        match non_empty(&self.bitclock) {
+            Some(bitclock) if spec.uses_isp() && valid_bitclock(bitclock) => {
+                spec.bitclock = Some(bitclock.to_owned())
+            }
+            _ => (),
+        }
+    }
+
+    /// Check that all options can be applied.
+    fn validate(&self) -> Result<(), Cow<'static, str>> {
+        if self.baud == BaudOverride::Custom && self.custom_baud.trim().parse::<u32>().is_err() {
+            return Err("Error: the custom baud rate is not a number".into());
+        }
+        if non_empty(&self.bitclock).is_some_and(|bitclock| !valid_bitclock(bitclock)) {
+            return Err(
+                "Error: the bit clock is not a positive number with an optional unit \
+                like 10, 10us or 125kHz"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Show the options, the baud rate and erase mode of the board are shown as the default. The
+    /// bit clock is only shown if an ISP programmer is used.
+    fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        board_baud: Option<u32>,
+        board_erase: EraseMode,
+        isp: bool,
+    ) {
+        egui::Grid::new("AdvancedOptions").show(ui, |ui| {
+            ui.label("Programmer override: ");
+            ui.text_edit_singleline(&mut self.programmer);
+            ui.end_row();
 
+            ui.label("Partno override: ");
+            ui.text_edit_singleline(&mut self.partno);
+            ui.end_row();
+
+            ui.label("Baud rate: ");
             ui.horizontal(|ui| {
-                ui.label("Select board: ");
-                egui::ComboBox::from_id_source("Boards")
-                    .selected_text(format!("{:?}", self.selected_board))
+                let board_default = match board_baud {
+                    Some(baud) => format!("Board default ({})", baud),
+                    None => "Board default".to_owned(),
+                };
+                let selected = match self.baud {
+                    BaudOverride::Board => board_default.clone(),
+                    BaudOverride::Fixed(baud) => baud.to_string(),
+                    BaudOverride::Custom => "Custom".to_owned(),
+                };
+                egui::ComboBox::from_id_source("BaudOverride")
+                    .selected_text(selected)
                     .show_ui(ui, |ui| {
-                        ui.selectable_value(
-                            &mut self.selected_board,
-                            ArduinoBoard::ArduinoUno,
-                            "Arduino Uno",
-                        );
+                        ui.selectable_value(&mut self.baud, BaudOverride::Board, board_default);
+                        for baud in BaudOverride::COMMON {
+                            ui.selectable_value(
+                                &mut self.baud,
+                                BaudOverride::Fixed(baud),
+                                baud.to_string(),
+                            );
+                        }
+                        ui.selectable_value(&mut self.baud, BaudOverride::Custom, "Custom");
                     });
-            });
 
-            ui.horizontal(|ui| {
-                if ui.button("Rescan").clicked() {
-                    portscan(&mut self.available_ports, &mut self.port_scan_error);
-                } else {
-                    let lbl = ui.label("Available Ports: ");
-                    egui::ComboBox::from_id_source("Ports")
-                        .selected_text(format!("{:?}", self.selected_port))
-                        .wrap(false)
-                        .width(lbl.rect.width().mul_add(-1.2, ui.available_width()))
-                        .show_ui(ui, |ui| {
-                            for info in self.available_ports.iter_mut() {
-                                ui.selectable_value(
-                                    &mut self.selected_port,
-                                    Some(info.clone()),
-                                    format!("{:?}: {}", info.port_type, info.port_name),
-                                );
-                            }
-                        });
+                if self.baud == BaudOverride::Custom {
+                    ui.text_edit_singleline(&mut self.custom_baud);
+                    if self.validate().is_err() {
+                        ui.colored_label(egui::Color32::RED, "not a number");
+                    }
                 }
             });
+            ui.end_row();
 
-            ui.scope(|ui| {
-                ui.visuals_mut().override_text_color = Some(egui::Color32::RED);
-                if let Some(ref s) = self.port_scan_error {
-                    ui.label(s);
-                }
-
-                if let Some(ref s) = self.general_error {
-                    ui.label(s.as_ref());
-                }
+            ui.label("Erase: ");
+            ui.vertical(|ui| {
+                let board_default = format!("Board default ({})", board_erase.label());
+                let selected = match self.erase {
+                    Some(erase) => erase.label().to_owned(),
+                    None => board_default.clone(),
+                };
+                egui::ComboBox::from_id_source("EraseOverride")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.erase, None, board_default);
+                        for erase in EraseMode::ALL {
+                            ui.selectable_value(&mut self.erase, Some(erase), erase.label());
+                        }
+                    });
+                ui.label(self.erase.unwrap_or(board_erase).description());
             });
+            ui.end_row();
 
-            if ui.button("Flash device!").clicked() {
-                match (&self.file_path, &self.selected_port) {
-                    (&Some(ref path), &Some(ref port)) => {
-                        let (used_command, res) = avrdude(self.selected_board.spec(), port, path);
-                        self.output = Some(format!(
-                            "Flashing: {:?}",
-                            res.map(|out| String::from_utf8(out.stdout)),
-                        ));
-                        self.used_command = Some(used_command);
+            if isp {
+                ui.label("Bit clock (-B): ");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.bitclock)
+                            .hint_text("programmer default"),
+                    )
+                    .on_hover_text(
+                        "Slows down the ISP programmer, e.g. 10 for a period of 10 us or \
+                        125kHz. Fresh chips run at 1 MHz from the internal oscillator and need \
+                        it when the device signature reads as 0x000000.",
+                    );
+                    if non_empty(&self.bitclock).is_some_and(|bitclock| !valid_bitclock(bitclock)) {
+                        ui.colored_label(egui::Color32::RED, "not a valid bit clock");
                     }
-                    (&None, &None | &Some(_)) => {
-                        self.general_error = Some("Error: no file selected".into());
-                    }
-                    (&Some(_), &None) => {
-                        self.general_error = Some("Error: No port selected".into());
-                    }
-                }
+                });
+                ui.end_row();
             }
 
-            if let Some(ref cmd) = self.used_command {
-                ui.label(cmd);
-            }
+            ui.label("Size check: ");
+            ui.checkbox(
+                &mut self.ignore_size,
+                "Flash even if the firmware is larger than the flash",
+            );
+            ui.end_row();
 
-            if let Some(ref out) = self.output {
-                ui.label(out);
-            }
+            ui.label("HEX check: ");
+            ui.checkbox(
+                &mut self.ignore_invalid_hex,
+                "Flash even if the Intel HEX file is invalid",
+            );
+            ui.end_row();
         });
     }
 }
 
-/// Enumeration of all supported Arduino boards
-#[derive(Debug, Default, PartialEq, Clone, Copy)]
-enum ArduinoBoard {
-    /// The Arduino Uno
-    #[default]
-    ArduinoUno,
+/// Wether the given bit clock is a positive number with a unit avrdude accepts, a number without
+/// unit is the period in microseconds.
+fn valid_bitclock(bitclock: &str) -> bool {
+    let split = bitclock
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(bitclock.len());
+    let (number, unit) = bitclock.split_at(split);
+    let units = ["", "us", "hz", "khz", "mhz"];
+    number.parse::<f64>().is_ok_and(|number| number > 0.0)
+        && units.contains(&unit.to_ascii_lowercase().as_str())
 }
 
-impl ArduinoBoard {
-    /// The specification required to install a program to the board.
-    fn spec(self) -> BoardSpec {
-        match self {
-            Self::ArduinoUno => BoardSpec {
-                programmer: "arduino",
-                partno: "atmega328p",
-                do_chip_erase: true,
-            },
+/// The trimmed string, `None` if it only contains whitespace.
+fn non_empty(s: &str) -> Option<&str> {
+    Some(s.trim()).filter(|s| !s.is_empty())
+}
+
+impl ArduinoInstallerGui {
+    /// Create a new instance of the gui state.
+    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        let mut styles = cc.egui_ctx.style().as_ref().clone();
+        styles.text_styles = TEXT_STYLE.into();
+        cc.egui_ctx.set_style(styles);
+
+        let mut me = Self::default();
+        match Settings::load() {
+            Ok(settings) => me.settings = settings,
+            Err(e) => me.general_error = Some(e.into()),
+        }
+        let avrdude = Avrdude::find(me.settings.avrdude_path.as_deref(), &cc.egui_ctx);
+        if me.general_error.is_none() && !avrdude.warnings.is_empty() {
+            me.general_error = Some(avrdude.warnings.join("\n").into());
+        }
+        me.avrdude = Some(avrdude);
+        me.rescan();
+        me.uf2_drives = find_uf2_drives();
+        if let Some(path) = custom_boards::definitions_path() {
+            match custom_boards::load(&path) {
+                Ok(boards) => me.custom_boards = boards,
+                Err(e) => me.general_error = Some(e.into()),
+            }
         }
+        me
     }
-}
 
-/// A specification used to install a program to board (passed to avrdude).
-#[derive(Debug, Clone)]
-struct BoardSpec {
-    /// The name of the onboard programmer.
-    programmer: &'static str,
-    /// The name of the chip the program should be installed to.
-    partno: &'static str,
-    /// Wether the chip should be whiped before installing.
-    do_chip_erase: bool,
-}
+    /// Scan for available ports and select the connected board if exactly one known board is
+    /// connected and neither a port nor a board was chosen yet.
+    fn rescan(&mut self) {
+        self.refresh_ports();
+        if self.selected_port.is_some() || self.board_picked {
+            return;
+        }
+
+        let mut detected = self
+            .available_ports
+            .iter()
+            .filter(|port| !self.settings.usb_only || is_usb_port(port))
+            .filter_map(|port| ArduinoBoard::detect(port).map(|board| (board, port)));
+        if let (Some((board, port)), None) = (detected.next(), detected.next()) {
+            self.selected_board = SelectedBoard::Builtin(board);
+            self.selected_port = Some(port.clone());
+            self.detected_board = Some(self.selected_board.clone());
+        }
+    }
+
+    /// The scanned ports listed for the given spec. The selected port and the ports checked for a
+    /// batch are always listed, so a filter can't hide them.
+    fn shown_ports(&self, spec: &BoardSpec) -> Vec<SerialPortInfo> {
+        let matching = self.only_matching_ports && !spec.usb_ids.is_empty();
+        self.available_ports
+            .iter()
+            .filter(|info| {
+                let kept = self
+                    .selected_port
+                    .as_ref()
+                    .is_some_and(|port| port.port_name == info.port_name)
+                    || self.batch_ports.contains(&info.port_name);
+                kept || ((!self.settings.usb_only || is_usb_port(info))
+                    && (!matching || spec.matches_port(info)))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Scan for available ports and keep the selected one, it is cleared with a notice if its
+    /// board was disconnected.
+    fn refresh_ports(&mut self) {
+        portscan(&mut self.available_ports, &mut self.port_scan_error);
+        self.last_port_scan = Some(Instant::now());
+        let Some(name) = self
+            .selected_port
+            .as_ref()
+            .map(|port| port.port_name.clone())
+        else {
+            return;
+        };
+        // Network ports can't be scanned and a failed scan lists no ports at all.
+        if self.network_ports.contains(&name) || self.port_scan_error.is_some() {
+            return;
+        }
+        match self
+            .available_ports
+            .iter()
+            .find(|port| port.port_name == name)
+        {
+            Some(port) => self.selected_port = Some(port.clone()),
+            None => {
+                self.selected_port = None;
+                self.general_error = Some(
+                    format!(
+                        "Warning: {} was disconnected, select the port of the board again",
+                        name
+                    )
+                    .into(),
+                );
+            }
+        }
+    }
+
+    /// Scan the ports every few seconds while the window has the focus and auto rescan is on.
+    fn auto_rescan_tick(&mut self, ctx: &egui::Context) {
+        // The production mode scans the ports on its own.
+        if !self.settings.auto_rescan
+            || self.production.is_some()
+            || !ctx.input(|i| i.raw.has_focus)
+        {
+            return;
+        }
+        let since = self
+            .last_port_scan
+            .map_or(AUTO_RESCAN_INTERVAL, |at| at.elapsed());
+        // Scanning could disturb the running flash.
+        if since >= AUTO_RESCAN_INTERVAL && !self.is_busy() {
+            self.refresh_ports();
+            ctx.request_repaint_after(AUTO_RESCAN_INTERVAL);
+        } else {
+            ctx.request_repaint_after(AUTO_RESCAN_INTERVAL.saturating_sub(since));
+        }
+    }
+
+    /// The programmer used for the given spec, boards without a bootloader fall back to the
+    /// USBasp.
+    fn programmer(&self, spec: &BoardSpec) -> Programmer {
+        match self.programmer {
+            Programmer::Bootloader if spec.over_isp => Programmer::UsbAsp,
+            programmer => programmer,
+        }
+    }
+
+    /// The spec of the selected board with the choices of the user applied.
+    fn board_spec(&self) -> BoardSpec {
+        self.port_spec(self.selected_port.as_ref())
+    }
+
+    /// The spec of the selected board on the given port with the choices of the user applied.
+    fn port_spec(&self, port: Option<&SerialPortInfo>) -> BoardSpec {
+        let mut spec = self.base_spec();
+        if self.settings.auto_baud && spec.fallback_baud.is_none() {
+            spec.fallback_baud = spec.alternate_bootloader_baud();
+        }
+        if let (Some(fallback), Some(port)) = (spec.fallback_baud, port) {
+            // The baud rate the bootloader answered at last time is tried first.
+            if self.settings.port_bauds.get(&port.port_name) == Some(&fallback) {
+                spec.fallback_baud = spec.baud.replace(fallback);
+            }
+        }
+        // The options of the user win over the ones the bundle was made with.
+        if let Some(manifest) = self.accepted_manifest() {
+            manifest.apply(&mut spec);
+        }
+        self.advanced.apply(&mut spec);
+        spec.avrdude_path = self
+            .avrdude
+            .as_ref()
+            .map(|avrdude| avrdude.executable.clone());
+        // The bundled avrdude needs its own avrdude.conf, unless the user chose another one.
+        spec.avrdude_conf = self.settings.avrdude_conf.clone().or_else(|| {
+            let conf = self
+                .avrdude
+                .as_ref()
+                .and_then(|avrdude| avrdude.conf.as_ref());
+            conf.map(|(conf, _)| conf.clone())
+        });
+        spec.verbosity = self.settings.verbosity;
+        spec.attempts = self.settings.attempts.clamp(1, MAX_ATTEMPTS);
+        if let Some(params) = self.settings.extended_params.get(spec.upload_programmer()) {
+            // Invalid parameters are rejected before a job starts.
+            spec.extended_params = params
+                .iter()
+                .filter(|(key, value)| avrdude::check_extended_param(key, value).is_ok())
+                .cloned()
+                .collect();
+        }
+        spec.reset_pulse = Some(self.settings.reset_pulse).filter(|pulse| {
+            pulse.enabled && spec.uploader == Uploader::Avrdude && spec.uses_serial_port()
+        });
+        spec.timeout = Some(self.settings.timeout_secs)
+            .filter(|&secs| secs > 0)
+            .map(Duration::from_secs);
+        spec.touch_timeout = Duration::from_secs(self.settings.touch_timeout_secs.max(1));
+        // Invalid arguments are rejected before a job starts.
+        if let Ok(args) = avrdude::split_args(&self.settings.extra_avrdude_args) {
+            spec.extra_args.extend(args.into_iter().map(Cow::Owned));
+        }
+        spec
+    }
+
+    /// Let avrdude skip verifying the flash after writing it if the user chose so, the other
+    /// uploaders always verify.
+    fn skip_verification(&self, spec: &mut BoardSpec) {
+        // The lock bits are only written after a verified flash, even one skipped by hand.
+        if self.locks_after_flash(spec) {
+            spec.extra_args.retain(|arg| arg != "-V");
+            return;
+        }
+        let skipped = spec.extra_args.iter().any(|arg| arg == "-V");
+        if self.settings.skip_verify && spec.uploader == Uploader::Avrdude && !skipped {
+            spec.extra_args.push("-V".into());
+        }
+    }
+
+    /// Wether the lock bits are written after flashing the board of the given spec.
+    fn locks_after_flash(&self, spec: &BoardSpec) -> bool {
+        self.lock_after_flash.is_some() && spec.can_write_fuses()
+    }
 
-/// Call avrdude with the given spec to flash the given program to the device connected on the given
-/// serial port.
-fn avrdude(
-    spec: BoardSpec,
-    port: &SerialPortInfo,
-    program_to_flash: &Path,
-) -> (String, io::Result<Output>) {
-    let mut cmd = Command::new("avrdude");
-    cmd.arg("-c")
-        .arg(spec.programmer)
-        .arg("-p")
-        .arg(spec.partno)
-        .arg("-P")
-        .arg(&port.port_name)
-        .arg("-D")
-        .arg("-U")
-        .arg(&format!("flash:w:{}", program_to_flash.display()));
+    /// Remember the avrdude executable chosen by the user and search it again.
+    fn set_avrdude_path(&mut self, path: Option<PathBuf>, ctx: &egui::Context) {
+        // The avrdude bundled with the Arduino IDE needs its own avrdude.conf.
+        if self.settings.avrdude_conf.is_none() {
+            self.settings.avrdude_conf = path.as_deref().and_then(avrdude::sibling_conf);
+        }
+        self.settings.avrdude_path = path;
+        if let Err(e) = self.settings.save() {
+            self.general_error = Some(e.into());
+        }
+        self.avrdude = Some(Avrdude::find(self.settings.avrdude_path.as_deref(), ctx));
+    }
 
-    if spec.do_chip_erase {
-        cmd.arg("-e");
+    /// Show the extended parameters of the given programmer, they can be added, edited and
+    /// removed.
+    fn extended_params_ui(&mut self, ui: &mut egui::Ui, programmer: &str) {
+        ui.label(format!("Extended parameters (-x) for {}:", programmer));
+        let params = self
+            .settings
+            .extended_params
+            .entry(programmer.to_owned())
+            .or_default();
+        let mut changed = false;
+        let mut removed = None;
+        for (i, (key, value)) in params.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                changed |= ui
+                    .add(
+                        egui::TextEdit::singleline(key)
+                            .hint_text("key")
+                            .desired_width(120.0),
+                    )
+                    .changed();
+                ui.label("=");
+                changed |= ui
+                    .add(
+                        egui::TextEdit::singleline(value)
+                            .hint_text("value")
+                            .desired_width(120.0),
+                    )
+                    .changed();
+                if ui.button("Remove").clicked() {
+                    removed = Some(i);
+                }
+                let filled = !key.is_empty() || !value.is_empty();
+                match avrdude::check_extended_param(key, value) {
+                    Err(e) if filled => {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                    _ => (),
+                }
+            });
+        }
+        if let Some(i) = removed {
+            params.remove(i);
+            changed = true;
+        }
+        if ui.button("Add parameter").clicked() {
+            params.push((String::new(), String::new()));
+            changed = true;
+        }
+        if params.is_empty() {
+            self.settings.extended_params.remove(programmer);
+        }
+        if changed {
+            if let Err(e) = self.settings.save() {
+                self.general_error = Some(e.into());
+            }
+        }
     }
 
-    let used_command = format!("CMD: {:?}", cmd);
+    /// Show the configured and the used avrdude executable with its version.
+    fn avrdude_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Executable: ");
+            match self.settings.avrdude_path {
+                Some(ref path) => ui.label(path.display().to_string()),
+                None => ui.label("search in PATH"),
+            };
+            if ui.button("Browse...").clicked() {
+                if let Some(path) = FileDialog::new().pick_file() {
+                    self.set_avrdude_path(Some(path), ui.ctx());
+                }
+            }
+            let clear = ui.add_enabled(
+                self.settings.avrdude_path.is_some(),
+                egui::Button::new("Use PATH"),
+            );
+            if clear.clicked() {
+                self.set_avrdude_path(None, ui.ctx());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("avrdude.conf: ");
+            let found = self.avrdude.as_ref().and_then(|a| a.conf.as_ref());
+            match (&self.settings.avrdude_conf, found) {
+                (Some(path), _) => ui.label(path.display().to_string()),
+                (None, Some((conf, source))) => {
+                    ui.label(format!("{}, {}", source.label(), conf.display()))
+                }
+                (None, None) => ui.label("default of avrdude"),
+            };
+            let mut changed = false;
+            if ui.button("Browse...").clicked() {
+                if let Some(path) = FileDialog::new()
+                    .add_filter("avrdude.conf", &["conf"])
+                    .pick_file()
+                {
+                    self.settings.avrdude_conf = Some(path);
+                    changed = true;
+                }
+            }
+            let clear = ui.add_enabled(
+                self.settings.avrdude_conf.is_some(),
+                egui::Button::new("Use default"),
+            );
+            if clear.clicked() {
+                self.settings.avrdude_conf = None;
+                changed = true;
+            }
+            if changed {
+                if let Err(e) = self.settings.save() {
+                    self.general_error = Some(e.into());
+                }
+            }
+        });
+
+        let Some(ref avrdude) = self.avrdude else {
+            return;
+        };
+        ui.label(format!(
+            "Using {} ({})",
+            avrdude.executable.display(),
+            avrdude.source.label()
+        ));
+        match avrdude.detection {
+            Some(Detection::Version(ref version)) => {
+                ui.label(format!("Version: {}", version));
+                if !avrdude.supports_updi() {
+                    ui.label(format!(
+                        "UPDI boards need avrdude {} or newer and can't be selected",
+                        avrdude::UPDI_VERSION
+                    ));
+                }
+            }
+            Some(Detection::UnknownVersion) => {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    "The executable did not report a version, it may not be avrdude. Features of \
+                    newer versions like UPDI are disabled.",
+                );
+            }
+            Some(Detection::Unavailable(ref unavailable)) => {
+                ui.colored_label(egui::Color32::RED, unavailable.message(&avrdude.executable));
+            }
+            None => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Detecting the version...");
+                });
+            }
+        }
+        if let Some(ref env_path) = avrdude.env_path {
+            ui.label(format!(
+                "{} is set to {}, it is used unless an executable is configured above",
+                avrdude::PATH_VARIABLE,
+                env_path.display()
+            ));
+        }
+        for warning in &avrdude.warnings {
+            ui.colored_label(egui::Color32::YELLOW, warning);
+        }
+    }
 
-    (used_command, cmd.output())
+    /// The spec of the selected board with the choices of the user applied, except for the
+    /// advanced options.
+    fn base_spec(&self) -> BoardSpec {
+        let mut spec = self.selected_board.spec();
+        if spec.pick_partno {
+            spec.partno = self.picked_partno.unwrap_or_default().into();
+        }
+        if spec.uploader == Uploader::Avrdude && !spec.updi {
+            if let Some(isp) = self.programmer(&spec).spec() {
+                spec.use_programmer(isp);
+            }
+        }
+        if spec.supports_builtin_uploader()
+            && self
+                .settings
+                .builtin_uploader
+                .contains(self.selected_board.name())
+        {
+            spec.uploader = Uploader::Stk500;
+        }
+        if spec.uploader == Uploader::Esptool && self.esp_method == EspMethod::Ota {
+            spec.uploader = Uploader::Espota;
+        }
+        if spec.uploader == Uploader::Stm32flash && self.stm32_method == Stm32Method::Dfu {
+            spec.uploader = Uploader::DfuUtil;
+        }
+        if spec.uploader == Uploader::Picotool && self.pico_target != PicoTarget::Picotool {
+            spec.uploader = Uploader::Uf2Copy;
+        }
+        spec
+    }
+
+    /// Select the given file, detect its format and remember it as recently selected.
+    fn select_file(&mut self, path: Option<PathBuf>, ctx: &egui::Context) {
+        self.path_input = path
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        self.path_error = None;
+        if let Some(ref path) = path {
+            self.settings.add_recent_file(path);
+            if let Err(e) = self.settings.save() {
+                self.general_error = Some(e.into());
+            }
+        }
+
+        self.bundle = None;
+        self.bundle_conflict = None;
+        let path = match path {
+            Some(path) if Bundle::is_bundle(&path) => match Bundle::open(&path) {
+                Ok(bundle) => {
+                    let image = bundle.image.clone();
+                    self.apply_manifest(&bundle);
+                    self.bundle = Some(bundle);
+                    Some(image)
+                }
+                Err(e) => {
+                    self.general_error = Some(e.into());
+                    None
+                }
+            },
+            path => path,
+        };
+
+        self.file_format = path.as_deref().map(FirmwareFormat::detect);
+        self.file_info = path
+            .as_deref()
+            .and_then(|path| FileInfo::load(path, &self.settings.version_section, ctx).ok());
+        self.file_changed = false;
+        self.file_path = path;
+    }
+
+    /// Select the firmware of the chosen target if the build system of the project directory is
+    /// known, the newest build artifact otherwise.
+    fn refresh_project(&mut self, ctx: &egui::Context) {
+        let Some(ref dir) = self.project_dir else {
+            return;
+        };
+        self.project_layout = BuildLayout::detect(dir);
+        let artifact = match self.project_layout {
+            Some(ref layout) => {
+                let target = layout
+                    .targets
+                    .iter()
+                    .find(|target| self.project_target.as_ref() == Some(&target.name))
+                    .or_else(|| layout.targets.first());
+                self.project_target = target.map(|target| target.name.clone());
+                match target {
+                    Some(BuildTarget {
+                        artifact: Some(artifact),
+                        ..
+                    }) => Ok(artifact.clone()),
+                    Some(target) => Err(format!(
+                        "Error: the {} {} was not built yet",
+                        layout.system.target_label(),
+                        target.name
+                    )),
+                    None => Err(format!(
+                        "Error: {} defines no {}",
+                        layout.config.display(),
+                        layout.system.target_label()
+                    )),
+                }
+            }
+            None => project::newest_artifact(dir, &self.settings.artifact_pattern),
+        };
+        match artifact {
+            Ok(artifact) => {
+                self.general_error = None;
+                self.select_file(Some(artifact), ctx);
+            }
+            Err(e) => self.general_error = Some(e.into()),
+        }
+    }
+
+    /// Select the board named by the manifest of the bundle, a board picked by the user is only
+    /// replaced once the user agrees.
+    fn apply_manifest(&mut self, bundle: &Bundle) {
+        if let Some(board) = bundle
+            .manifest
+            .board
+            .as_deref()
+            .and_then(|name| self.find_board(name))
+        {
+            if !self.board_picked {
+                self.selected_board = board;
+            } else if board != self.selected_board {
+                self.bundle_conflict = Some(board);
+            }
+        }
+    }
+
+    /// The manifest of the selected bundle if the selected board is the one it is made for.
+    fn accepted_manifest(&self) -> Option<&Manifest> {
+        let bundle = self.bundle.as_ref()?;
+        if self.bundle_conflict.is_some() {
+            return None;
+        }
+        match bundle.manifest.board.as_deref() {
+            Some(name) => {
+                (self.find_board(name)? == self.selected_board).then_some(&bundle.manifest)
+            }
+            None => Some(&bundle.manifest),
+        }
+    }
+
+    /// The built in or custom board with the given name.
+    fn find_board(&self, name: &str) -> Option<SelectedBoard> {
+        let name = name.trim();
+        ArduinoBoard::ALL
+            .into_iter()
+            .find(|board| {
+                board.label().eq_ignore_ascii_case(name)
+                    || format!("{:?}", board).eq_ignore_ascii_case(name)
+            })
+            .map(SelectedBoard::Builtin)
+            .or_else(|| {
+                self.custom_boards
+                    .iter()
+                    .find(|board| board.name.eq_ignore_ascii_case(name))
+                    .cloned()
+                    .map(SelectedBoard::Custom)
+            })
+    }
+
+    /// Reload the information about the selected file if it changed on disk.
+    fn refresh_file_info(&mut self, ctx: &egui::Context) {
+        let Some(ref mut info) = self.file_info else {
+            return;
+        };
+        info.poll();
+        // The file is polled as nothing else repaints the window when it changes.
+        ctx.request_repaint_after(FILE_SETTLE_TIME);
+        if !info.is_outdated() {
+            self.file_settling = None;
+            return;
+        }
+
+        // Build tools may still be writing the file, so wait until it stopped changing.
+        let now = file_info::stamp(&info.path);
+        match self.file_settling {
+            Some((since, ref stamp)) if *stamp == now => {
+                if since.elapsed() < FILE_SETTLE_TIME {
+                    return;
+                }
+            }
+            _ => {
+                self.file_settling = Some((Instant::now(), now));
+                return;
+            }
+        }
+
+        let path = info.path.clone();
+        self.file_settling = None;
+        self.file_format = Some(FirmwareFormat::detect(&path));
+        self.file_info = FileInfo::load(&path, &self.settings.version_section, ctx).ok();
+        self.file_changed = true;
+        if self.auto_flash && !self.is_busy() {
+            match self.start_flash(ctx) {
+                Ok(()) => self.general_error = None,
+                Err(e) => self.general_error = Some(e),
+            }
+        }
+    }
+
+    /// Select the dropped file if it is the only one and has an extension the uploader accepts.
+    fn drop_files(&mut self, dropped: &[egui::DroppedFile], ctx: &egui::Context) {
+        let [file] = dropped else {
+            self.general_error = Some("Error: drop only one file at once".into());
+            return;
+        };
+        let Some(ref path) = file.path else {
+            self.general_error = Some("Error: the dropped file has no path".into());
+            return;
+        };
+
+        match self.check_file(path) {
+            Ok(()) => {
+                self.general_error = None;
+                self.select_file(Some(path.clone()), ctx);
+            }
+            Err(e) => self.general_error = Some(e),
+        }
+    }
+
+    /// Check that the given path is a file with an extension the uploader accepts.
+    fn check_file(&self, path: &Path) -> Result<(), Cow<'static, str>> {
+        if !path.exists() {
+            return Err(format!("Error: {} does not exist", path.display()).into());
+        }
+        if !path.is_file() {
+            return Err(format!("Error: {} is not a file", path.display()).into());
+        }
+
+        let extensions: Vec<&str> = self
+            .board_spec()
+            .uploader
+            .file_filters()
+            .iter()
+            .flat_map(|(_, extensions)| extensions.iter().copied())
+            .collect();
+        let accepted = Bundle::is_bundle(path)
+            || path.extension().is_some_and(|ext| {
+                extensions
+                    .iter()
+                    .any(|accepted| ext.eq_ignore_ascii_case(accepted))
+            });
+        if !accepted {
+            return Err(format!(
+                "Error: {} can't be flashed to the selected board, accepted are .{} and .zip bundles",
+                path.display(),
+                extensions.join(", .")
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// The filters of the file dialog, the last used one first as the dialog starts with it.
+    fn file_filters(&self) -> Vec<(&'static str, &'static [&'static str])> {
+        let mut filters = self.board_spec().uploader.file_filters().to_vec();
+        filters.push(("firmware bundle", &["zip"]));
+        filters.push(ALL_FILES);
+        if let Some(ref last) = self.settings.last_filter {
+            if let Some(index) = filters.iter().position(|(name, _)| name == last) {
+                let last = filters.remove(index);
+                filters.insert(0, last);
+            }
+        }
+        filters
+    }
+
+    /// Select the file entered in the path field if it differs from the selected one.
+    fn apply_path_input(&mut self, ctx: &egui::Context) -> Result<(), Cow<'static, str>> {
+        if self.path_input.trim().is_empty() {
+            return Ok(());
+        }
+        let path = parse_path_input(&self.path_input);
+        let selected = match self.bundle {
+            Some(ref bundle) => Some(&bundle.source),
+            None => self.file_path.as_ref(),
+        };
+        if selected != Some(&path) {
+            self.check_file(&path)?;
+            self.select_file(Some(path), ctx);
+        }
+        Ok(())
+    }
+
+    /// The warnings about the selected file.
+    fn file_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let (Some(path), Some(Ok(format))) = (&self.file_path, &self.file_format) else {
+            return warnings;
+        };
+
+        let unknown_board = self.bundle.as_ref().and_then(|bundle| {
+            let board = bundle.manifest.board.as_deref()?;
+            self.find_board(board).is_none().then_some(board)
+        });
+        let stale = self
+            .project_layout
+            .as_ref()
+            .filter(|layout| path.starts_with(layout.config.parent().unwrap_or(path)))
+            .filter(|layout| layout.is_stale(path));
+        if let Some(layout) = stale {
+            warnings.push(format!(
+                "Warning: {} changed after the firmware was built, rebuild the project",
+                layout.system.config_file()
+            ));
+        }
+        if let Some(board) = unknown_board {
+            warnings.push(format!(
+                "Warning: the bundle is made for the unknown board {}, check the selected board",
+                board
+            ));
+        }
+
+        let by_extension = FirmwareFormat::from_extension(path);
+        if by_extension != FirmwareFormat::Auto && by_extension != *format {
+            warnings.push(format!(
+                "Warning: the file is named like a {} file but contains a {} file, \
+                it is flashed as {}",
+                by_extension.label(),
+                format.label(),
+                format.label()
+            ));
+        }
+
+        // Raw binaries larger than the flash would be truncated without any error.
+        if *format == FirmwareFormat::Raw {
+            let chip = self.base_spec().chip();
+            let size = fs::metadata(path).map(|meta| meta.len());
+            if let (Some(chip), Ok(size)) = (chip, size) {
+                if size > u64::from(chip.flash_size) {
+                    warnings.push(format!(
+                        "Warning: the file has {} bytes, the {} only has {} of flash",
+                        size,
+                        chip.name,
+                        format_size(chip.flash_size)
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Check that the selected Intel HEX file was parsed without errors.
+    fn check_hex(&self) -> Result<(), Cow<'static, str>> {
+        let Some(ref info) = self.file_info else {
+            return Ok(());
+        };
+        if info.is_parsing_hex() {
+            return Err("Error: the Intel HEX file is still being checked".into());
+        }
+        match info.hex {
+            Some(Err(ref e)) => Err(format!(
+                "{}, enable the HEX check override in the advanced options to flash it anyway",
+                e
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Start installing the selected file to the selected board on a background thread.
+    fn start_flash(&mut self, ctx: &egui::Context) -> Result<(), Cow<'static, str>> {
+        self.start_program_job(JobKind::Flash, ctx)
+    }
+
+    /// Start comparing the flash of the selected board with the selected file on a background
+    /// thread.
+    fn start_verify(&mut self, ctx: &egui::Context) -> Result<(), Cow<'static, str>> {
+        self.start_program_job(JobKind::Verify, ctx)
+    }
+
+    /// Start the given job with the selected file as its program, after checking that the file
+    /// fits the selected board.
+    fn start_program_job(
+        &mut self,
+        kind: JobKind,
+        ctx: &egui::Context,
+    ) -> Result<(), Cow<'static, str>> {
+        let port = self.selected_port.clone();
+        let job = self.program_job(kind, port.as_ref(), ctx)?;
+        self.start(job, ctx)?;
+        if let (Some(cmd), Some(info)) = (&mut self.used_command, &self.file_info) {
+            cmd.push('\n');
+            cmd.push_str(&info.summary());
+        }
+        Ok(())
+    }
+
+    /// Build the given job with the selected file as its program for the given port, after
+    /// checking that the file fits the selected board.
+    fn program_job(
+        &mut self,
+        kind: JobKind,
+        port: Option<&SerialPortInfo>,
+        ctx: &egui::Context,
+    ) -> Result<UploadJob, Cow<'static, str>> {
+        self.file_changed = false;
+        self.apply_path_input(ctx)?;
+        let Some(path) = self.file_path.clone() else {
+            return Err("Error: no file selected".into());
+        };
+        self.advanced.validate()?;
+        // The file may have changed since it was selected.
+        let detected = FirmwareFormat::detect(&path);
+        self.file_format = Some(detected.clone());
+        let format = detected?;
+
+        let mut spec = self.port_spec(port);
+        if spec.partno.is_empty() {
+            return Err("Error: no chip selected".into());
+        }
+        if kind == JobKind::Flash {
+            self.skip_verification(&mut spec);
+        }
+        if spec.uploader == Uploader::Avrdude {
+            if let Some(ref mut avrdude) = self.avrdude {
+                avrdude.check()?;
+            }
+        }
+        if format == FirmwareFormat::Elf {
+            self.check_machine(&spec, &path)?;
+        }
+        let invalid_hex = format == FirmwareFormat::IntelHex && self.check_hex().is_err();
+        if invalid_hex && !self.advanced.ignore_invalid_hex {
+            self.check_hex()?;
+        }
+        // The size of an invalid file which is flashed anyway is unknown.
+        if !self.advanced.ignore_size && !invalid_hex {
+            check_size(&spec, &path, format)?;
+        }
+        let port = self.port_of(&spec, port)?;
+        let ota = match spec.uploader {
+            Uploader::Espota => Some(self.ota.target(spec.ota_port)?),
+            _ => None,
+        };
+        let signature_check = match spec.chip().and_then(|chip| chip.signature) {
+            Some(expected)
+                if kind == JobKind::Flash
+                    && spec.uploader == Uploader::Avrdude
+                    && self.settings.check_signature
+                    // A separate run would end the bootloader session before the flash, avrdude
+                    // still checks the signature while flashing.
+                    && !spec.single_bootloader_session() =>
+            {
+                Some(SignatureCheck {
+                    expected,
+                    board: self.selected_board.name().to_owned(),
+                })
+            }
+            _ => None,
+        };
+        let (before_flash, after_flash) = self.flash_hooks(kind);
+        let backup = self.backup_target(kind, &spec, port.as_deref())?;
+        let fuses = match self.fuses_with_flash {
+            Some((layout, values)) if kind == JobKind::Flash => {
+                if !spec.can_write_fuses() {
+                    return Err("Error: the fuses can only be written through an external \
+                        programmer, bootloaders can't write them"
+                        .into());
+                }
+                if FuseLayout::of(&spec.partno)? != layout {
+                    return Err(
+                        "Error: the fuses written with the flash were entered for another \
+                        chip, enter them again for the selected board"
+                            .into(),
+                    );
+                }
+                Some((layout, values))
+            }
+            _ => None,
+        };
+        let lock = match self.lock_after_flash {
+            Some(lock) if kind == JobKind::Flash => {
+                if !spec.can_write_fuses() {
+                    return Err("Error: the lock bits can only be set through an external \
+                        programmer, bootloaders can't write them"
+                        .into());
+                }
+                Some(lock)
+            }
+            _ => None,
+        };
+        let eeprom = match self.eeprom_path {
+            Some(ref path) if kind == JobKind::Flash && spec.uploader == Uploader::Avrdude => {
+                Some(MemoryFile {
+                    path: path.clone(),
+                    format: FirmwareFormat::detect(path)?,
+                })
+            }
+            _ => None,
+        };
+        let job = UploadJob {
+            eeprom,
+            ota,
+            fuses,
+            lock,
+            backup,
+            before_flash,
+            after_flash,
+            signature_check,
+            ..UploadJob::new(kind, spec, port, path, format)
+        };
+        Ok(job)
+    }
+
+    /// Check that the given ELF file was built for the architecture of the selected board.
+    fn check_machine(&self, spec: &BoardSpec, path: &Path) -> Result<(), Cow<'static, str>> {
+        let machine = firmware::elf_machine(path)?;
+        if machine != spec.elf_machine() {
+            return Err(format!(
+                "Error: the firmware was built for {} but the {} needs {}",
+                elf::machine_name(machine),
+                self.selected_board.name(),
+                elf::machine_name(spec.elf_machine())
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Start burning the bootloader of the selected board through the selected programmer on a
+    /// background thread.
+    fn start_burn_bootloader(&mut self, ctx: &egui::Context) -> Result<(), Cow<'static, str>> {
+        self.advanced.validate()?;
+
+        let spec = self.board_spec();
+        let Some(bootloader) = spec.bootloader else {
+            return Err("Error: the bootloader of the selected board is unknown".into());
+        };
+        if spec.isp.is_none() {
+            return Err("Error: select an external programmer to burn the bootloader".into());
+        }
+        let Some(image) = bootloader.find_image() else {
+            return Err(format!(
+                "Error: could not find the bootloader image {}, place it in a bootloaders \
+                directory next to the executable",
+                bootloader.image
+            )
+            .into());
+        };
+
+        let port = self.upload_port(&spec)?;
+        let job = UploadJob::new(
+            JobKind::BurnBootloader(bootloader),
+            spec,
+            port,
+            image,
+            FirmwareFormat::IntelHex,
+        );
+        self.start(job, ctx)
+    }
+
+    /// Start the given job saving a memory of the selected board to the given file on a background
+    /// thread, the format is chosen by the extension of the file.
+    fn start_read(
+        &mut self,
+        kind: JobKind,
+        destination: PathBuf,
+        ctx: &egui::Context,
+    ) -> Result<(), Cow<'static, str>> {
+        self.advanced.validate()?;
+        let spec = self.board_spec();
+        if spec.partno.is_empty() {
+            return Err("Error: no chip selected".into());
+        }
+        let format = match FirmwareFormat::from_extension(&destination) {
+            FirmwareFormat::IntelHex => FirmwareFormat::IntelHex,
+            _ => FirmwareFormat::Raw,
+        };
+
+        let port = self.upload_port(&spec)?;
+        let job = UploadJob::new(kind, spec, port, destination, format);
+        self.start(job, ctx)
+    }
+
+    /// Start writing the given file to the EEPROM of the selected board on a background thread,
+    /// after checking that it fits the EEPROM of the chip.
+    fn start_write_eeprom(
+        &mut self,
+        source: PathBuf,
+        ctx: &egui::Context,
+    ) -> Result<(), Cow<'static, str>> {
+        self.advanced.validate()?;
+        let spec = self.board_spec();
+        if spec.partno.is_empty() {
+            return Err("Error: no chip selected".into());
+        }
+        let format = FirmwareFormat::detect(&source)?;
+        if !matches!(format, FirmwareFormat::IntelHex | FirmwareFormat::Raw) {
+            return Err("Error: the EEPROM can only be written from Intel HEX or raw files".into());
+        }
+        if let Some(chip) = spec.chip() {
+            let size = firmware::program_size(&source, format)?;
+            if chip.eeprom_size == 0 {
+                return Err(format!("Error: the {} has no EEPROM", chip.name).into());
+            }
+            if size > u64::from(chip.eeprom_size) && !self.advanced.ignore_size {
+                return Err(format!(
+                    "Error: the EEPROM data is {} bytes but the {} has {} bytes of EEPROM",
+                    size, chip.name, chip.eeprom_size
+                )
+                .into());
+            }
+        }
+
+        let port = self.upload_port(&spec)?;
+        let job = UploadJob::new(JobKind::WriteEeprom, spec, port, source, format);
+        self.start(job, ctx)
+    }
+
+    /// Start printing the fuses or the lock byte of the selected board on a background thread.
+    fn start_read_fuses(
+        &mut self,
+        lock: bool,
+        ctx: &egui::Context,
+    ) -> Result<(), Cow<'static, str>> {
+        self.advanced.validate()?;
+        let spec = self.board_spec();
+        if spec.partno.is_empty() {
+            return Err("Error: no chip selected".into());
+        }
+        let layout = if lock {
+            FuseLayout::lock(&spec.partno)?
+        } else {
+            FuseLayout::of(&spec.partno)?
+        };
+
+        let port = self.upload_port(&spec)?;
+        let job = UploadJob::new(
+            JobKind::ReadFuses(layout),
+            spec,
+            port,
+            PathBuf::new(),
+            FirmwareFormat::Raw,
+        );
+        self.start(job, ctx)
+    }
+
+    /// Start writing the given fuse values through the selected programmer on a background thread.
+    fn start_write_fuses(
+        &mut self,
+        layout: FuseLayout,
+        values: [u8; 3],
+        ctx: &egui::Context,
+    ) -> Result<(), Cow<'static, str>> {
+        self.advanced.validate()?;
+        let spec = self.board_spec();
+        if !spec.can_write_fuses() {
+            return Err("Error: select an external programmer to write the fuses".into());
+        }
+
+        let port = self.upload_port(&spec)?;
+        let job = UploadJob::new(
+            JobKind::WriteFuses(layout, values),
+            spec,
+            port,
+            PathBuf::new(),
+            FirmwareFormat::Raw,
+        );
+        self.start(job, ctx)
+    }
+
+    /// Ask for the file the given read job saves to, existing files are only overwritten after the
+    /// user confirmed it.
+    fn choose_read_destination(&mut self, kind: JobKind, file_name: &str, ctx: &egui::Context) {
+        let dialog = FileDialog::new()
+            .add_filter("Intel HEX", &["hex"])
+            .add_filter("Raw binary", &["bin"])
+            .set_file_name(file_name);
+        let Some(destination) = dialog.save_file() else {
+            return;
+        };
+        if destination.exists() {
+            self.confirm_read = Some((kind, destination));
+        } else {
+            match self.start_read(kind, destination, ctx) {
+                Ok(()) => self.general_error = None,
+                Err(e) => self.general_error = Some(e),
+            }
+        }
+    }
+
+    /// Start erasing the chip of the selected board on a background thread, no file is needed.
+    fn start_erase(&mut self, ctx: &egui::Context) -> Result<(), Cow<'static, str>> {
+        self.advanced.validate()?;
+        let spec = self.board_spec();
+        if spec.partno.is_empty() {
+            return Err("Error: no chip selected".into());
+        }
+        if !spec.can_erase_chip() {
+            return Err(
+                "Error: the bootloader can't erase the chip, select an external \
+                programmer"
+                    .into(),
+            );
+        }
+
+        let port = self.upload_port(&spec)?;
+        let job = UploadJob::new(
+            JobKind::Erase,
+            spec,
+            port,
+            PathBuf::new(),
+            FirmwareFormat::Raw,
+        );
+        self.start(job, ctx)
+    }
+
+    /// Start talking to the chip with writing disabled on a background thread, the selected file
+    /// is used if there is one but none is needed.
+    fn start_test_communication(&mut self, ctx: &egui::Context) -> Result<(), Cow<'static, str>> {
+        self.advanced.validate()?;
+        let mut spec = self.board_spec();
+        if spec.partno.is_empty() {
+            return Err("Error: no chip selected".into());
+        }
+        if let Some(ref mut avrdude) = self.avrdude {
+            avrdude.check()?;
+        }
+        // Nothing is written anyway, but the test should not even ask for an erase.
+        spec.erase = EraseMode::Auto;
+        let port = self.upload_port(&spec)?;
+        let (program, format) = match (&self.file_path, &self.file_format) {
+            (Some(path), Some(Ok(format))) if path.is_file() => (path.clone(), *format),
+            _ => (PathBuf::new(), FirmwareFormat::Raw),
+        };
+        let job = UploadJob::new(JobKind::TestCommunication, spec, port, program, format);
+        self.start(job, ctx)
+    }
+
+    /// The commands flashing the selected file with the current choices, missing inputs are shown
+    /// as placeholders. Nothing is run or validated.
+    fn command_preview(&self) -> String {
+        let mut spec = self.board_spec();
+        self.skip_verification(&mut spec);
+        if spec.partno.is_empty() {
+            spec.partno = "<no chip selected>".into();
+        }
+        let port = self
+            .upload_port(&spec)
+            .unwrap_or_else(|_| Some("<no port selected>".to_owned()));
+        let program = self
+            .file_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("<no file selected>"));
+        let format = match self.file_format {
+            Some(Ok(format)) => format,
+            _ => FirmwareFormat::from_extension(&program),
+        };
+        let eeprom = match self.eeprom_path {
+            Some(ref path) if spec.uploader == Uploader::Avrdude => Some(MemoryFile {
+                path: path.clone(),
+                format: FirmwareFormat::from_extension(path),
+            }),
+            _ => None,
+        };
+        let ota = match spec.uploader {
+            Uploader::Espota => Some(self.ota.target(spec.ota_port).unwrap_or(OtaTarget {
+                host: "<no ip address entered>".to_owned(),
+                port: spec.ota_port,
+                password: String::new(),
+            })),
+            _ => None,
+        };
+        let fuses = self.fuses_with_flash.filter(|(layout, _)| {
+            spec.can_write_fuses() && FuseLayout::of(&spec.partno).as_ref() == Ok(layout)
+        });
+        let lock = self.lock_after_flash.filter(|_| spec.can_write_fuses());
+        let (before_flash, after_flash) = self.flash_hooks(JobKind::Flash);
+        let backup = self
+            .backup_target(JobKind::Flash, &spec, port.as_deref())
+            .unwrap_or_default();
+        UploadJob {
+            eeprom,
+            ota,
+            fuses,
+            lock,
+            backup,
+            before_flash,
+            after_flash,
+            ..UploadJob::new(JobKind::Flash, spec, port, program, format)
+        }
+        .describe()
+    }
+
+    /// The new backup the flash is read to before a job of the given kind overwrites it on the
+    /// given port, `None` if no backup is taken.
+    fn backup_target(
+        &self,
+        kind: JobKind,
+        spec: &BoardSpec,
+        port: Option<&str>,
+    ) -> Result<Option<(PathBuf, usize)>, Cow<'static, str>> {
+        if !self.settings.backup_before_flash
+            || kind != JobKind::Flash
+            || spec.uploader != Uploader::Avrdude
+        {
+            return Ok(None);
+        }
+        if spec.single_bootloader_session() {
+            return Err(
+                "Error: the bootloader of the board only serves a single run, it can't \
+                be backed up before flashing, turn off the backup or flash through an external \
+                programmer"
+                    .into(),
+            );
+        }
+        let dir = self
+            .settings
+            .backup_dir
+            .clone()
+            .or_else(backup::default_dir)
+            .ok_or(
+                "Error: there is no data directory for the backups, choose a backup directory",
+            )?;
+        let path = backup::new_path(&dir, port.unwrap_or("board"), SystemTime::now());
+        Ok(Some((path, self.settings.kept_backups)))
+    }
+
+    /// Show the options of the backups taken before flashing the board of the given spec.
+    fn backup_ui(&mut self, ui: &mut egui::Ui, spec: &BoardSpec) {
+        let mut changed = ui
+            .checkbox(
+                &mut self.settings.backup_before_flash,
+                "Back up the flash before flashing",
+            )
+            .on_hover_text(
+                "The flash is read to a file named after the port and the time first, nothing is \
+                written if the read fails",
+            )
+            .changed();
+        if self.settings.backup_before_flash && spec.single_bootloader_session() {
+            ui.colored_label(egui::Color32::YELLOW, backup::SINGLE_SESSION_WARNING);
+        } else if self.settings.backup_before_flash && !spec.uses_isp() {
+            ui.colored_label(egui::Color32::YELLOW, backup::SLOW_WARNING);
+        }
+        ui.horizontal(|ui| {
+            ui.label("Backup directory: ");
+            let dir = self
+                .settings
+                .backup_dir
+                .clone()
+                .or_else(backup::default_dir);
+            match dir {
+                Some(ref dir) => ui.label(dir.to_string_lossy().as_ref()),
+                None => ui.label("<no data directory>"),
+            };
+            if ui.button("Choose...").clicked() {
+                let mut dialog = FileDialog::new();
+                if let Some(ref dir) = dir {
+                    dialog = dialog.set_directory(dir);
+                }
+                if let Some(dir) = dialog.pick_folder() {
+                    self.settings.backup_dir = Some(dir);
+                    changed = true;
+                }
+            }
+            if self.settings.backup_dir.is_some() && ui.button("Default").clicked() {
+                self.settings.backup_dir = None;
+                changed = true;
+            }
+        });
+        let restore = ui
+            .add_enabled(!self.is_busy(), egui::Button::new("Restore..."))
+            .on_hover_text("Flash one of the backups of the selected port back and verify it");
+        if restore.clicked() {
+            match self.list_backups(spec) {
+                Ok(backups) => self.restore_list = Some(backups),
+                Err(e) => self.general_error = Some(e),
+            }
+        }
+        ui.horizontal(|ui| {
+            ui.label("Backups kept per port: ");
+            changed |= ui
+                .add(egui::DragValue::new(&mut self.settings.kept_backups).clamp_range(1..=1000))
+                .changed();
+        });
+        if changed {
+            if let Err(e) = self.settings.save() {
+                self.general_error = Some(e.into());
+            }
+        }
+    }
+
+    /// The name of the job in flight, like "Flash" or "Batch flash", `None` if nothing runs. Jobs
+    /// can't be started and the ports are not scanned while something runs, and the state ends
+    /// with the job however it ends.
+    fn busy(&self) -> Option<&'static str> {
+        match (&self.running, &self.batch) {
+            (Some(running), _) => Some(running.kind.name()),
+            (None, Some(batch)) if !batch.is_done() => Some("Batch flash"),
+            _ => None,
+        }
+    }
+
+    /// Wether a job is in flight.
+    fn is_busy(&self) -> bool {
+        self.busy().is_some()
+    }
+
+    /// Start flashing each new board, which is only allowed after the selected file was flashed to
+    /// the selected board manually.
+    fn start_production(&mut self) -> Result<(), Cow<'static, str>> {
+        if self.dry_run {
+            return Err("Error: a dry run flashes no boards, turn it off first".into());
+        }
+        let Some(file) = self.file_path.clone() else {
+            return Err("Error: no file selected".into());
+        };
+        let confirmed = self.history.iter().any(|entry| {
+            entry.is_successful_flash()
+                && entry.board == self.selected_board
+                && entry.file.as_ref() == Some(&file)
+        });
+        if !confirmed {
+            return Err(
+                "Error: flash the selected file to the selected board manually first, \
+                production mode only repeats a flash which worked"
+                    .into(),
+            );
+        }
+        if self.board_spec().usb_ids.is_empty() {
+            return Err(format!(
+                "Error: new boards can't be recognized, the {} has no known USB ids",
+                self.selected_board.name()
+            )
+            .into());
+        }
+        portscan(&mut self.available_ports, &mut self.port_scan_error);
+        self.production = Some(Production::new(
+            self.selected_board.clone(),
+            file,
+            &self.available_ports,
+        ));
+        Ok(())
+    }
+
+    /// Scan the ports while the production mode is on and flash each new board once it settled.
+    fn production_tick(&mut self, ctx: &egui::Context) {
+        let Some(mut production) = self.production.take() else {
+            return;
+        };
+        ctx.request_repaint_after(production::SCAN_INTERVAL);
+        if production.board != self.selected_board
+            || self.file_path.as_ref() != Some(&production.file)
+        {
+            self.general_error =
+                Some("Production mode stopped, the board or the file was changed".into());
+            return;
+        }
+        if self.dry_run {
+            self.general_error =
+                Some("Production mode stopped, a dry run flashes no boards".into());
+            return;
+        }
+        // Scanning could disturb the running flash.
+        if production.scan_due() && !self.is_busy() {
+            portscan(&mut self.available_ports, &mut self.port_scan_error);
+            let spec = self.board_spec();
+            let matching: Vec<&SerialPortInfo> = self
+                .available_ports
+                .iter()
+                .filter(|port| spec.matches_port(port))
+                .collect();
+            if let Some(port) = production.update(&matching) {
+                self.selected_port = Some(port);
+                match self.start_flash(ctx) {
+                    // A dry run returns without starting a job.
+                    Ok(()) if self.running.is_none() => production.finished(false),
+                    Ok(()) => self.general_error = None,
+                    Err(e) => {
+                        self.general_error = Some(e);
+                        production.finished(false);
+                    }
+                }
+            }
+        }
+        self.production = Some(production);
+    }
+
+    /// Start flashing the selected file to the given ports, one after another or at the same time.
+    fn start_batch(
+        &mut self,
+        ports: Vec<SerialPortInfo>,
+        stop_on_failure: bool,
+    ) -> Result<(), Cow<'static, str>> {
+        if let Some(name) = self.busy() {
+            return Err(format!("Error: wait until the {} job is done", name).into());
+        }
+        if self.production.is_some() {
+            return Err("Error: stop the production mode first".into());
+        }
+        if self.dry_run {
+            return Err("Error: a dry run checks a single port, use the Flash button".into());
+        }
+        let spec = self.board_spec();
+        // An ISP programmer is a single device, only the bootloaders of the boards are separate.
+        if self.settings.parallel_flash && spec.uses_isp() {
+            return Err(
+                "Error: only boards flashed through their bootloader can be flashed at \
+                the same time, an ISP programmer can only flash one board at a time"
+                    .into(),
+            );
+        }
+        // The bootloader ports of boards reset at the same time can't be told apart.
+        if self.settings.parallel_flash && (spec.touch_1200bps || spec.needs_reset) {
+            return Err(format!(
+                "Error: the {} starts its bootloader on a new port, such boards can only be \
+                flashed one after another",
+                self.selected_board.name()
+            )
+            .into());
+        }
+        if ports.is_empty() {
+            return Err("Error: none of the ports is connected".into());
+        }
+        let concurrency = if self.settings.parallel_flash {
+            self.settings.flash_concurrency
+        } else {
+            1
+        };
+        self.batch = Some(Batch::new(ports, concurrency, stop_on_failure));
+        Ok(())
+    }
+
+    /// Collect the finished flashes of the batch and start the next ports while workers are free.
+    fn batch_tick(&mut self, ctx: &egui::Context) {
+        let Some(ref mut batch) = self.batch else {
+            return;
+        };
+        let mut finished = Vec::new();
+        let mut i = 0;
+        while i < batch.workers.len() {
+            match batch.workers[i].upload.poll() {
+                Some(res) => finished.push((batch.workers.remove(i), res)),
+                None => i += 1,
+            }
+        }
+        for (worker, res) in finished {
+            let result = self.finish_job(&worker.upload, res, worker.info);
+            self.history.insert(
+                0,
+                HistoryEntry {
+                    result: Some(result.outcome.label()),
+                    succeeded: matches!(result.outcome, Outcome::Succeeded(_)),
+                    ..worker.entry
+                },
+            );
+            self.history.truncate(KEPT_HISTORY);
+            if let Some(ref mut batch) = self.batch {
+                batch.finished(worker.port, result.outcome.to_result(), result.output);
+            }
+        }
+
+        while let Some(port) = self.batch.as_mut().and_then(Batch::next) {
+            let name = port.port_name.clone();
+            let started = self.start_worker(port, ctx);
+            let Some(ref mut batch) = self.batch else {
+                return;
+            };
+            match started {
+                Ok(worker) => batch.workers.push(worker),
+                Err(e) => batch.finished(name, Err(e.into_owned()), String::new()),
+            }
+        }
+        if self.batch.as_ref().is_some_and(|batch| !batch.is_done()) {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    /// Start flashing the selected file to the given port as a worker of the batch.
+    fn start_worker(
+        &mut self,
+        port: SerialPortInfo,
+        ctx: &egui::Context,
+    ) -> Result<Worker, Cow<'static, str>> {
+        let job = self.program_job(JobKind::Flash, Some(&port), ctx)?;
+        self.check_job(&job)?;
+        create_backup_dir(&job)?;
+        let info = self.run_info(&job);
+        let name = port.port_name.clone();
+        let entry = self.history_entry(&job, Some(port));
+        let uploader = job.spec.uploader;
+        Ok(Worker {
+            port: name,
+            upload: RunningUpload::start(job, uploader, ctx.clone()),
+            info,
+            entry,
+        })
+    }
+
+    /// Show the ports which can be flashed together and the button starting the batch.
+    fn batch_ui(&mut self, ui: &mut egui::Ui, spec: &BoardSpec) {
+        for info in self.shown_ports(spec) {
+            let mut checked = self.batch_ports.contains(&info.port_name);
+            if ui
+                .checkbox(
+                    &mut checked,
+                    format!("{:?}: {}", info.port_type, info.port_name),
+                )
+                .changed()
+            {
+                if checked {
+                    self.batch_ports.insert(info.port_name.clone());
+                } else {
+                    self.batch_ports.remove(&info.port_name);
+                }
+            }
+        }
+        ui.checkbox(&mut self.batch_stop_on_failure, "Stop on the first failure");
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add_enabled(
+                    !spec.uses_isp(),
+                    egui::Checkbox::new(
+                        &mut self.settings.parallel_flash,
+                        "Flash the ports at the same time, at most",
+                    ),
+                )
+                .on_disabled_hover_text("An ISP programmer can only flash one board at a time")
+                .changed();
+            changed |= ui
+                .add_enabled(
+                    self.settings.parallel_flash && !spec.uses_isp(),
+                    egui::DragValue::new(&mut self.settings.flash_concurrency)
+                        .clamp_range(1..=batch::MAX_CONCURRENCY),
+                )
+                .changed();
+        });
+        if changed {
+            if let Err(e) = self.settings.save() {
+                self.general_error = Some(e.into());
+            }
+        }
+        let idle = !self.is_busy() && self.production.is_none();
+        let flash_all = ui
+            .add_enabled(
+                idle && !self.batch_ports.is_empty(),
+                egui::Button::new(format!("Flash all ({})", self.batch_ports.len())),
+            )
+            .on_hover_text("Flash the selected file to each checked port");
+        if flash_all.clicked() {
+            let ports = self
+                .available_ports
+                .iter()
+                .filter(|port| self.batch_ports.contains(&port.port_name))
+                .cloned()
+                .collect();
+            match self.start_batch(ports, self.batch_stop_on_failure) {
+                Ok(()) => self.general_error = None,
+                Err(e) => self.general_error = Some(e),
+            }
+        }
+    }
+
+    /// Find the connected ports with the usb ids of the selected board, which the user is asked to
+    /// confirm flashing.
+    fn find_matching(&mut self) -> Result<(), Cow<'static, str>> {
+        portscan(&mut self.available_ports, &mut self.port_scan_error);
+        let spec = self.board_spec();
+        let matching: Vec<SerialPortInfo> = self
+            .available_ports
+            .iter()
+            .filter(|port| spec.matches_port(port))
+            .cloned()
+            .collect();
+        if matching.is_empty() {
+            return Err(format!(
+                "Error: no connected port matches the {}",
+                self.selected_board.name()
+            )
+            .into());
+        }
+        self.confirm_matching = Some(matching);
+        Ok(())
+    }
+
+    /// Show the state of each port of the batch, the output of the chosen port and a summary
+    /// once all are done.
+    fn batch_status_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(ref mut batch) = self.batch else {
+            return;
+        };
+        let mut close = false;
+        let mut shown = batch.shown.clone();
+        ui.horizontal(|ui| {
+            if batch.is_done() {
+                ui.label(format!(
+                    "Batch done, {} of {} ports succeeded",
+                    batch.succeeded(),
+                    batch.total
+                ));
+                close = ui.button("Close").clicked();
+            } else {
+                ui.spinner();
+                ui.label(format!(
+                    "Flashing {} ports, {} finished",
+                    batch.total,
+                    batch.results.len()
+                ));
+                // The running flashes are finished, only the queued ports are skipped.
+                if ui.button("Stop").clicked() {
+                    batch.skip_remaining("skipped, the batch was stopped");
+                }
+            }
+        });
+        egui::Grid::new("BatchPorts").striped(true).show(ui, |ui| {
+            for (port, state) in batch.states() {
+                ui.label(port);
+                match state {
+                    PortState::Queued => ui.label("queued"),
+                    PortState::Flashing(fraction) => ui.add(
+                        egui::ProgressBar::new(fraction)
+                            .text(format!("flashing {:.0}%", fraction * 100.0)),
+                    ),
+                    PortState::Verifying(fraction) => ui.add(
+                        egui::ProgressBar::new(fraction)
+                            .text(format!("verifying {:.0}%", fraction * 100.0)),
+                    ),
+                    PortState::Done => ui.colored_label(egui::Color32::GREEN, "done"),
+                    PortState::Failed(e) => ui.colored_label(egui::Color32::RED, e),
+                };
+                if state != PortState::Queued && ui.small_button("Output").clicked() {
+                    shown = Some(port.to_owned()).filter(|port| shown.as_ref() != Some(port));
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(output) = shown.as_deref().and_then(|port| batch.output(port)) {
+            output_view(ui, &output);
+        }
+        batch.shown = shown;
+        if close {
+            self.batch = None;
+        }
+    }
+
+    /// Show the switch of the production mode and the result of the last board.
+    fn production_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(ref production) = self.production else {
+            let start = ui
+                .add_enabled(!self.is_busy(), egui::Button::new("Start production mode"))
+                .on_hover_text(
+                    "Flash each newly connected board with the USB ids of the selected board, \
+                    after the selected file was flashed to it manually once",
+                );
+            if start.clicked() {
+                match self.start_production() {
+                    Ok(()) => self.general_error = None,
+                    Err(e) => self.general_error = Some(e),
+                }
+            }
+            return;
+        };
+        let status = match production.flashing {
+            Some(ref port) => format!("Flashing {}...", port),
+            None => format!(
+                "Production mode: {} boards flashed, waiting for the next one",
+                production.units
+            ),
+        };
+        let last_unit = production.last_unit.clone();
+        ui.horizontal(|ui| {
+            let stop = ui
+                .button("Stop production mode")
+                .on_hover_text("No more boards are flashed, a running flash is finished");
+            if stop.clicked() {
+                self.production = None;
+            }
+            ui.label(status);
+        });
+        if let Some((port, succeeded)) = last_unit {
+            let (color, text) = if succeeded {
+                (egui::Color32::GREEN, format!("PASS {}", port))
+            } else {
+                (egui::Color32::RED, format!("FAIL {}", port))
+            };
+            ui.label(egui::RichText::new(text).size(40.0).strong().color(color));
+        }
+    }
+
+    /// Select the board, the port and the file of the given history entry again and flash them.
+    fn run_again(&mut self, entry: HistoryEntry, ctx: &egui::Context) {
+        self.selected_board = entry.board;
+        self.selected_port = entry.port;
+        if entry.file.is_some() && self.file_path != entry.file {
+            self.select_file(entry.file, ctx);
+        }
+        match self.start_flash(ctx) {
+            Ok(()) => self.general_error = None,
+            Err(e) => self.general_error = Some(e),
+        }
+    }
+
+    /// The directory the logs are written to, `None` if there is no data directory.
+    fn log_dir(&self) -> Option<PathBuf> {
+        self.settings.log_dir.clone().or_else(run_log::default_dir)
+    }
+
+    /// Show the settings of the log files written after each job.
+    fn log_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = ui
+            .checkbox(
+                &mut self.settings.write_logs,
+                "Write a log file after each job",
+            )
+            .on_hover_text(
+                "The log contains the time, the command, the board, the port, the firmware with \
+                its SHA-256, the output and the exit status",
+            )
+            .changed();
+        let dir = self.log_dir();
+        ui.horizontal(|ui| {
+            ui.label("Log directory: ");
+            match dir {
+                Some(ref dir) => ui.label(dir.to_string_lossy().as_ref()),
+                None => ui.label("<no data directory>"),
+            };
+            if ui.button("Choose...").clicked() {
+                let mut dialog = FileDialog::new();
+                if let Some(ref dir) = dir {
+                    dialog = dialog.set_directory(dir);
+                }
+                if let Some(dir) = dialog.pick_folder() {
+                    self.settings.log_dir = Some(dir);
+                    changed = true;
+                }
+            }
+            if self.settings.log_dir.is_some() && ui.button("Default").clicked() {
+                self.settings.log_dir = None;
+                changed = true;
+            }
+        });
+        if let Some(ref dir) = dir {
+            if ui.button("Open log directory").clicked() {
+                if let Err(e) = run_log::open_dir(dir) {
+                    self.general_error =
+                        Some(format!("Error: could not open {}: {}", dir.display(), e).into());
+                }
+            }
+        }
+        if changed {
+            if let Err(e) = self.settings.save() {
+                self.general_error = Some(e.into());
+            }
+        }
+    }
+
+    /// The backups taken of the board of the given spec on the selected port, the newest one first.
+    fn list_backups(&self, spec: &BoardSpec) -> Result<Vec<Backup>, Cow<'static, str>> {
+        let port = self.upload_port(spec)?;
+        let Some(dir) = self
+            .settings
+            .backup_dir
+            .clone()
+            .or_else(backup::default_dir)
+        else {
+            return Ok(Vec::new());
+        };
+        match backup::list(&dir, port.as_deref().unwrap_or("board")) {
+            Ok(paths) => Ok(paths.into_iter().map(Backup::read).collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(format!("Error: could not list {}: {}", dir.display(), e).into()),
+        }
+    }
+
+    /// Start flashing the given backup back to the selected board on a background thread, a
+    /// backup which does not fit into the flash is refused.
+    fn start_restore(
+        &mut self,
+        path: PathBuf,
+        ctx: &egui::Context,
+    ) -> Result<(), Cow<'static, str>> {
+        self.advanced.validate()?;
+        let mut spec = self.board_spec();
+        if spec.partno.is_empty() {
+            return Err("Error: no chip selected".into());
+        }
+        if let Some(ref mut avrdude) = self.avrdude {
+            avrdude.check()?;
+        }
+        // The dump contains the bootloader too, so it is compared with the whole flash.
+        let end = backup::end_address(&path)?;
+        if let Some(chip) = spec.chip().filter(|chip| end > u64::from(chip.flash_size)) {
+            return Err(format!(
+                "Error: the backup is {} bytes but the {} has only {} bytes of flash",
+                group_digits(end),
+                chip.name,
+                group_digits(u64::from(chip.flash_size))
+            )
+            .into());
+        }
+        // A restore is always verified, even if the verification is skipped otherwise.
+        spec.extra_args.retain(|arg| arg != "-V");
+        let signature_check = spec
+            .chip()
+            .and_then(|chip| chip.signature)
+            .filter(|_| {
+                spec.uploader == Uploader::Avrdude
+                    && self.settings.check_signature
+                    && !spec.single_bootloader_session()
+            })
+            .map(|expected| SignatureCheck {
+                expected,
+                board: self.selected_board.name().to_owned(),
+            });
+        let port = self.upload_port(&spec)?;
+        let backup = self.backup_target(JobKind::Flash, &spec, port.as_deref())?;
+        let (before_flash, after_flash) = self.flash_hooks(JobKind::Flash);
+        let job = UploadJob {
+            backup,
+            before_flash,
+            after_flash,
+            signature_check,
+            ..UploadJob::new(JobKind::Flash, spec, port, path, FirmwareFormat::IntelHex)
+        };
+        self.start(job, ctx)
+    }
+
+    /// The commands run before and after a job of the given kind, only flashing runs them.
+    fn flash_hooks(&self, kind: JobKind) -> (Option<Hook>, Option<Hook>) {
+        let active = |hook: &Hook| {
+            Some(hook.clone()).filter(|hook| kind == JobKind::Flash && hook.is_active())
+        };
+        (
+            active(&self.settings.before_flash),
+            active(&self.settings.after_flash),
+        )
+    }
+
+    /// The port passed to the uploader of the given spec.
+    fn upload_port(&self, spec: &BoardSpec) -> Result<Option<String>, Cow<'static, str>> {
+        self.port_of(spec, self.selected_port.as_ref())
+    }
+
+    /// The port passed to the uploader of the given spec if the given serial port is used.
+    fn port_of(
+        &self,
+        spec: &BoardSpec,
+        port: Option<&SerialPortInfo>,
+    ) -> Result<Option<String>, Cow<'static, str>> {
+        if spec.uses_usb_programmer() {
+            // Usb programmers are found by avrdude and never use the serial port.
+            Ok(Some("usb".to_owned()))
+        } else if spec.uses_serial_port() {
+            match port {
+                Some(port) => Ok(Some(port.port_name.clone())),
+                None => Err("Error: No port selected".into()),
+            }
+        } else if let PicoTarget::Drive(ref drive) = self.pico_target {
+            Ok(Some(drive.to_string_lossy().into_owned()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Run the given job on a background thread.
+    fn start(&mut self, job: UploadJob, ctx: &egui::Context) -> Result<(), Cow<'static, str>> {
+        // A second job would run next to the first one on the same port.
+        if let Some(name) = self.busy() {
+            return Err(format!("Error: wait until the {} job is done", name).into());
+        }
+        self.check_job(&job)?;
+        self.used_command = Some(job.describe());
+        self.command_lines = Some(job.command_lines().join("\n")).filter(|lines| !lines.is_empty());
+        if self.dry_run {
+            self.result = Some(JobResult::dry_run(job.kind));
+            return Ok(());
+        }
+        create_backup_dir(&job)?;
+        self.history
+            .insert(0, self.history_entry(&job, self.selected_port.clone()));
+        self.history.truncate(KEPT_HISTORY);
+        self.run_info = self.run_info(&job);
+        self.result = None;
+        let uploader = job.spec.uploader;
+        self.running = Some(RunningUpload::start(job, uploader, ctx.clone()));
+        Ok(())
+    }
+
+    /// The entry of the given job on the given port in the history, without a result yet.
+    fn history_entry(&self, job: &UploadJob, port: Option<SerialPortInfo>) -> HistoryEntry {
+        HistoryEntry {
+            time: SystemTime::now(),
+            kind: job.kind,
+            board: self.selected_board.clone(),
+            port,
+            file: matches!(job.kind, JobKind::Flash | JobKind::Verify).then(|| job.program.clone()),
+            result: None,
+            succeeded: false,
+        }
+    }
+
+    /// What is written to the log of the given job, `None` if no logs are written.
+    fn run_info(&self, job: &UploadJob) -> Option<RunInfo> {
+        self.settings.write_logs.then(|| RunInfo {
+            started: SystemTime::now(),
+            job: job.kind.name(),
+            command: job.describe(),
+            board: self.selected_board.name().to_owned(),
+            port: job.port.clone(),
+            firmware: matches!(job.kind, JobKind::Flash | JobKind::Verify)
+                .then(|| job.program.clone()),
+        })
+    }
+
+    /// The result of the given job which exited with the given result, its log is written with
+    /// the given information and it is counted.
+    fn finish_job(
+        &mut self,
+        running: &RunningUpload,
+        res: io::Result<Output>,
+        info: Option<RunInfo>,
+    ) -> JobResult {
+        let status = match res {
+            Ok(ref out) => out.status.to_string(),
+            Err(ref e) => format!("not completed: {}", e),
+        };
+        let result = JobResult::new(running, res);
+        // A log which can't be written is only warned about, the job is done anyway.
+        if let Some(info) = info {
+            self.log_warning = self
+                .log_dir()
+                .ok_or_else(|| "Warning: no directory to write the log to".to_owned())
+                .and_then(|dir| info.write(&dir, &result.output, &status))
+                .err();
+        }
+        let synced = match (&result.outcome, running.log.synced_baud()) {
+            (Outcome::Succeeded(_), Some(baud)) => running.port.clone().zip(Some(baud)),
+            _ => None,
+        };
+        if let Some((port, baud)) = synced {
+            if self.settings.port_bauds.insert(port, baud) != Some(baud) {
+                if let Err(e) = self.settings.save() {
+                    self.general_error = Some(e.into());
+                }
+            }
+        }
+        self.stats.record(&result);
+        if result.outcome != Outcome::DryRun {
+            self.timings.insert(0, Timing::of(&result));
+            self.timings.truncate(KEPT_TIMINGS);
+        }
+        result
+    }
+
+    /// Check the settings the given job depends on before it is started.
+    fn check_job(&self, job: &UploadJob) -> Result<(), Cow<'static, str>> {
+        let uploader = job.spec.uploader;
+        self.advanced.validate()?;
+        // avrdude only reports a missing config file with a confusing parse error.
+        match job.spec.avrdude_conf {
+            Some(ref conf) if uploader == Uploader::Avrdude && !conf.is_file() => {
+                return Err(
+                    format!("Error: the avrdude.conf {} does not exist", conf.display()).into(),
+                )
+            }
+            _ => (),
+        }
+        if uploader == Uploader::Avrdude {
+            avrdude::split_args(&self.settings.extra_avrdude_args)?;
+            if let Some(reason) = self.avrdude.as_ref().and_then(|a| a.unsupported(&job.spec)) {
+                return Err(format!("Error: {}", reason).into());
+            }
+            let params = self
+                .settings
+                .extended_params
+                .get(job.spec.upload_programmer());
+            // Rows which were added but not filled in yet are skipped.
+            let filled = params
+                .into_iter()
+                .flatten()
+                .filter(|(key, value)| !key.is_empty() || !value.is_empty());
+            for (key, value) in filled {
+                avrdude::check_extended_param(key, value).map_err(|e| {
+                    format!(
+                        "Error: invalid extended parameter \"{}={}\": {}",
+                        key, value, e
+                    )
+                })?;
+            }
+        }
+        for hook in job.before_flash.iter().chain(&job.after_flash) {
+            hook.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Create the directory the backup of the given job is written to.
+fn create_backup_dir(job: &UploadJob) -> Result<(), Cow<'static, str>> {
+    if let Some(dir) = job.backup.as_ref().and_then(|(path, _)| path.parent()) {
+        fs::create_dir_all(dir).map_err(|e| {
+            format!(
+                "Error: could not create the backup directory {}: {}",
+                dir.display(),
+                e
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Check that the given program fits into the flash of the board.
+fn check_size(
+    spec: &BoardSpec,
+    path: &Path,
+    format: FirmwareFormat,
+) -> Result<(), Cow<'static, str>> {
+    let (Some(usable), Some(chip)) = (spec.usable_flash(), spec.chip()) else {
+        return Ok(());
+    };
+    let size = firmware::program_size(path, format)?;
+    if size > u64::from(usable) {
+        return Err(format!(
+            "Error: the firmware is {} bytes but the {} has {} bytes usable",
+            group_digits(size),
+            chip.name,
+            group_digits(u64::from(usable))
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Show the merged stdout and stderr of the uploader, scrolled to the newest output.
+fn output_view(ui: &mut egui::Ui, output: &str) {
+    egui::ScrollArea::vertical()
+        .max_height(300.0)
+        .stick_to_bottom(true)
+        .show(ui, |ui| ui.monospace(output));
+}
+
+/// Show the options of the given hook with the given label, returns wether they changed.
+fn hook_ui(ui: &mut egui::Ui, label: &str, hook: &mut Hook) -> bool {
+    let before = hook.clone();
+    ui.checkbox(&mut hook.enabled, label);
+    if hook.enabled {
+        ui.horizontal(|ui| {
+            ui.label("Command: ");
+            ui.add(egui::TextEdit::singleline(&mut hook.command).hint_text("relay on {port}"))
+                .on_hover_text(format!(
+                    "{} are replaced by the port, the file and the chip of the flash",
+                    HOOK_PLACEHOLDERS.join(", ")
+                ));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Working directory: ");
+            ui.add(
+                egui::TextEdit::singleline(&mut hook.working_dir)
+                    .hint_text("the current directory"),
+            );
+            ui.label("Timeout: ");
+            ui.add(
+                egui::DragValue::new(&mut hook.timeout_secs)
+                    .clamp_range(0..=3600)
+                    .suffix(" s"),
+            )
+            .on_hover_text("The command is killed when it runs longer, 0 waits forever");
+        });
+        if let Err(e) = hook.validate() {
+            ui.colored_label(egui::Color32::RED, e);
+        }
+    }
+    *hook != before
+}
+
+/// Show the options of the reset pulse, returns wether they changed.
+fn reset_pulse_ui(ui: &mut egui::Ui, pulse: &mut ResetPulse) -> bool {
+    let before = *pulse;
+    ui.checkbox(&mut pulse.enabled, "Pulse DTR/RTS before flashing")
+        .on_hover_text(
+            "Resets boards without an auto reset circuit, like a bare ATmega328P on an FTDI \
+            adapter, right before avrdude connects",
+        );
+    if pulse.enabled {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut pulse.rts, "Also pulse RTS");
+            ui.label("Width: ");
+            ui.add(
+                egui::DragValue::new(&mut pulse.width_ms)
+                    .clamp_range(1..=2000)
+                    .suffix(" ms"),
+            );
+            ui.checkbox(&mut pulse.inverted, "Inverted polarity")
+                .on_hover_text("Releases the lines during the pulse instead of asserting them");
+        });
+    }
+    *pulse != before
+}
+
+/// The given fuse values named by the given layout like "lfuse 0xFF, hfuse 0xDE".
+fn fuse_list(layout: &FuseLayout, values: [u8; 3]) -> String {
+    layout
+        .names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| format!("{} 0x{:02X}", name, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A line like "completed in 12.4 s (2.5 KB/s)" for a job which took the given time to transfer
+/// the given number of bytes.
+fn completed(elapsed: Duration, bytes: Option<u64>) -> String {
+    let seconds = elapsed.as_secs_f32();
+    match bytes.filter(|_| seconds > 0.0) {
+        Some(bytes) => format!(
+            "completed in {:.1} s ({:.1} KB/s)",
+            seconds,
+            bytes as f32 / 1024.0 / seconds
+        ),
+        None => format!("completed in {:.1} s", seconds),
+    }
+}
+
+/// Format the given number with its digits grouped by thousands like "32 256".
+fn group_digits(number: u64) -> String {
+    let digits = number.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i != 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(' ');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Paint a hint over the whole window while files are dragged over it.
+fn drop_overlay(ctx: &egui::Context) {
+    let layer = egui::LayerId::new(egui::Order::Foreground, egui::Id::new("DropOverlay"));
+    let painter = ctx.layer_painter(layer);
+    let screen = ctx.screen_rect();
+    painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(192));
+    painter.text(
+        screen.center(),
+        egui::Align2::CENTER_CENTER,
+        "Drop firmware here",
+        TextStyle::Heading.resolve(&ctx.style()),
+        egui::Color32::WHITE,
+    );
+}
+
+/// The path entered by the user without surrounding quotes and with the home directory expanded.
+fn parse_path_input(input: &str) -> PathBuf {
+    let mut input = input.trim();
+    for quote in ['"', '\''] {
+        if input.len() >= 2 && input.starts_with(quote) && input.ends_with(quote) {
+            input = &input[1..input.len() - 1];
+        }
+    }
+
+    if cfg!(unix) {
+        if let Some(home) = dirs::home_dir() {
+            if input == "~" {
+                return home;
+            }
+            if let Some(rest) = input.strip_prefix("~/") {
+                return home.join(rest);
+            }
+        }
+    }
+    PathBuf::from(input)
+}
+
+/// The port info of a network port, avrdude gets its name passed as is.
+fn network_port(name: &str) -> SerialPortInfo {
+    SerialPortInfo {
+        port_name: name.to_owned(),
+        port_type: SerialPortType::Unknown,
+    }
+}
+
+/// Parse a network port entered as "host:port" or "net:host:port" into the form used by avrdude.
+fn parse_network_port(input: &str) -> Option<String> {
+    let input = input.trim();
+    let address = input.strip_prefix("net:").unwrap_or(input);
+    let (host, port) = address.rsplit_once(':')?;
+    if host.is_empty() || port.parse::<u16>().is_err() {
+        return None;
+    }
+    Some(format!("net:{}", address))
+}
+
+/// Wether the given port is a usb serial device.
+fn is_usb_port(port: &SerialPortInfo) -> bool {
+    matches!(port.port_type, SerialPortType::UsbPort(_))
+}
+
+/// Scan for available ports
+fn portscan(available_ports: &mut Vec<SerialPortInfo>, port_scan_error: &mut Option<String>) {
+    match serialport::available_ports() {
+        Ok(ports) => {
+            *available_ports = ports;
+            *port_scan_error = None;
+        }
+        Err(e) => {
+            *available_ports = Vec::new();
+            *port_scan_error = Some(format!("ERROR: {}", e));
+        }
+    }
+}
+
+impl eframe::App for ArduinoInstallerGui {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // Removes the extracted files of the bundle.
+        self.bundle = None;
+        if self.settings.write_logs && self.stats.attempted > 0 {
+            if let Some(dir) = self.log_dir() {
+                let summary = format!(
+                    "Session since {}\n{}\n",
+                    format_time(self.stats.since),
+                    self.stats.describe()
+                );
+                // The window is gone, so there is nobody to show a failure to.
+                let _ = run_log::write_session(&dir, self.stats.since, &summary);
+            }
+        }
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let dropped = ctx.input(|i| i.raw.dropped_files.clone());
+        if !dropped.is_empty() {
+            self.drop_files(&dropped, ctx);
+        }
+        self.refresh_file_info(ctx);
+        if let Some(ref mut avrdude) = self.avrdude {
+            avrdude.poll();
+        }
+        if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+            drop_overlay(ctx);
+        }
+        self.production_tick(ctx);
+        self.batch_tick(ctx);
+        self.auto_rescan_tick(ctx);
+        let rerun = ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::R));
+        if rerun && !self.is_busy() {
+            let last = self
+                .history
+                .iter()
+                .find(|entry| entry.is_successful_flash())
+                .cloned();
+            match last {
+                Some(entry) => self.run_again(entry, ctx),
+                None => self.general_error = Some("Error: no flash succeeded yet".into()),
+            }
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.visuals_mut().override_text_color = Some(egui::Color32::WHITE);
+            ui.heading("Arduino Installer gui");
+            ui.horizontal(|ui| {
+                ui.label("File: ");
+                let field = ui.text_edit_singleline(&mut self.path_input);
+                if field.changed() {
+                    let ctx = ui.ctx().clone();
+                    self.path_error = self.apply_path_input(&ctx).err();
+                }
+                match self.file_format {
+                    Some(Ok(format)) => {
+                        ui.label(format!("({})", format.label()));
+                    }
+                    Some(Err(ref e)) => {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                    None => (),
+                }
+                if ui.button("Choose a file").clicked() {
+                    let filters = self.file_filters();
+                    let mut dialog = FileDialog::new();
+                    // A directory which was removed in the meantime falls back to the default.
+                    if let Some(dir) = self
+                        .settings
+                        .last_directory
+                        .as_ref()
+                        .filter(|dir| dir.is_dir())
+                    {
+                        dialog = dialog.set_directory(dir);
+                    }
+                    let file = filters
+                        .iter()
+                        .fold(dialog, |dialog, (name, extensions)| {
+                            dialog.add_filter(name, extensions)
+                        })
+                        .pick_file();
+                    if let Some(ref file) = file {
+                        // The dialog does not tell which filter was used, so it is guessed from
+                        // the extension of the file.
+                        let filter = filters
+                            .iter()
+                            .find(|(name, extensions)| {
+                                *name != ALL_FILES.0
+                                    && file.extension().is_some_and(|ext| {
+                                        extensions.iter().any(|e| ext.eq_ignore_ascii_case(e))
+                                    })
+                            })
+                            .map_or(ALL_FILES.0, |(name, _)| name);
+                        self.settings.last_filter = Some(filter.to_owned());
+                        self.general_error = None;
+                        self.select_file(Some(file.clone()), ui.ctx());
+                    }
+                }
+
+                let mut picked = None;
+                egui::ComboBox::from_id_source("RecentFiles")
+                    .selected_text("Recent")
+                    .show_ui(ui, |ui| {
+                        if self.settings.recent_files.is_empty() {
+                            ui.label("No recent files");
+                        }
+                        for recent in &self.settings.recent_files {
+                            let name = recent.to_string_lossy();
+                            let entry = ui
+                                .add_enabled(
+                                    recent.is_file(),
+                                    egui::SelectableLabel::new(false, name),
+                                )
+                                .on_disabled_hover_text("The file does not exist anymore");
+                            if entry.clicked() {
+                                picked = Some(recent.clone());
+                            }
+                        }
+                    });
+                if picked.is_some() {
+                    self.general_error = None;
+                    self.select_file(picked, ui.ctx());
+                }
+
+                if ui.button("Choose project folder...").clicked() {
+                    let mut dialog = FileDialog::new();
+                    if let Some(dir) = self
+                        .settings
+                        .last_directory
+                        .as_ref()
+                        .filter(|dir| dir.is_dir())
+                    {
+                        dialog = dialog.set_directory(dir);
+                    }
+                    if let Some(dir) = dialog.pick_folder() {
+                        self.project_dir = Some(dir);
+                        self.refresh_project(ui.ctx());
+                    }
+                }
+
+                let download =
+                    ui.add_enabled(self.download.is_none(), egui::Button::new("From URL..."));
+                if download.clicked() {
+                    self.url_input = Some(String::new());
+                }
+                if ui.button("Compare...").clicked() {
+                    self.compare.show_with(self.file_path.as_deref());
+                }
+            });
+
+            if let Some(dir) = self.project_dir.clone() {
+                let mut close = false;
+                ui.horizontal(|ui| {
+                    ui.label(format!("Project: {} ", dir.display()));
+                    let mut refresh = false;
+                    match self.project_layout {
+                        Some(ref layout) if layout.targets.len() > 1 => {
+                            ui.label(format!("{}: ", layout.system.target_label()));
+                            egui::ComboBox::from_id_source("ProjectTarget")
+                                .selected_text(self.project_target.as_deref().unwrap_or_default())
+                                .show_ui(ui, |ui| {
+                                    for target in &layout.targets {
+                                        let selected =
+                                            self.project_target.as_ref() == Some(&target.name);
+                                        if ui.selectable_label(selected, &target.name).clicked() {
+                                            self.project_target = Some(target.name.clone());
+                                            refresh = true;
+                                        }
+                                    }
+                                });
+                        }
+                        Some(_) => (),
+                        None => {
+                            ui.label("Pattern: ");
+                            let pattern =
+                                ui.text_edit_singleline(&mut self.settings.artifact_pattern);
+                            refresh = pattern.lost_focus()
+                                && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        }
+                    }
+                    if ui.button("Refresh").clicked() || refresh {
+                        self.refresh_project(ui.ctx());
+                    }
+                    if ui.button("Close").clicked() {
+                        close = true;
+                    }
+                });
+                let artifact = self
+                    .file_path
+                    .as_deref()
+                    .and_then(|path| path.strip_prefix(&dir).ok());
+                if let Some(artifact) = artifact {
+                    ui.label(format!("Newest artifact: {}", artifact.display()));
+                }
+                if close {
+                    self.project_dir = None;
+                    self.project_layout = None;
+                    self.project_target = None;
+                }
+            }
+
+            if let Some(ref mut url) = self.url_input {
+                let mut close = false;
+                ui.horizontal(|ui| {
+                    ui.label("URL: ");
+                    ui.text_edit_singleline(url);
+                    if ui.button("Download").clicked() {
+                        match Download::start(url, ui.ctx()) {
+                            Ok(download) => {
+                                self.download = Some(download);
+                                self.general_error = None;
+                                close = true;
+                            }
+                            Err(e) => self.general_error = Some(e.into()),
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+                if close {
+                    self.url_input = None;
+                }
+            }
+
+            if let Some(ref download) = self.download {
+                match download.poll() {
+                    Some(Ok(path)) => {
+                        self.download = None;
+                        self.general_error = None;
+                        self.select_file(Some(path), ui.ctx());
+                    }
+                    Some(Err(e)) => {
+                        self.download = None;
+                        self.general_error = Some(e.into());
+                    }
+                    None => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!(
+                                "Downloading {} ({})",
+                                download.url,
+                                format_size(
+                                    u32::try_from(download.downloaded()).unwrap_or(u32::MAX)
+                                )
+                            ));
+                        });
+                        // The progress is read from the partial file, which does not repaint.
+                        ui.ctx().request_repaint_after(Duration::from_millis(200));
+                    }
+                }
+            }
+
+            if let Some(ref info) = self.file_info {
+                ui.label(format!(
+                    "{} bytes, modified {}",
+                    info.size,
+                    info.modified.map(format_time).unwrap_or_default()
+                ));
+                ui.horizontal(|ui| {
+                    ui.label("SHA-256: ");
+                    match info.sha256 {
+                        Some(Ok(ref hash)) => {
+                            ui.monospace(hash);
+                            if ui.button("Copy").clicked() {
+                                ui.output_mut(|o| o.copied_text = hash.clone());
+                            }
+                        }
+                        Some(Err(ref e)) => {
+                            ui.colored_label(egui::Color32::RED, e);
+                        }
+                        None => {
+                            ui.spinner();
+                        }
+                    }
+                });
+                if info.is_parsing_hex() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Checking the Intel HEX records");
+                    });
+                }
+                match info.hex {
+                    Some(Ok(ref image)) => {
+                        if let Some((start, end)) = image.address_range() {
+                            ui.label(format!(
+                                "{} data bytes from 0x{:X} to 0x{:X}",
+                                group_digits(image.size()),
+                                start,
+                                end
+                            ));
+                        }
+                    }
+                    Some(Err(ref e)) => {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                    None => (),
+                }
+                match info.elf {
+                    Some(Ok(ref elf)) => {
+                        if let Some(ref version) = elf.version {
+                            ui.label(format!("Version: {}", version));
+                        }
+                        ui.label(format!(
+                            "text {}, data {}, bss {} bytes, entry point 0x{:X}",
+                            group_digits(elf.sizes.text),
+                            group_digits(elf.sizes.data),
+                            group_digits(elf.sizes.bss),
+                            elf.entry
+                        ))
+                        .on_hover_text("The flash holds text and data, the RAM holds data and bss");
+                    }
+                    Some(Err(ref e)) => {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                    None => (),
+                }
+            }
+            if let Some(FirmwareFormat::Elf) = self.file_format.clone().and_then(Result::ok) {
+                ui.horizontal(|ui| {
+                    ui.label("Version section: ");
+                    let section = ui.text_edit_singleline(&mut self.settings.version_section);
+                    if section.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        if let Some(ref path) = self.file_path {
+                            self.file_info =
+                                FileInfo::load(path, &self.settings.version_section, ui.ctx()).ok();
+                        }
+                        if let Err(e) = self.settings.save() {
+                            self.general_error = Some(e.into());
+                        }
+                    }
+                });
+            }
+            if self.file_info.is_some() {
+                ui.checkbox(
+                    &mut self.auto_flash,
+                    "Flash again whenever the file changes",
+                );
+            }
+            if let Some(ref e) = self.path_error {
+                ui.colored_label(egui::Color32::RED, e.as_ref());
+            }
+            if self.file_changed {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        "Firmware changed on disk \u{2014} Flash again?",
+                    );
+                    let again = ui.add_enabled(!self.is_busy(), egui::Button::new("Flash again"));
+                    if again.clicked() {
+                        match self.start_flash(ui.ctx()) {
+                            Ok(()) => self.general_error = None,
+                            Err(e) => self.general_error = Some(e),
+                        }
+                    }
+                });
+            }
+
+            for warning in self.file_warnings() {
+                ui.colored_label(egui::Color32::YELLOW, warning);
+            }
+            if let Some(board) = self.bundle_conflict.clone() {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    format!(
+                        "Warning: the bundle is made for the {}, but the {} is selected",
+                        board.name(),
+                        self.selected_board.name()
+                    ),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button(format!("Use {}", board.name())).clicked() {
+                        self.selected_board = board.clone();
+                        self.bundle_conflict = None;
+                    }
+                    if ui
+                        .button(format!("Keep {}", self.selected_board.name()))
+                        .clicked()
+                    {
+                        self.bundle_conflict = None;
+                    }
+                });
+            }
+
+            if self.board_spec().uploader == Uploader::Avrdude {
+                ui.horizontal(|ui| {
+                    ui.label("EEPROM file (optional): ");
+                    if let Some(ref path) = self.eeprom_path {
+                        ui.label(path.to_string_lossy().as_ref());
+                    }
+                    if ui.button("Choose").clicked() {
+                        let file = FileDialog::new()
+                            .add_filter("hex file", &["hex", "eep"])
+                            .pick_file();
+                        if file.is_some() {
+                            self.eeprom_path = file;
+                        }
+                    }
+                    if self.eeprom_path.is_some() && ui.button("Clear").clicked() {
+                        self.eeprom_path = None;
+                    }
+                });
+                if self.eeprom_path.is_none() && self.board_spec().erase.erases_chip() {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        "The chip erase also clears the EEPROM unless the EESAVE fuse is set",
+                    );
+                }
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Select board: ");
+                let before = self.selected_board.clone();
+                let avrdude = self.avrdude.as_ref();
+                let unsupported = |board: &SelectedBoard| {
+                    let spec = board.spec();
+                    match avrdude {
+                        Some(avrdude) if spec.uploader == Uploader::Avrdude => {
+                            avrdude.unsupported(&spec)
+                        }
+                        _ => None,
+                    }
+                };
+                board_picker(
+                    ui,
+                    &mut self.selected_board,
+                    &self.custom_boards,
+                    &unsupported,
+                );
+                if self.selected_board != before {
+                    self.board_picked = true;
+                    self.bundle_conflict = None;
+                }
+            });
+
+            if self.selected_board.spec().uploader == Uploader::Stm32flash {
+                ui.horizontal(|ui| {
+                    ui.label("Upload method: ");
+                    egui::ComboBox::from_id_source("Stm32Methods")
+                        .selected_text(self.stm32_method.label())
+                        .show_ui(ui, |ui| {
+                            for method in Stm32Method::ALL {
+                                ui.selectable_value(&mut self.stm32_method, method, method.label());
+                            }
+                        });
+                });
+            }
+
+            if self.selected_board.spec().uploader == Uploader::Esptool {
+                ui.horizontal(|ui| {
+                    ui.label("Upload method: ");
+                    egui::ComboBox::from_id_source("EspMethods")
+                        .selected_text(self.esp_method.label())
+                        .show_ui(ui, |ui| {
+                            for method in EspMethod::ALL {
+                                ui.selectable_value(&mut self.esp_method, method, method.label());
+                            }
+                        });
+                });
+            }
+
+            let board_spec = self.base_spec();
+            if board_spec.uploader == Uploader::Stk500 || board_spec.supports_builtin_uploader() {
+                let name = self.selected_board.name().to_owned();
+                let mut builtin = self.settings.builtin_uploader.contains(&name);
+                let checkbox = ui
+                    .checkbox(&mut builtin, "Built-in uploader (experimental)")
+                    .on_hover_text(
+                        "Flash the Uno or Nano without avrdude, the program is written and \
+                        verified over the optiboot bootloader",
+                    );
+                if checkbox.changed() {
+                    if builtin {
+                        self.settings.builtin_uploader.insert(name);
+                    } else {
+                        self.settings.builtin_uploader.remove(&name);
+                    }
+                    if let Err(e) = self.settings.save() {
+                        self.general_error = Some(e.into());
+                    }
+                }
+            }
+            let board_spec = self.base_spec();
+            let details = ui.label(format!(
+                "{} \u{2014} {}",
+                self.selected_board.name(),
+                board_spec.summary()
+            ));
+            if let Some(chip) = board_spec.chip() {
+                details.on_hover_text(format!("{} EEPROM", format_size(chip.eeprom_size)));
+            }
+
+            if self.detected_board.as_ref() == Some(&self.selected_board) {
+                ui.label("(detected automatically)");
+            }
+
+            let spec = self.board_spec();
+            if spec.pick_partno {
+                ui.horizontal(|ui| {
+                    ui.label("Chip: ");
+                    egui::ComboBox::from_id_source("UpdiParts")
+                        .selected_text(self.picked_partno.unwrap_or("Select a chip"))
+                        .show_ui(ui, |ui| {
+                            for partno in UPDI_PARTS {
+                                ui.selectable_value(&mut self.picked_partno, Some(partno), partno);
+                            }
+                        });
+                });
+            }
+
+            if spec.uploader == Uploader::Avrdude && !spec.updi {
+                let selected = self.programmer(&spec);
+                ui.horizontal(|ui| {
+                    ui.label("Programmer: ");
+                    egui::ComboBox::from_id_source("Programmers")
+                        .selected_text(selected.label())
+                        .show_ui(ui, |ui| {
+                            for programmer in Programmer::ALL {
+                                // Boards without a bootloader can't be flashed through it.
+                                if programmer == Programmer::Bootloader && spec.over_isp {
+                                    continue;
+                                }
+                                ui.selectable_value(
+                                    &mut self.programmer,
+                                    programmer,
+                                    programmer.label(),
+                                );
+                            }
+                        });
+                });
+                if selected == Programmer::ArduinoAsIsp {
+                    ui.label(
+                        "Select the port of the Arduino running the ArduinoISP sketch, \
+                        the usb port of the target board is not used.",
+                    );
+                }
+            }
+
+            if !spec.extra_args.is_empty() {
+                ui.label(format!(
+                    "Extra avrdude arguments: {}",
+                    spec.extra_args.join(" ")
+                ));
+            }
+
+            // The 1200 baud touch starts the bootloader without pressing the button.
+            if spec.needs_reset && !spec.touch_1200bps {
+                ui.label(
+                    "Press the reset button of the board right before flashing, \
+                    the bootloader port is only available for a few seconds.",
+                );
+            }
+
+            if spec.uses_serial_port() {
+                ui.horizontal(|ui| {
+                    let rescan = ui
+                        .add_enabled(!self.is_busy(), egui::Button::new("Rescan"))
+                        .on_disabled_hover_text(RESCAN_BLOCKED);
+                    if rescan.clicked() {
+                        self.rescan();
+                    } else {
+                        let lbl = ui.label("Available Ports: ");
+                        egui::ComboBox::from_id_source("Ports")
+                            .selected_text(format!("{:?}", self.selected_port))
+                            .wrap(false)
+                            .width(lbl.rect.width().mul_add(-1.2, ui.available_width()))
+                            .show_ui(ui, |ui| {
+                                for info in self.shown_ports(&spec) {
+                                    ui.selectable_value(
+                                        &mut self.selected_port,
+                                        Some(info.clone()),
+                                        format!("{:?}: {}", info.port_type, info.port_name),
+                                    );
+                                }
+                                for name in &self.network_ports {
+                                    ui.selectable_value(
+                                        &mut self.selected_port,
+                                        Some(network_port(name)),
+                                        format!("Network: {}", name),
+                                    );
+                                }
+                                if ui.selectable_label(false, "Add network port...").clicked() {
+                                    self.network_port_input = Some(String::new());
+                                }
+                            });
+                    }
+                });
+                if let Some(ref mut input) = self.network_port_input {
+                    let mut close = false;
+                    ui.horizontal(|ui| {
+                        ui.label("Network port (host:port): ");
+                        ui.text_edit_singleline(input);
+                        if ui.button("Add").clicked() {
+                            match parse_network_port(input) {
+                                Some(name) => {
+                                    self.selected_port = Some(network_port(&name));
+                                    if !self.network_ports.contains(&name) {
+                                        self.network_ports.push(name);
+                                    }
+                                    close = true;
+                                }
+                                None => {
+                                    self.general_error =
+                                        Some("Error: a network port has to be host:port".into());
+                                }
+                            }
+                        }
+                        if ui.button("Cancel").clicked() {
+                            close = true;
+                        }
+                    });
+                    if close {
+                        self.network_port_input = None;
+                    }
+                }
+                ui.add_enabled(
+                    !spec.usb_ids.is_empty(),
+                    egui::Checkbox::new(&mut self.only_matching_ports, "Show only matching ports"),
+                )
+                .on_disabled_hover_text("The usb ids of the selected board are unknown");
+                let usb_only = ui
+                    .checkbox(&mut self.settings.usb_only, "USB devices only")
+                    .on_hover_text(
+                        "Hide Bluetooth and legacy serial ports, the selected port is always shown",
+                    );
+                if usb_only.changed() {
+                    if let Err(e) = self.settings.save() {
+                        self.general_error = Some(e.into());
+                    }
+                }
+                let auto_rescan = ui
+                    .checkbox(
+                        &mut self.settings.auto_rescan,
+                        "Rescan the ports automatically",
+                    )
+                    .on_hover_text(
+                        "Scan the ports every 2 s while the window has the focus, turn it off if \
+                        your usb serial adapter resets when it is enumerated repeatedly",
+                    );
+                if auto_rescan.changed() {
+                    if let Err(e) = self.settings.save() {
+                        self.general_error = Some(e.into());
+                    }
+                }
+                let idle = !self.is_busy() && self.production.is_none();
+                let matching = ui
+                    .add_enabled(
+                        idle && !spec.usb_ids.is_empty(),
+                        egui::Button::new("Flash all matching boards..."),
+                    )
+                    .on_hover_text(
+                        "Flash every connected board with the usb ids of the selected board, \
+                        boards with another chip are skipped",
+                    )
+                    .on_disabled_hover_text("The usb ids of the selected board are unknown");
+                if matching.clicked() {
+                    match self.find_matching() {
+                        Ok(()) => self.general_error = None,
+                        Err(e) => self.general_error = Some(e),
+                    }
+                }
+                egui::CollapsingHeader::new("Flash several ports")
+                    .show(ui, |ui| self.batch_ui(ui, &spec));
+            } else if spec.uploader == Uploader::Espota {
+                self.ota.ui(ui, spec.ota_port);
+            } else if matches!(spec.uploader, Uploader::Picotool | Uploader::Uf2Copy) {
+                ui.horizontal(|ui| {
+                    let rescan = ui
+                        .add_enabled(!self.is_busy(), egui::Button::new("Rescan"))
+                        .on_disabled_hover_text(RESCAN_BLOCKED);
+                    if rescan.clicked() {
+                        self.uf2_drives = find_uf2_drives();
+                    }
+                    ui.label("Target: ");
+                    egui::ComboBox::from_id_source("PicoTargets")
+                        .selected_text(self.pico_target.label())
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.pico_target,
+                                PicoTarget::Picotool,
+                                PicoTarget::Picotool.label(),
+                            );
+                            for drive in &self.uf2_drives {
+                                let target = PicoTarget::Drive(drive.clone());
+                                let label = target.label().into_owned();
+                                ui.selectable_value(&mut self.pico_target, target, label);
+                            }
+                        });
+                });
+            }
+
+            let base_spec = self.base_spec();
+            let (board_baud, board_erase) = (base_spec.upload_baud(), base_spec.erase);
+            let isp = base_spec.uses_isp();
+            let programmer = match base_spec.uploader {
+                Uploader::Avrdude => Some(self.board_spec().upload_programmer().to_owned()),
+                _ => None,
+            };
+            egui::CollapsingHeader::new("Advanced").show(ui, |ui| {
+                self.advanced.ui(ui, board_baud, board_erase, isp);
+                let check = ui.checkbox(
+                    &mut self.settings.check_signature,
+                    "Check the chip signature before flashing",
+                );
+                if check.changed() {
+                    if let Err(e) = self.settings.save() {
+                        self.general_error = Some(e.into());
+                    }
+                }
+                let auto_baud = ui
+                    .checkbox(
+                        &mut self.settings.auto_baud,
+                        "Auto-detect the bootloader speed of ATmega328P boards",
+                    )
+                    .on_hover_text(
+                        "A bootloader which does not sync is tried once more at the baud rate of \
+                        the other bootloader, 57600 for the old one and 115200 for optiboot",
+                    );
+                if auto_baud.changed() {
+                    if let Err(e) = self.settings.save() {
+                        self.general_error = Some(e.into());
+                    }
+                }
+                let skip_verify = ui
+                    .checkbox(
+                        &mut self.settings.skip_verify,
+                        "Skip verifying the flash after writing it (-V)",
+                    )
+                    .on_hover_text(
+                        "Flashing takes about half the time, but a bad write is not noticed. \
+                        Restoring a backup and flashing with the lock bits set afterwards always \
+                        verify.",
+                    );
+                if skip_verify.changed() {
+                    if let Err(e) = self.settings.save() {
+                        self.general_error = Some(e.into());
+                    }
+                }
+                ui.checkbox(
+                    &mut self.dry_run,
+                    "Dry run: check everything and show the commands without running them",
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Verbosity: ");
+                    let before = self.settings.verbosity;
+                    egui::ComboBox::from_id_source("Verbosity")
+                        .selected_text(before.label())
+                        .show_ui(ui, |ui| {
+                            for verbosity in Verbosity::ALL {
+                                ui.selectable_value(
+                                    &mut self.settings.verbosity,
+                                    verbosity,
+                                    verbosity.label(),
+                                );
+                            }
+                        });
+                    if self.settings.verbosity != before {
+                        if let Err(e) = self.settings.save() {
+                            self.general_error = Some(e.into());
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Attempts: ");
+                    let attempts = ui
+                        .add(
+                            egui::DragValue::new(&mut self.settings.attempts)
+                                .clamp_range(1..=MAX_ATTEMPTS),
+                        )
+                        .on_hover_text(
+                            "avrdude is run again when the bootloader did not sync, the \
+                            programmer did not respond or the port was busy",
+                        );
+                    if attempts.changed() {
+                        if let Err(e) = self.settings.save() {
+                            self.general_error = Some(e.into());
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Timeout: ");
+                    let timeout = ui
+                        .add(
+                            egui::DragValue::new(&mut self.settings.timeout_secs)
+                                .clamp_range(0..=3600)
+                                .suffix(" s"),
+                        )
+                        .on_hover_text(
+                            "avrdude is killed when it runs longer, 0 waits forever. Reads and \
+                            writes of large files get more time.",
+                        );
+                    if timeout.changed() {
+                        if let Err(e) = self.settings.save() {
+                            self.general_error = Some(e.into());
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Bootloader port timeout: ");
+                    let timeout = ui
+                        .add(
+                            egui::DragValue::new(&mut self.settings.touch_timeout_secs)
+                                .clamp_range(1..=60)
+                                .suffix(" s"),
+                        )
+                        .on_hover_text(
+                            "How long native usb boards like the Leonardo are waited for after \
+                            the 1200 baud touch, the original port is used if they do not \
+                            reappear.",
+                        );
+                    if timeout.changed() {
+                        if let Err(e) = self.settings.save() {
+                            self.general_error = Some(e.into());
+                        }
+                    }
+                });
+                if let Some(ref programmer) = programmer {
+                    self.extended_params_ui(ui, programmer);
+                }
+                if reset_pulse_ui(ui, &mut self.settings.reset_pulse) {
+                    if let Err(e) = self.settings.save() {
+                        self.general_error = Some(e.into());
+                    }
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Extra avrdude arguments: ");
+                    let field = ui.add(
+                        egui::TextEdit::singleline(&mut self.settings.extra_avrdude_args)
+                            .hint_text("-V -F"),
+                    );
+                    if field.changed() {
+                        if let Err(e) = self.settings.save() {
+                            self.general_error = Some(e.into());
+                        }
+                    }
+                    if let Err(e) = avrdude::split_args(&self.settings.extra_avrdude_args) {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                });
+            });
+            egui::CollapsingHeader::new("avrdude").show(ui, |ui| self.avrdude_ui(ui));
+            egui::CollapsingHeader::new("Logs").show(ui, |ui| self.log_ui(ui));
+            egui::CollapsingHeader::new("Commands around flashing").show(ui, |ui| {
+                let before = hook_ui(
+                    ui,
+                    "Run a command before flashing, the flash is aborted if it fails",
+                    &mut self.settings.before_flash,
+                );
+                let after = hook_ui(
+                    ui,
+                    "Run a command after each successful flash",
+                    &mut self.settings.after_flash,
+                );
+                if before || after {
+                    if let Err(e) = self.settings.save() {
+                        self.general_error = Some(e.into());
+                    }
+                }
+            });
+
+            ui.scope(|ui| {
+                ui.visuals_mut().override_text_color = Some(egui::Color32::RED);
+                if let Some(ref s) = self.port_scan_error {
+                    ui.label(s);
+                }
+
+                if let Some(ref s) = self.general_error {
+                    ui.label(s.as_ref());
+                }
+            });
+
+            let finished = self.running.as_ref().and_then(RunningUpload::poll);
+            if let Some(res) = finished {
+                if let Some(running) = self.running.take() {
+                    let info = self.run_info.take();
+                    let result = self.finish_job(&running, res, info);
+                    // The values read are the starting point for changing the fuses.
+                    match result.kind {
+                        JobKind::ReadFuses(layout) if layout.is_lock() => {
+                            self.lock_editor.set_values(&result.fuses)
+                        }
+                        JobKind::ReadFuses(_) => self.fuse_editor.set_values(&result.fuses),
+                        _ => (),
+                    }
+                    // The entry of the running job was added when it started.
+                    if let Some(entry) = self.history.first_mut() {
+                        entry.result = Some(result.outcome.label());
+                        entry.succeeded = matches!(result.outcome, Outcome::Succeeded(_));
+                    }
+                    if let Some(ref mut production) = self.production {
+                        production.finished(matches!(result.outcome, Outcome::Succeeded(_)));
+                    }
+                    self.result = Some(result);
+                }
+            }
+
+            let avrdude_missing = match self.avrdude {
+                Some(ref avrdude) if spec.uploader == Uploader::Avrdude => avrdude.unavailable(),
+                _ => None,
+            };
+            if let Some(ref missing) = avrdude_missing {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::RED, missing);
+                    if ui.button("Check again").clicked() {
+                        self.avrdude = Some(Avrdude::find(
+                            self.settings.avrdude_path.as_deref(),
+                            ui.ctx(),
+                        ));
+                    }
+                });
+            }
+
+            ui.add(
+                egui::Label::new(
+                    egui::RichText::new(self.command_preview())
+                        .monospace()
+                        .small(),
+                )
+                .wrap(true),
+            );
+
+            ui.horizontal(|ui| {
+                let flash_button = ui
+                    .add_enabled(
+                        !self.is_busy() && avrdude_missing.is_none(),
+                        egui::Button::new("Flash device!"),
+                    )
+                    .on_disabled_hover_text(
+                        avrdude_missing
+                            .as_deref()
+                            .unwrap_or("Another job is running"),
+                    );
+                if flash_button.clicked() {
+                    match self.start_flash(ui.ctx()) {
+                        Ok(()) => self.general_error = None,
+                        Err(e) => self.general_error = Some(e),
+                    }
+                }
+                if let Some(name) = self.busy() {
+                    ui.spinner();
+                    ui.label(format!("{} running", name));
+                }
+
+                if spec.uploader == Uploader::Avrdude {
+                    let verify_button = ui
+                        .add_enabled(!self.is_busy(), egui::Button::new("Verify"))
+                        .on_hover_text("Check if the board contains the selected file");
+                    if verify_button.clicked() {
+                        match self.start_verify(ui.ctx()) {
+                            Ok(()) => self.general_error = None,
+                            Err(e) => self.general_error = Some(e),
+                        }
+                    }
+
+                    let test_button = ui
+                        .add_enabled(!self.is_busy(), egui::Button::new("Test communication"))
+                        .on_hover_text(
+                            "Talk to the chip with writing disabled (-n) to check the port, the \
+                            cable and the board, no file is needed",
+                        );
+                    if test_button.clicked() {
+                        match self.start_test_communication(ui.ctx()) {
+                            Ok(()) => self.general_error = None,
+                            Err(e) => self.general_error = Some(e),
+                        }
+                    }
+
+                    let read_button = ui
+                        .add_enabled(!self.is_busy(), egui::Button::new("Read flash..."))
+                        .on_hover_text("Save the firmware of the board to a file");
+                    if read_button.clicked() {
+                        self.choose_read_destination(JobKind::ReadFlash, "flash.hex", ui.ctx());
+                    }
+                }
+            });
+            self.production_ui(ui);
+            self.batch_status_ui(ui);
+            // Easy to forget while it is on, so it is shown next to the flash button.
+            if self.settings.skip_verify
+                && spec.uploader == Uploader::Avrdude
+                && !self.locks_after_flash(&spec)
+            {
+                ui.colored_label(
+                    egui::Color32::from_rgb(255, 140, 0),
+                    "Verification is skipped, the flash is not checked after writing (see \
+                    Advanced)",
+                );
+            }
+
+            if spec.uploader == Uploader::Avrdude && !spec.updi {
+                egui::CollapsingHeader::new("Fuses").show(ui, |ui| {
+                    let fuses_button = ui
+                        .add_enabled(!self.is_busy(), egui::Button::new("Read fuses"))
+                        .on_hover_text("Show the fuse bytes of the chip, nothing is written");
+                    if fuses_button.clicked() {
+                        match self.start_read_fuses(false, ui.ctx()) {
+                            Ok(()) => self.general_error = None,
+                            Err(e) => self.general_error = Some(e),
+                        }
+                    }
+
+                    let Ok(layout) = FuseLayout::of(&spec.partno) else {
+                        return;
+                    };
+                    let blocked = if self.is_busy() {
+                        Some("Wait until the running job is done")
+                    } else if !spec.can_write_fuses() {
+                        Some(
+                            "Bootloaders can't write fuses, select an external programmer to \
+                            write them through ISP",
+                        )
+                    } else {
+                        None
+                    };
+                    let presets = spec.fuse_presets;
+                    if let Some(values) = self.fuse_editor.ui(ui, &layout, presets, blocked) {
+                        match self.start_write_fuses(layout, values, ui.ctx()) {
+                            Ok(()) => self.general_error = None,
+                            Err(e) => self.general_error = Some(e),
+                        }
+                    }
+
+                    let mut with_flash = self.fuses_with_flash.is_some();
+                    let label = match self.fuses_with_flash {
+                        Some((layout, values)) => {
+                            format!("Write {} with each flash", fuse_list(&layout, values))
+                        }
+                        None => "Write the fuses above with each flash".to_owned(),
+                    };
+                    let checkbox = ui.checkbox(&mut with_flash, label).on_hover_text(
+                        "The fuses are written in the same avrdude run after the flash and the \
+                        EEPROM, so the chip is only reset once",
+                    );
+                    if checkbox.changed() {
+                        if with_flash {
+                            match self.fuse_editor.values(&layout) {
+                                Ok(values) => {
+                                    self.confirm_fuses_with_flash = Some((layout, values))
+                                }
+                                Err(e) => self.general_error = Some(e.into()),
+                            }
+                        } else {
+                            self.fuses_with_flash = None;
+                        }
+                    }
+                });
+
+                egui::CollapsingHeader::new("Lock bits").show(ui, |ui| {
+                    let read_button = ui
+                        .add_enabled(!self.is_busy(), egui::Button::new("Read lock bits"))
+                        .on_hover_text("Show the lock byte of the chip, nothing is written");
+                    if read_button.clicked() {
+                        match self.start_read_fuses(true, ui.ctx()) {
+                            Ok(()) => self.general_error = None,
+                            Err(e) => self.general_error = Some(e),
+                        }
+                    }
+
+                    let Ok(layout) = FuseLayout::lock(&spec.partno) else {
+                        return;
+                    };
+                    let blocked = if self.is_busy() {
+                        Some("Wait until the running job is done")
+                    } else if !spec.can_write_fuses() {
+                        Some(
+                            "Bootloaders can't write lock bits, select an external programmer \
+                            to write them through ISP",
+                        )
+                    } else {
+                        None
+                    };
+                    if let Some(values) = self.lock_editor.ui(ui, &layout, &[], blocked) {
+                        match self.start_write_fuses(layout, values, ui.ctx()) {
+                            Ok(()) => self.general_error = None,
+                            Err(e) => self.general_error = Some(e),
+                        }
+                    }
+
+                    let mut after_flash = self.lock_after_flash.is_some();
+                    let label = match self.lock_after_flash {
+                        Some(lock) => format!(
+                            "Set the lock bits to 0x{:02X} after each successful flash",
+                            lock
+                        ),
+                        None => "Set the lock bits above after each successful flash".to_owned(),
+                    };
+                    if ui.checkbox(&mut after_flash, label).changed() {
+                        if after_flash {
+                            match self.lock_editor.values(&layout) {
+                                Ok(values) => self.confirm_lock_after_flash = Some(values[0]),
+                                Err(e) => self.general_error = Some(e.into()),
+                            }
+                        } else {
+                            self.lock_after_flash = None;
+                        }
+                    }
+                });
+            }
+
+            if let Some((layout, values)) = self.confirm_fuses_with_flash {
+                egui::Window::new("Write fuses with each flash?")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                    .show(ui.ctx(), |ui| {
+                        ui.label(format!(
+                            "Each flash also writes {} after the flash and the EEPROM.",
+                            fuse_list(&layout, values)
+                        ));
+                        ui.label(fuses::FUSE_WARNING);
+                        ui.horizontal(|ui| {
+                            if ui.button("Write with each flash").clicked() {
+                                self.fuses_with_flash = Some((layout, values));
+                                self.confirm_fuses_with_flash = None;
+                            }
+                            if ui.button("Cancel").clicked() {
+                                self.confirm_fuses_with_flash = None;
+                            }
+                        });
+                    });
+            }
+
+            if let Some(lock) = self.confirm_lock_after_flash {
+                egui::Window::new("Lock after flashing?")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                    .show(ui.ctx(), |ui| {
+                        ui.label(format!(
+                            "After each flash which was written and verified successfully the \
+                            lock byte is set to 0x{:02X}.",
+                            lock
+                        ));
+                        ui.label(fuses::LOCK_WARNING);
+                        ui.horizontal(|ui| {
+                            if ui.button("Lock after flashing").clicked() {
+                                self.lock_after_flash = Some(lock);
+                                self.confirm_lock_after_flash = None;
+                            }
+                            if ui.button("Cancel").clicked() {
+                                self.confirm_lock_after_flash = None;
+                            }
+                        });
+                    });
+            }
+
+            if spec.uploader == Uploader::Avrdude {
+                egui::CollapsingHeader::new("Backups").show(ui, |ui| self.backup_ui(ui, &spec));
+            }
+
+            if spec.uploader == Uploader::Avrdude {
+                egui::CollapsingHeader::new("EEPROM").show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let idle = !self.is_busy();
+                        if ui
+                            .add_enabled(idle, egui::Button::new("Read EEPROM to file..."))
+                            .clicked()
+                        {
+                            self.choose_read_destination(
+                                JobKind::ReadEeprom,
+                                "eeprom.hex",
+                                ui.ctx(),
+                            );
+                        }
+                        if ui
+                            .add_enabled(idle, egui::Button::new("Write EEPROM from file..."))
+                            .clicked()
+                        {
+                            let source = FileDialog::new()
+                                .add_filter("Intel HEX", &["hex", "eep"])
+                                .add_filter("Raw binary", &["bin"])
+                                .pick_file();
+                            if let Some(source) = source {
+                                match self.start_write_eeprom(source, ui.ctx()) {
+                                    Ok(()) => self.general_error = None,
+                                    Err(e) => self.general_error = Some(e),
+                                }
+                            }
+                        }
+                    });
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        "Writing the EEPROM never erases the chip, but flashing with chip erase \
+                        clears the EEPROM again unless the EESAVE fuse is set",
+                    );
+                });
+            }
+
+            if let Some(backups) = self.restore_list.clone() {
+                egui::Window::new("Restore a backup")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                    .show(ui.ctx(), |ui| {
+                        if backups.is_empty() {
+                            ui.label("No backups were taken of the board on the selected port.");
+                        }
+                        egui::ScrollArea::vertical()
+                            .max_height(300.0)
+                            .show(ui, |ui| {
+                                for backup in &backups {
+                                    ui.horizontal(|ui| {
+                                        let restore = ui
+                                            .add_enabled(
+                                                backup.size.is_ok(),
+                                                egui::Button::new("Restore"),
+                                            )
+                                            .on_hover_text(backup.path.to_string_lossy());
+                                        if restore.clicked() {
+                                            self.confirm_restore = Some(backup.path.clone());
+                                            self.restore_list = None;
+                                        }
+                                        ui.label(backup.describe());
+                                    });
+                                }
+                            });
+                        ui.horizontal(|ui| {
+                            if ui.button("Browse...").clicked() {
+                                let file = FileDialog::new()
+                                    .add_filter("Intel HEX", &["hex"])
+                                    .pick_file();
+                                if let Some(file) = file {
+                                    self.confirm_restore = Some(file);
+                                    self.restore_list = None;
+                                }
+                            }
+                            if ui.button("Close").clicked() {
+                                self.restore_list = None;
+                            }
+                        });
+                    });
+            }
+
+            if let Some(ports) = self.confirm_matching.clone() {
+                egui::Window::new("Flash all matching boards?")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                    .show(ui.ctx(), |ui| {
+                        ui.label(format!(
+                            "{} \u{00d7} {} found \u{2014} flash all?",
+                            ports.len(),
+                            self.selected_board.name()
+                        ));
+                        for port in &ports {
+                            ui.label(&port.port_name);
+                        }
+                        ui.horizontal(|ui| {
+                            let flash_all =
+                                ui.add_enabled(!self.is_busy(), egui::Button::new("Flash all"));
+                            if flash_all.clicked() {
+                                self.confirm_matching = None;
+                                // A board with another chip is reported and the others are still
+                                // flashed.
+                                match self.start_batch(ports.clone(), false) {
+                                    Ok(()) => self.general_error = None,
+                                    Err(e) => self.general_error = Some(e),
+                                }
+                            }
+                            if ui.button("Cancel").clicked() {
+                                self.confirm_matching = None;
+                            }
+                        });
+                    });
+            }
+
+            if let Some(path) = self.confirm_restore.clone() {
+                egui::Window::new("Restore backup?")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                    .show(ui.ctx(), |ui| {
+                        ui.label(format!(
+                            "The flash of the board is overwritten with {} and verified \
+                            afterwards.",
+                            path.display()
+                        ));
+                        ui.horizontal(|ui| {
+                            let confirm =
+                                ui.add_enabled(!self.is_busy(), egui::Button::new("Restore"));
+                            if confirm.clicked() {
+                                self.confirm_restore = None;
+                                match self.start_restore(path.clone(), ui.ctx()) {
+                                    Ok(()) => self.general_error = None,
+                                    Err(e) => self.general_error = Some(e),
+                                }
+                            }
+                            if ui.button("Cancel").clicked() {
+                                self.confirm_restore = None;
+                            }
+                        });
+                    });
+            }
+
+            if let Some((kind, destination)) = self.confirm_read.clone() {
+                egui::Window::new("Overwrite file?")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                    .show(ui.ctx(), |ui| {
+                        ui.label(format!(
+                            "{} already exists, it is replaced by the {} of the board.",
+                            destination.display(),
+                            kind.read_memory().unwrap_or_default()
+                        ));
+                        ui.horizontal(|ui| {
+                            let confirm =
+                                ui.add_enabled(!self.is_busy(), egui::Button::new("Overwrite"));
+                            if confirm.clicked() {
+                                self.confirm_read = None;
+                                match self.start_read(kind, destination.clone(), ui.ctx()) {
+                                    Ok(()) => self.general_error = None,
+                                    Err(e) => self.general_error = Some(e),
+                                }
+                            }
+                            if ui.button("Cancel").clicked() {
+                                self.confirm_read = None;
+                            }
+                        });
+                    });
+            }
+
+            if spec.uploader == Uploader::Avrdude {
+                ui.horizontal(|ui| {
+                    if spec.bootloader.is_some() {
+                        let burn_button =
+                            ui.add_enabled(!self.is_busy(), egui::Button::new("Burn bootloader"));
+                        if burn_button.clicked() {
+                            self.confirm_burn = true;
+                        }
+                    }
+
+                    let erase_button = ui
+                        .add_enabled(
+                            !self.is_busy() && spec.can_erase_chip(),
+                            egui::Button::new("Erase chip"),
+                        )
+                        .on_hover_text("Erase the flash without writing a new program")
+                        .on_disabled_hover_text(
+                            "The bootloader only pretends to erase the chip and keeps the \
+                            program, select an external programmer for a real chip erase or \
+                            flash an empty sketch instead",
+                        );
+                    if erase_button.clicked() {
+                        self.confirm_erase = true;
+                    }
+                });
+            }
+
+            if self.confirm_erase {
+                egui::Window::new("Erase chip?")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                    .show(ui.ctx(), |ui| {
+                        let bootloader = if spec.bootloader.is_some() {
+                            " This also removes the bootloader, burn it again to flash the board \
+                            over usb."
+                        } else {
+                            ""
+                        };
+                        ui.label(format!(
+                            "This erases the whole flash of the {} and also the EEPROM unless the \
+                            EESAVE fuse is set.{}",
+                            spec.partno, bootloader
+                        ));
+                        ui.horizontal(|ui| {
+                            let confirm =
+                                ui.add_enabled(!self.is_busy(), egui::Button::new("Erase"));
+                            if confirm.clicked() {
+                                self.confirm_erase = false;
+                                match self.start_erase(ui.ctx()) {
+                                    Ok(()) => self.general_error = None,
+                                    Err(e) => self.general_error = Some(e),
+                                }
+                            }
+                            if ui.button("Cancel").clicked() {
+                                self.confirm_erase = false;
+                            }
+                        });
+                    });
+            }
+
+            if self.confirm_burn {
+                egui::Window::new("Burn bootloader?")
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                    .show(ui.ctx(), |ui| {
+                        ui.label(format!(
+                            "This erases the {} and writes its fuses, wrong fuses can make the \
+                            chip unusable without a high voltage programmer.",
+                            spec.partno
+                        ));
+                        ui.horizontal(|ui| {
+                            let confirm =
+                                ui.add_enabled(!self.is_busy(), egui::Button::new("Burn"));
+                            if confirm.clicked() {
+                                self.confirm_burn = false;
+                                match self.start_burn_bootloader(ui.ctx()) {
+                                    Ok(()) => self.general_error = None,
+                                    Err(e) => self.general_error = Some(e),
+                                }
+                            }
+                            if ui.button("Cancel").clicked() {
+                                self.confirm_burn = false;
+                            }
+                        });
+                    });
+            }
+
+            if let Some(ref cmd) = self.used_command {
+                ui.label(cmd);
+            }
+            let output = match (&self.running, &self.result) {
+                (Some(running), _) => Some(running.log.text()),
+                (None, Some(result)) => Some(result.output.clone()),
+                (None, None) => None,
+            }
+            .filter(|output| !output.is_empty());
+            if self.command_lines.is_some() || output.is_some() {
+                ui.horizontal(|ui| {
+                    if let Some(ref lines) = self.command_lines {
+                        if ui.button("Copy command").clicked() {
+                            ui.output_mut(|o| o.copied_text = lines.clone());
+                            self.copied = Some(("Command copied", Instant::now()));
+                        }
+                    }
+                    if let Some(output) = output {
+                        if ui.button("Copy output").clicked() {
+                            ui.output_mut(|o| o.copied_text = output);
+                            self.copied = Some(("Output copied", Instant::now()));
+                        }
+                    }
+                    match self.copied {
+                        Some((note, at)) if at.elapsed() < COPIED_NOTE => {
+                            ui.label(note);
+                            ui.ctx().request_repaint_after(COPIED_NOTE - at.elapsed());
+                        }
+                        _ => self.copied = None,
+                    }
+                });
+            }
+
+            if let Some(ref running) = self.running {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(running.uploader.running_message());
+                    ui.label(format!("{:.1} s", running.started.elapsed().as_secs_f32()));
+                    ui.ctx().request_repaint_after(Duration::from_millis(100));
+                    let cancel =
+                        ui.add_enabled(!running.log.is_cancelled(), egui::Button::new("Cancel"));
+                    if cancel.clicked() {
+                        running.log.cancel();
+                    }
+                });
+                let log = running.log.text();
+                match Progress::parse(&log) {
+                    Some(progress) => {
+                        ui.add(egui::ProgressBar::new(progress.fraction).text(format!(
+                            "{} {:.0}%",
+                            progress.phase,
+                            progress.fraction * 100.0
+                        )));
+                    }
+                    None => {
+                        ui.spinner();
+                    }
+                }
+                output_view(ui, &log);
+            } else if let Some(ref result) = self.result {
+                if result.ui(ui) {
+                    self.advanced.bitclock = result.slow_bitclock.clone().unwrap_or_default();
+                }
+            }
+            if self.stats.attempted > 0 {
+                ui.horizontal(|ui| {
+                    ui.label(self.stats.describe())
+                        .on_hover_text(format!("Since {}", format_time(self.stats.since)));
+                    if ui.small_button("Reset").clicked() {
+                        self.stats = SessionStats::default();
+                    }
+                });
+            }
+            if let Some(ref warning) = self.log_warning {
+                ui.colored_label(egui::Color32::YELLOW, warning);
+            }
+            if self.timings.len() > 1 {
+                egui::CollapsingHeader::new("Recent timings").show(ui, |ui| {
+                    for timing in &self.timings {
+                        ui.label(format!(
+                            "{}: {}",
+                            timing.name,
+                            completed(timing.elapsed, timing.bytes)
+                        ));
+                    }
+                });
+            }
+            if !self.history.is_empty() {
+                let mut again = None;
+                egui::CollapsingHeader::new("History").show(ui, |ui| {
+                    egui::ScrollArea::vertical()
+                        .id_source("History")
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for entry in &self.history {
+                                ui.horizontal(|ui| {
+                                    if entry.kind == JobKind::Flash && entry.file.is_some() {
+                                        let button = ui
+                                            .add_enabled(
+                                                !self.is_busy(),
+                                                egui::Button::new("Run again"),
+                                            )
+                                            .on_hover_text(
+                                                "Select the board, the port and the file again \
+                                                and flash, Ctrl+R does it for the last successful \
+                                                flash",
+                                            );
+                                        if button.clicked() {
+                                            again = Some(entry.clone());
+                                        }
+                                    }
+                                    ui.label(entry.describe());
+                                });
+                            }
+                        });
+                });
+                if let Some(entry) = again {
+                    self.run_again(entry, ui.ctx());
+                }
+            }
+        });
+
+        self.compare.ui(ctx);
+    }
 }
@@ -0,0 +1,113 @@
+//! Flashing every newly connected board without clicking, for manufacturing runs
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use serialport::SerialPortInfo;
+
+use crate::board::SelectedBoard;
+
+/// How often the ports are scanned for new boards.
+pub const SCAN_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a new port has to stay connected before it is flashed, the board may still be
+/// starting.
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// The state of the production mode, which flashes the selected file to each new board
+pub struct Production {
+    /// The board the mode was started for, it stops if another one is selected.
+    pub board: SelectedBoard,
+    /// The file the mode was started with, it stops if another one is selected.
+    pub file: PathBuf,
+    /// The ports which are not flashed until they disappeared, because they were connected when
+    /// the mode started, were flashed already or appeared while a flash ran.
+    done: BTreeSet<String>,
+    /// The new ports by the time they appeared, they are flashed once they settled.
+    appeared: BTreeMap<String, Instant>,
+    /// When the ports were scanned last.
+    last_scan: Instant,
+    /// The port of the running flash, `None` while waiting for a board.
+    pub flashing: Option<String>,
+    /// Wether the next scan is the first one after a flash, the ports are not scanned during it.
+    after_flash: bool,
+    /// The port of the last flashed board and wether the flash succeeded.
+    pub last_unit: Option<(String, bool)>,
+    /// The number of boards flashed since the mode started.
+    pub units: u32,
+}
+
+impl Production {
+    /// Start flashing the given file for the given board, the given ports which are connected
+    /// already are left alone.
+    pub fn new(board: SelectedBoard, file: PathBuf, ports: &[SerialPortInfo]) -> Self {
+        Self {
+            board,
+            file,
+            done: ports.iter().map(|port| port.port_name.clone()).collect(),
+            appeared: BTreeMap::new(),
+            last_scan: Instant::now(),
+            flashing: None,
+            after_flash: false,
+            last_unit: None,
+            units: 0,
+        }
+    }
+
+    /// Wether the ports should be scanned again.
+    pub fn scan_due(&self) -> bool {
+        self.last_scan.elapsed() >= SCAN_INTERVAL
+    }
+
+    /// Take the given ports of a new scan which match the board, returns the new port which
+    /// settled and is flashed now. Ports which appeared while a flash ran are the flashed board
+    /// starting again, so they are never flashed.
+    pub fn update(&mut self, matching: &[&SerialPortInfo]) -> Option<SerialPortInfo> {
+        self.last_scan = Instant::now();
+        let after_flash = std::mem::take(&mut self.after_flash);
+        let connected: BTreeSet<&str> = matching
+            .iter()
+            .map(|port| port.port_name.as_str())
+            .collect();
+        // A board which was unplugged can be flashed again when it is plugged in.
+        self.done.retain(|port| connected.contains(port.as_str()));
+        self.appeared
+            .retain(|port, _| connected.contains(port.as_str()));
+        for port in matching {
+            let name = &port.port_name;
+            if self.done.contains(name) || self.appeared.contains_key(name) {
+                continue;
+            }
+            if after_flash {
+                self.done.insert(name.clone());
+            } else {
+                self.appeared.insert(name.clone(), Instant::now());
+            }
+        }
+
+        let settled = self
+            .appeared
+            .iter()
+            .find(|(_, appeared)| appeared.elapsed() >= SETTLE_TIME)
+            .map(|(port, _)| port.clone())?;
+        self.appeared.remove(&settled);
+        self.done.insert(settled.clone());
+        self.units += 1;
+        self.flashing = Some(settled.clone());
+        matching
+            .iter()
+            .find(|port| port.port_name == settled)
+            .map(|&port| port.clone())
+    }
+
+    /// Remember how the flash of the running board ended.
+    pub fn finished(&mut self, succeeded: bool) {
+        if let Some(port) = self.flashing.take() {
+            self.last_unit = Some((port, succeeded));
+            self.after_flash = true;
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! The progress of avrdude parsed from its output
+
+/// The bars avrdude draws while reading or writing a memory.
+const BARS: [(&str, &str); 2] = [("Writing |", "Writing"), ("Reading |", "Reading")];
+
+/// The number of `#` avrdude prints for a complete bar.
+const BAR_WIDTH: usize = 50;
+
+/// The progress of the current phase
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Progress {
+    /// The name of the phase shown to the user.
+    pub phase: &'static str,
+    /// How much of the phase is done, from 0 to 1.
+    pub fraction: f32,
+}
+
+impl Progress {
+    /// Parse the progress of the last bar in the output of avrdude. avrdude 6 only appends `#`
+    /// until the bar is complete while avrdude 7 redraws the bar including a percentage.
+    pub fn parse(output: &str) -> Option<Self> {
+        let (start, marker, phase) = BARS
+            .iter()
+            .filter_map(|&(marker, phase)| output.rfind(marker).map(|start| (start, marker, phase)))
+            .max_by_key(|&(start, _, _)| start)?;
+        let bar = output[start + marker.len()..]
+            .lines()
+            .next()
+            .unwrap_or_default();
+
+        let percent = bar.find('%').and_then(|end| {
+            let digits = bar[..end].trim_end();
+            let begin = digits
+                .rfind(|c: char| !c.is_ascii_digit())
+                .map_or(0, |i| i + 1);
+            digits[begin..].parse::<f32>().ok()
+        });
+        let fraction = match percent {
+            Some(percent) => percent / 100.0,
+            None => bar.chars().filter(|&c| c == '#').count() as f32 / BAR_WIDTH as f32,
+        };
+
+        // The flash is read back after it was written to verify it.
+        let verifying = phase == "Reading"
+            && output[..start]
+                .rfind("Writing |")
+                .is_some_and(|write| output[write..start].contains("verifying"));
+        Some(Self {
+            phase: if verifying { "Verifying" } else { phase },
+            fraction: fraction.clamp(0.0, 1.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The output of avrdude 6.3 flashing an Uno, cut off while the flash is read back.
+    const AVRDUDE_6_VERIFYING: &str = "\
+avrdude: AVR device initialized and ready to accept instructions
+
+Reading | ################################################## | 100% 0.00s
+
+avrdude: Device signature = 0x1e950f (probably m328p)
+avrdude: reading input file \"blink.hex\"
+avrdude: writing flash (924 bytes):
+
+Writing | ################################################## | 100% 0.17s
+
+avrdude: 924 bytes of flash written
+avrdude: verifying flash memory against blink.hex:
+avrdude: load data flash data from input file blink.hex:
+avrdude: input file blink.hex contains 924 bytes
+avrdude: reading on-chip flash data:
+
+Reading | ################";
+
+    /// The output of avrdude 7.2 while the flash is written, the bar is redrawn after `\r`.
+    const AVRDUDE_7_WRITING: &str = "\
+avrdude: AVR device initialized and ready to accept instructions
+avrdude: device signature = 0x1e950f (probably m328p)
+avrdude: erasing chip
+avrdude: reading input file blink.hex for flash
+         with 924 bytes in 1 section within [0, 0x39b]
+         using 8 pages and 100 pad bytes
+avrdude: writing 924 bytes flash ...
+
+Writing |                                                    | 0% 0.00 s \
+\rWriting | ##########                                         | 20% 0.03 s \
+\rWriting | #####################                              | 42% 0.06 s ";
+
+    #[test]
+    fn avrdude_6_appended_bars() {
+        let writing = AVRDUDE_6_VERIFYING
+            .find("avrdude: 924 bytes of flash written")
+            .unwrap();
+        let partial = format!(
+            "{}Writing | ##########",
+            &AVRDUDE_6_VERIFYING[..AVRDUDE_6_VERIFYING.find("Writing").unwrap()]
+        );
+        assert_eq!(
+            Progress::parse(&partial),
+            Some(Progress {
+                phase: "Writing",
+                fraction: 0.2
+            })
+        );
+        assert_eq!(
+            Progress::parse(&AVRDUDE_6_VERIFYING[..writing]),
+            Some(Progress {
+                phase: "Writing",
+                fraction: 1.0
+            })
+        );
+        assert_eq!(
+            Progress::parse(AVRDUDE_6_VERIFYING),
+            Some(Progress {
+                phase: "Verifying",
+                fraction: 0.32
+            })
+        );
+    }
+
+    #[test]
+    fn avrdude_7_redrawn_bars() {
+        assert_eq!(
+            Progress::parse(AVRDUDE_7_WRITING),
+            Some(Progress {
+                phase: "Writing",
+                fraction: 0.42
+            })
+        );
+        let verifying = format!(
+            "{}\rWriting | {} | 100% 0.15 s \n\navrdude: 924 bytes of flash written\n\
+            avrdude: verifying flash memory against blink.hex\n\n\
+            Reading | #########################                          | 50% 0.06 s ",
+            AVRDUDE_7_WRITING,
+            "#".repeat(BAR_WIDTH)
+        );
+        assert_eq!(
+            Progress::parse(&verifying),
+            Some(Progress {
+                phase: "Verifying",
+                fraction: 0.5
+            })
+        );
+    }
+
+    #[test]
+    fn no_bar_yet() {
+        assert_eq!(
+            Progress::parse("avrdude: AVR device initialized and ready to accept instructions\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Searching the build artifacts of a project directory
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// How deep the project directory is searched.
+const MAX_DEPTH: usize = 8;
+
+/// The pattern used to find the artifacts if none is configured.
+pub const DEFAULT_PATTERN: &str = "**/*.{hex,elf}";
+
+/// The build systems whose output layout is known
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BuildSystem {
+    /// PlatformIO, configured by a platformio.ini
+    PlatformIo,
+    /// The arduino-cli, configured by a sketch.yaml
+    ArduinoCli,
+}
+
+impl BuildSystem {
+    /// The name of the file configuring a project of the build system.
+    pub fn config_file(&self) -> &'static str {
+        match self {
+            Self::PlatformIo => "platformio.ini",
+            Self::ArduinoCli => "sketch.yaml",
+        }
+    }
+
+    /// The name of a build target of the build system.
+    pub fn target_label(&self) -> &'static str {
+        match self {
+            Self::PlatformIo => "Environment",
+            Self::ArduinoCli => "FQBN",
+        }
+    }
+}
+
+/// A project of a known build system.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BuildLayout {
+    /// The build system of the project.
+    pub system: BuildSystem,
+    /// The file configuring the project.
+    pub config: PathBuf,
+    /// The environments or FQBNs the project is built for.
+    pub targets: Vec<BuildTarget>,
+}
+
+/// An environment or FQBN of a project.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BuildTarget {
+    /// The name of the environment or the FQBN.
+    pub name: String,
+    /// The firmware built for the target, `None` if it was not built yet.
+    pub artifact: Option<PathBuf>,
+}
+
+impl BuildLayout {
+    /// Detect the build system of the given project directory.
+    pub fn detect(dir: &Path) -> Option<Self> {
+        [BuildSystem::PlatformIo, BuildSystem::ArduinoCli]
+            .into_iter()
+            .find_map(|system| {
+                let config = dir.join(system.config_file());
+                let content = fs::read_to_string(&config).ok()?;
+                let targets = match system {
+                    BuildSystem::PlatformIo => platformio_targets(dir, &content),
+                    BuildSystem::ArduinoCli => arduino_cli_targets(dir, &content),
+                };
+                Some(Self {
+                    system,
+                    config,
+                    targets,
+                })
+            })
+    }
+
+    /// Wether the configuration of the project changed after the artifact was built.
+    pub fn is_stale(&self, artifact: &Path) -> bool {
+        let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        matches!(
+            (modified(&self.config), modified(artifact)),
+            (Some(config), Some(artifact)) if config > artifact
+        )
+    }
+}
+
+/// The environments of a PlatformIO project, built to `.pio/build/<env>/firmware.*`.
+fn platformio_targets(dir: &Path, ini: &str) -> Vec<BuildTarget> {
+    let build = dir.join(".pio").join("build");
+    let mut names: Vec<String> = ini
+        .lines()
+        .filter_map(|line| {
+            let section = line.trim().strip_prefix("[env:")?.strip_suffix(']')?;
+            Some(section.trim().to_owned())
+        })
+        .collect();
+    add_built_dirs(&mut names, &build, |name| name.to_owned());
+
+    names
+        .into_iter()
+        .map(|name| {
+            let env = build.join(&name);
+            let artifact = ["firmware.hex", "firmware.bin", "firmware.elf"]
+                .into_iter()
+                .map(|file| env.join(file))
+                .find(|path| path.is_file());
+            BuildTarget { name, artifact }
+        })
+        .collect()
+}
+
+/// The FQBNs of an arduino-cli sketch, built to `build/<fqbn with dots>/<sketch>.ino.*`.
+fn arduino_cli_targets(dir: &Path, yaml: &str) -> Vec<BuildTarget> {
+    let build = dir.join("build");
+    let mut names: Vec<String> = yaml
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim().trim_start_matches("- ");
+            let fqbn = line
+                .strip_prefix("default_fqbn:")
+                .or_else(|| line.strip_prefix("fqbn:"))?;
+            Some(fqbn.trim().trim_matches('"').to_owned())
+        })
+        .filter(|fqbn| !fqbn.is_empty())
+        .collect();
+    add_built_dirs(&mut names, &build, |name| name.replace('.', ":"));
+
+    names
+        .into_iter()
+        .map(|name| {
+            let target = build.join(name.replace(':', "."));
+            let files: Vec<PathBuf> = fs::read_dir(&target)
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect();
+            // The image including the bootloader can only be flashed through a programmer.
+            let artifact = [".ino.hex", ".ino.bin", ".ino.elf"]
+                .into_iter()
+                .find_map(|suffix| {
+                    files
+                        .iter()
+                        .find(|path| path.to_string_lossy().ends_with(suffix))
+                        .cloned()
+                });
+            BuildTarget { name, artifact }
+        })
+        .collect()
+}
+
+/// Add the targets built into the given directory which are missing in the configuration.
+fn add_built_dirs(names: &mut Vec<String>, build: &Path, name: impl Fn(&str) -> String) {
+    let mut built: Vec<String> = fs::read_dir(build)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| name(&entry.file_name().to_string_lossy()))
+        .filter(|built| !names.contains(built))
+        .collect();
+    built.sort_unstable();
+    let mut seen = Vec::new();
+    names.retain(|name| {
+        let new = !seen.contains(name);
+        if new {
+            seen.push(name.clone());
+        }
+        new
+    });
+    names.append(&mut built);
+}
+
+/// Find the most recently modified file in the given directory whose path relative to the
+/// directory matches the glob pattern.
+pub fn newest_artifact(dir: &Path, pattern: &str) -> Result<PathBuf, String> {
+    let patterns = expand_braces(pattern.trim());
+    let mut newest: Option<(SystemTime, PathBuf)> = None;
+    let mut errors = Vec::new();
+    let mut pending = vec![(dir.to_owned(), 0)];
+
+    while let Some((current, depth)) = pending.pop() {
+        let entries = match fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(e) => {
+                errors.push((current, e));
+                continue;
+            }
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    errors.push((current.clone(), e));
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if depth + 1 < MAX_DEPTH {
+                    pending.push((path, depth + 1));
+                }
+                continue;
+            }
+
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            // Patterns always use slashes, also on windows.
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            if !patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, &relative))
+            {
+                continue;
+            }
+            let modified = match entry.metadata().and_then(|meta| meta.modified()) {
+                Ok(modified) => modified,
+                Err(e) => {
+                    errors.push((path, e));
+                    continue;
+                }
+            };
+            if newest.as_ref().is_none_or(|(time, _)| modified > *time) {
+                newest = Some((modified, path));
+            }
+        }
+    }
+
+    match newest {
+        Some((_, path)) => Ok(path),
+        None => Err(match errors.first() {
+            Some((path, e)) if e.kind() == io::ErrorKind::PermissionDenied => format!(
+                "Error: no file in {} matches {}, {} could not be searched: permission denied",
+                dir.display(),
+                pattern,
+                path.display()
+            ),
+            Some((path, e)) => format!(
+                "Error: no file in {} matches {}, {} could not be searched: {}",
+                dir.display(),
+                pattern,
+                path.display(),
+                e
+            ),
+            None => format!("Error: no file in {} matches {}", dir.display(), pattern),
+        }),
+    }
+}
+
+/// Expand the first `{a,b}` group of the pattern, recursively for the following groups.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let (Some(start), Some(end)) = (pattern.find('{'), pattern.find('}')) else {
+        return vec![pattern.to_owned()];
+    };
+    if end < start {
+        return vec![pattern.to_owned()];
+    }
+    let (prefix, rest) = (&pattern[..start], &pattern[end + 1..]);
+    pattern[start + 1..end]
+        .split(',')
+        .flat_map(|alternative| expand_braces(&format!("{}{}{}", prefix, alternative, rest)))
+        .collect()
+}
+
+/// Wether the slash separated path matches the glob pattern, `*` and `?` do not match slashes and
+/// `**/` matches any number of directories.
+fn glob_match(pattern: &str, path: &str) -> bool {
+    matches(pattern.as_bytes(), path.as_bytes())
+}
+
+/// Match the remaining pattern against the remaining path.
+fn matches(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            matches(rest, path)
+                || path
+                    .iter()
+                    .enumerate()
+                    .any(|(i, &c)| c == b'/' && matches(rest, &path[i + 1..]))
+        }
+        [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+        [b'*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != b'/')
+            .any(|i| matches(rest, &path[i..])),
+        [b'?', rest @ ..] => path
+            .split_first()
+            .is_some_and(|(&c, path)| c != b'/' && matches(rest, path)),
+        [c, rest @ ..] => path
+            .split_first()
+            .is_some_and(|(p, path)| p.eq_ignore_ascii_case(c) && matches(rest, path)),
+    }
+}
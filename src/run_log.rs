@@ -0,0 +1,101 @@
+//! The log files written after each job, so every flash can be traced later
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
+
+use crate::{
+    backup,
+    file_info::{self, file_time, format_time},
+};
+
+/// What is known about a job when it starts, written at the top of its log
+#[derive(Debug, Clone)]
+pub struct RunInfo {
+    /// When the job was started.
+    pub started: SystemTime,
+    /// The name of the job, like "Flash".
+    pub job: &'static str,
+    /// The commands of the job as shown to the user.
+    pub command: String,
+    /// The name of the selected board.
+    pub board: String,
+    /// The port of the board, `None` if the uploader finds the board on its own.
+    pub port: Option<String>,
+    /// The file written to or compared with the board, `None` if the job uses none.
+    pub firmware: Option<PathBuf>,
+}
+
+impl RunInfo {
+    /// The path of the log in the given directory, like "2024-05-10T14-32-07_ttyUSB0.log".
+    pub fn path(&self, dir: &Path) -> PathBuf {
+        let port = backup::port_prefix(self.port.as_deref().unwrap_or("board"));
+        log_path(dir, self.started, &port)
+    }
+
+    /// Write the log of the job which ended with the given status and printed the given output
+    /// into the given directory, which is created if needed. Returns the path of the log.
+    pub fn write(&self, dir: &Path, output: &str, status: &str) -> Result<PathBuf, String> {
+        let firmware = match self.firmware {
+            Some(ref path) => {
+                let hash = file_info::hash_file(path).unwrap_or_else(|e| format!("<{}>", e));
+                format!("Firmware: {}\nSHA-256: {}\n", path.display(), hash)
+            }
+            None => String::new(),
+        };
+        let log = format!(
+            "Time: {}\nJob: {}\nBoard: {}\nPort: {}\n{}Command: {}\nStatus: {}\n\n{}",
+            format_time(self.started),
+            self.job,
+            self.board,
+            self.port.as_deref().unwrap_or("<found by the uploader>"),
+            firmware,
+            self.command,
+            status,
+            output
+        );
+        write(dir, self.path(dir), &log)
+    }
+}
+
+/// Write the given summary of the session which started at the given time into the given
+/// directory, like "2024-05-10T14-32-07_session.log". Returns the path of the log.
+pub fn write_session(dir: &Path, started: SystemTime, summary: &str) -> Result<PathBuf, String> {
+    write(dir, log_path(dir, started, "session"), summary)
+}
+
+/// The path of a log in the given directory, named after the given time and name.
+fn log_path(dir: &Path, time: SystemTime, name: &str) -> PathBuf {
+    let time = file_time(time).replacen('_', "T", 1);
+    dir.join(format!("{}_{}.log", time, name))
+}
+
+/// Write the given log to the given path in the given directory, which is created if needed.
+fn write(dir: &Path, path: PathBuf, log: &str) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir)
+        .and_then(|()| fs::write(&path, log))
+        .map_err(|e| format!("Warning: could not write the log {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// The directory the logs are written to if none is configured, `None` if there is no data
+/// directory.
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("arduino_installer_gui").join("logs"))
+}
+
+/// Show the given directory in the file manager, it is created if needed.
+pub fn open_dir(dir: &Path) -> io::Result<()> {
+    let program = if cfg!(windows) {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    fs::create_dir_all(dir)?;
+    Command::new(program).arg(dir).spawn().map(drop)
+}
@@ -0,0 +1,240 @@
+//! Settings which are kept between runs
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backup,
+    board::Verbosity,
+    project::DEFAULT_PATTERN,
+    touch,
+    upload::{Hook, ResetPulse},
+};
+
+/// The file name of the settings.
+const FILE_NAME: &str = "settings.toml";
+
+/// The number of recently selected files which are remembered.
+pub const MAX_RECENT_FILES: usize = 10;
+
+/// The settings stored in the config directory.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Settings {
+    /// The recently selected files, the most recent one first.
+    #[serde(default)]
+    pub recent_files: Vec<PathBuf>,
+    /// The directory of the last selected file, the file dialog is opened in it.
+    #[serde(default)]
+    pub last_directory: Option<PathBuf>,
+    /// The name of the file dialog filter used last.
+    #[serde(default)]
+    pub last_filter: Option<String>,
+    /// The glob pattern the build artifacts of a project directory are searched with.
+    #[serde(default = "default_artifact_pattern")]
+    pub artifact_pattern: String,
+    /// The ELF section the version string of the firmware is read from.
+    #[serde(default = "default_version_section")]
+    pub version_section: String,
+    /// Wether the signature of the chip is compared with the selected board before flashing.
+    #[serde(default = "default_true")]
+    pub check_signature: bool,
+    /// The avrdude executable chosen by the user, `None` to search it in PATH.
+    #[serde(default)]
+    pub avrdude_path: Option<PathBuf>,
+    /// The avrdude.conf passed to avrdude with `-C`, `None` to use the default one of avrdude.
+    #[serde(default)]
+    pub avrdude_conf: Option<PathBuf>,
+    /// The arguments appended to every avrdude command, split like a shell does.
+    #[serde(default)]
+    pub extra_avrdude_args: String,
+    /// How much avrdude prints while it runs.
+    #[serde(default)]
+    pub verbosity: Verbosity,
+    /// How often avrdude is run before a transient failure is reported.
+    #[serde(default = "default_attempts")]
+    pub attempts: u8,
+    /// The seconds an avrdude command may run before it is killed, 0 to wait forever.
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+    /// The seconds waited for the bootloader port of native usb boards after the 1200 baud touch.
+    #[serde(default = "default_touch_timeout")]
+    pub touch_timeout_secs: u64,
+    /// The reset pulse sent through DTR and RTS before avrdude is run.
+    #[serde(default)]
+    pub reset_pulse: ResetPulse,
+    /// The extended parameters passed with `-x` by the name of the programmer they are used for.
+    #[serde(default)]
+    pub extended_params: BTreeMap<String, Vec<(String, String)>>,
+    /// Wether ATmega328P boards are tried at the baud rate of the other bootloader when they do
+    /// not sync.
+    #[serde(default)]
+    pub auto_baud: bool,
+    /// Wether avrdude skips verifying the flash after writing it, to flash many boards faster.
+    #[serde(default)]
+    pub skip_verify: bool,
+    /// The baud rate the bootloader answered at by the port, tried first on the next flash.
+    #[serde(default)]
+    pub port_bauds: BTreeMap<String, u32>,
+    /// The names of the boards flashed with the built-in uploader instead of avrdude.
+    #[serde(default)]
+    pub builtin_uploader: BTreeSet<String>,
+    /// Wether the flash is read to a backup before it is overwritten.
+    #[serde(default)]
+    pub backup_before_flash: bool,
+    /// The directory the backups are saved in, `None` for the default one in the data directory.
+    #[serde(default)]
+    pub backup_dir: Option<PathBuf>,
+    /// The number of backups kept per port, older ones are removed.
+    #[serde(default = "default_kept_backups")]
+    pub kept_backups: usize,
+    /// The command run before each flash.
+    #[serde(default)]
+    pub before_flash: Hook,
+    /// The command run after each successful flash.
+    #[serde(default)]
+    pub after_flash: Hook,
+    /// Wether the ports of a batch are flashed at the same time instead of one after another.
+    #[serde(default)]
+    pub parallel_flash: bool,
+    /// The number of ports of a batch flashed at the same time.
+    #[serde(default = "default_flash_concurrency")]
+    pub flash_concurrency: usize,
+    /// Wether a log file is written after each job.
+    #[serde(default = "default_true")]
+    pub write_logs: bool,
+    /// The directory the logs are written to, `None` for the default one in the data directory.
+    #[serde(default)]
+    pub log_dir: Option<PathBuf>,
+    /// Wether the ports are scanned every few seconds, some usb serial adapters reset when they
+    /// are enumerated repeatedly.
+    #[serde(default = "default_true")]
+    pub auto_rescan: bool,
+    /// Wether only usb serial devices are listed, hiding Bluetooth and legacy serial ports.
+    #[serde(default = "default_true")]
+    pub usb_only: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            recent_files: Vec::new(),
+            last_directory: None,
+            last_filter: None,
+            artifact_pattern: default_artifact_pattern(),
+            version_section: default_version_section(),
+            check_signature: true,
+            avrdude_path: None,
+            avrdude_conf: None,
+            extra_avrdude_args: String::new(),
+            verbosity: Verbosity::Normal,
+            attempts: default_attempts(),
+            timeout_secs: default_timeout(),
+            touch_timeout_secs: default_touch_timeout(),
+            reset_pulse: ResetPulse::default(),
+            extended_params: BTreeMap::new(),
+            auto_baud: false,
+            skip_verify: false,
+            port_bauds: BTreeMap::new(),
+            builtin_uploader: BTreeSet::new(),
+            backup_before_flash: false,
+            backup_dir: None,
+            kept_backups: default_kept_backups(),
+            before_flash: Hook::default(),
+            after_flash: Hook::default(),
+            parallel_flash: false,
+            flash_concurrency: default_flash_concurrency(),
+            write_logs: true,
+            log_dir: None,
+            auto_rescan: true,
+            usb_only: true,
+        }
+    }
+}
+
+impl Settings {
+    /// Load the settings, the defaults are used if none were saved yet.
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = path() else {
+            return Ok(Self::default());
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Error: could not read {}: {}", path.display(), e)),
+        };
+        toml::from_str(&content)
+            .map_err(|e| format!("Error: invalid settings in {}: {}", path.display(), e))
+    }
+
+    /// Save the settings to the config directory.
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = path() else {
+            return Err("Error: no config directory to save the settings in".to_owned());
+        };
+        let content = toml::to_string(self).map_err(|e| format!("Error: {}", e))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Error: could not create {}: {}", dir.display(), e))?;
+        }
+        fs::write(&path, content)
+            .map_err(|e| format!("Error: could not write {}: {}", path.display(), e))
+    }
+
+    /// Put the given file in front of the recently selected files and remember its directory.
+    pub fn add_recent_file(&mut self, file: &Path) {
+        self.recent_files.retain(|recent| recent != file);
+        self.recent_files.insert(0, file.to_owned());
+        self.recent_files.truncate(MAX_RECENT_FILES);
+        self.last_directory = file.parent().map(Path::to_owned);
+    }
+}
+
+/// The artifact pattern used if none is saved.
+fn default_artifact_pattern() -> String {
+    DEFAULT_PATTERN.to_owned()
+}
+
+/// The version section used if none is saved.
+fn default_version_section() -> String {
+    ".fw_version".to_owned()
+}
+
+/// The attempts used if none are saved, failures are not retried.
+fn default_attempts() -> u8 {
+    1
+}
+
+/// The timeout used if none is saved.
+fn default_timeout() -> u64 {
+    60
+}
+
+/// The time waited for the bootloader port if none is saved.
+fn default_touch_timeout() -> u64 {
+    touch::DEFAULT_TIMEOUT_SECS
+}
+
+/// The number of ports flashed at the same time if none is saved.
+fn default_flash_concurrency() -> usize {
+    4
+}
+
+/// The number of backups kept if none is saved.
+fn default_kept_backups() -> usize {
+    backup::DEFAULT_KEPT
+}
+
+/// The value of the flags which are enabled unless they were disabled.
+fn default_true() -> bool {
+    true
+}
+
+/// The path of the settings file, `None` if there is no config directory.
+fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("arduino_installer_gui").join(FILE_NAME))
+}
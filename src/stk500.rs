@@ -0,0 +1,397 @@
+//! Flashing the optiboot bootloader of ATmega328P boards over STK500v1 without avrdude
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    process::{ExitStatus, Output},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serialport::{ClearBuffer, SerialPort};
+
+use crate::{
+    hex::Block,
+    upload::{self, Log, WrongChip},
+};
+
+/// The answer starting every response of the bootloader.
+const STK_INSYNC: u8 = 0x14;
+/// The answer ending every successful response of the bootloader.
+const STK_OK: u8 = 0x10;
+/// The byte ending every command.
+const CRC_EOP: u8 = 0x20;
+/// Check that the bootloader is listening.
+const STK_GET_SYNC: u8 = 0x30;
+/// Start programming.
+const STK_ENTER_PROGMODE: u8 = 0x50;
+/// Stop programming, optiboot starts the program after it.
+const STK_LEAVE_PROGMODE: u8 = 0x51;
+/// Set the word address of the next page.
+const STK_LOAD_ADDRESS: u8 = 0x55;
+/// Write a page of the flash.
+const STK_PROG_PAGE: u8 = 0x64;
+/// Read a page of the flash.
+const STK_READ_PAGE: u8 = 0x74;
+/// Read the signature of the chip.
+const STK_READ_SIGN: u8 = 0x75;
+/// The memory type of the flash in page commands.
+const FLASH: u8 = b'F';
+
+/// The size of a flash page of the ATmega328P in bytes.
+const PAGE_SIZE: usize = 128;
+
+/// How often the sync is sent before the bootloader is considered missing, optiboot waits about a
+/// second after the reset.
+const SYNC_ATTEMPTS: usize = 10;
+
+/// How long is waited for each answer of the bootloader.
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The number of `#` of a complete progress bar, the same as avrdude prints.
+const BAR_WIDTH: usize = 50;
+
+/// Flash the given blocks to the ATmega328P with an optiboot bootloader on the given port and
+/// verify them. The progress is written to the log like avrdude prints it, so the progress bar
+/// and the summary work the same for both.
+pub fn flash(
+    port: &str,
+    baud: u32,
+    blocks: &[Block],
+    expected_signature: Option<[u8; 3]>,
+    log: &Log,
+) -> io::Result<Output> {
+    let pages = pages(blocks);
+    let bytes: usize = blocks.iter().map(|block| block.data.len()).sum();
+    let mut bootloader = Bootloader::open(port, baud)?;
+    log.push("Resetting the board through DTR...\n");
+    bootloader.reset()?;
+    bootloader.sync()?;
+
+    let signature = bootloader.signature()?;
+    log.push(&format!(
+        "Device signature = {:02X} {:02X} {:02X}\n",
+        signature[0], signature[1], signature[2]
+    ));
+    if let Some(expected) = expected_signature.filter(|&expected| expected != signature) {
+        return Err(WrongChip::error(format!(
+            "the chip reports the signature {:02X} {:02X} {:02X} but {:02X} {:02X} {:02X} is \
+            expected",
+            signature[0], signature[1], signature[2], expected[0], expected[1], expected[2]
+        )));
+    }
+
+    bootloader.command(&[STK_ENTER_PROGMODE], 0)?;
+    log.push(&format!("writing flash ({} bytes):\n", bytes));
+    progress(log, "Writing", &pages, |address, page| {
+        bootloader.load_address(address)?;
+        let mut command = vec![STK_PROG_PAGE];
+        command.extend_from_slice(&(page.len() as u16).to_be_bytes());
+        command.push(FLASH);
+        command.extend_from_slice(page);
+        bootloader.command(&command, 0).map(drop)
+    })?;
+    log.push(&format!("{} bytes of flash written\n", bytes));
+
+    log.push("verifying flash memory:\n");
+    progress(log, "Reading", &pages, |address, page| {
+        bootloader.load_address(address)?;
+        let mut command = vec![STK_READ_PAGE];
+        command.extend_from_slice(&(page.len() as u16).to_be_bytes());
+        command.push(FLASH);
+        let read = bootloader.command(&command, page.len())?;
+        match read
+            .iter()
+            .zip(page)
+            .position(|(read, written)| read != written)
+        {
+            Some(offset) => Err(io::Error::other(format!(
+                "verification error, content mismatch at byte 0x{:04X}: 0x{:02X} != 0x{:02X}",
+                address as usize + offset,
+                page[offset],
+                read[offset]
+            ))),
+            None => Ok(()),
+        }
+    })?;
+    log.push(&format!("{} bytes of flash verified\n", bytes));
+
+    bootloader.command(&[STK_LEAVE_PROGMODE], 0)?;
+    Ok(Output {
+        status: ExitStatus::default(),
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    })
+}
+
+/// The pages the given blocks touch by their byte address, bytes of a page which are not part of
+/// any block are left erased.
+fn pages(blocks: &[Block]) -> BTreeMap<u32, Vec<u8>> {
+    let mut pages: BTreeMap<u32, Vec<u8>> = BTreeMap::new();
+    for block in blocks {
+        for (address, &byte) in (block.address..).zip(&block.data) {
+            let start = address - address % PAGE_SIZE as u32;
+            let page = pages.entry(start).or_insert_with(|| vec![0xFF; PAGE_SIZE]);
+            page[(address - start) as usize] = byte;
+        }
+    }
+    pages
+}
+
+/// Run the given operation for each page and draw a progress bar with the given name into the
+/// log, a cancelled job stops before the next page.
+fn progress(
+    log: &Log,
+    name: &str,
+    pages: &BTreeMap<u32, Vec<u8>>,
+    mut operation: impl FnMut(u32, &[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    let started = Instant::now();
+    log.push(&format!("{} | ", name));
+    let mut drawn = 0;
+    for (i, (&address, page)) in pages.iter().enumerate() {
+        if log.is_cancelled() {
+            log.push("\n");
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
+        if let Err(e) = operation(address, page) {
+            log.push("\n");
+            return Err(e);
+        }
+        let done = (i + 1) * BAR_WIDTH / pages.len();
+        log.push(&"#".repeat(done - drawn));
+        drawn = done;
+    }
+    log.push(&format!(
+        "{} | 100% {:.2}s\n",
+        "#".repeat(BAR_WIDTH - drawn),
+        started.elapsed().as_secs_f32()
+    ));
+    Ok(())
+}
+
+/// The serial connection to a bootloader speaking STK500v1, over any port in tests
+struct Bootloader<P = Box<dyn SerialPort>> {
+    /// The name of the port, used in the errors.
+    name: String,
+    /// The open port.
+    port: P,
+}
+
+impl Bootloader {
+    /// Open the given port with the given baud rate.
+    fn open(name: &str, baud: u32) -> io::Result<Self> {
+        let port = serialport::new(name, baud)
+            .timeout(READ_TIMEOUT)
+            .open()
+            .map_err(|e| upload::serial_open_error(name, "to flash the board", e))?;
+        Ok(Self {
+            name: name.to_owned(),
+            port,
+        })
+    }
+
+    /// Reset the board through DTR and RTS like the auto reset circuit of an Arduino expects, so
+    /// the bootloader is started.
+    fn reset(&mut self) -> io::Result<()> {
+        let mut set_lines = |level: bool| -> serialport::Result<()> {
+            self.port.write_data_terminal_ready(level)?;
+            self.port.write_request_to_send(level)
+        };
+        set_lines(false)
+            .and_then(|()| {
+                thread::sleep(Duration::from_millis(250));
+                set_lines(true)
+            })
+            .map_err(|e| {
+                io::Error::other(format!("Could not toggle DTR/RTS of {}: {}", self.name, e))
+            })?;
+        thread::sleep(Duration::from_millis(50));
+        Ok(())
+    }
+
+    /// Send the sync until the bootloader answers it.
+    fn sync(&mut self) -> io::Result<()> {
+        for _ in 0..SYNC_ATTEMPTS {
+            // Whatever the program printed before the reset is still in the buffer.
+            self.port.clear(ClearBuffer::Input)?;
+            if self.command(&[STK_GET_SYNC], 0).is_ok() {
+                return Ok(());
+            }
+        }
+        Err(io::Error::other(format!(
+            "the bootloader on {} is not in sync, check that the board is an Uno or a Nano with \
+            optiboot and that the baud rate is right",
+            self.name
+        )))
+    }
+}
+
+impl<P: Read + Write> Bootloader<P> {
+    /// Read the signature of the chip.
+    fn signature(&mut self) -> io::Result<[u8; 3]> {
+        let signature = self.command(&[STK_READ_SIGN], 3)?;
+        Ok([signature[0], signature[1], signature[2]])
+    }
+
+    /// Set the address of the next page command to the given byte address.
+    fn load_address(&mut self, address: u32) -> io::Result<()> {
+        // The bootloader counts in words of two bytes.
+        let word = ((address / 2) as u16).to_le_bytes();
+        self.command(&[STK_LOAD_ADDRESS, word[0], word[1]], 0)
+            .map(drop)
+    }
+
+    /// Send the given command and return the given number of bytes of the answer between the sync
+    /// and the ok.
+    fn command(&mut self, command: &[u8], answer_len: usize) -> io::Result<Vec<u8>> {
+        self.port.write_all(command)?;
+        self.port.write_all(&[CRC_EOP])?;
+        self.port.flush()?;
+
+        let mut answer = vec![0; answer_len + 2];
+        self.port
+            .read_exact(&mut answer)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::TimedOut => io::Error::new(
+                    e.kind(),
+                    format!("the bootloader on {} is not responding", self.name),
+                ),
+                _ => e,
+            })?;
+        if answer[0] != STK_INSYNC || answer[answer_len + 1] != STK_OK {
+            return Err(io::Error::other(format!(
+                "protocol error, the bootloader on {} answered 0x{:02X} ... 0x{:02X}",
+                self.name,
+                answer[0],
+                answer[answer_len + 1]
+            )));
+        }
+        answer.pop();
+        answer.remove(0);
+        Ok(answer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A port which answers with the given bytes and times out once they are read
+    struct MockPort {
+        /// Everything written to the port.
+        written: Vec<u8>,
+        /// The answers of the simulated bootloader.
+        answers: io::Cursor<Vec<u8>>,
+    }
+
+    impl Read for MockPort {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.answers.read(buf)? {
+                0 => Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")),
+                read => Ok(read),
+            }
+        }
+    }
+
+    impl Write for MockPort {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A connection to a bootloader which answers with the given bytes.
+    fn bootloader(answers: &[u8]) -> Bootloader<MockPort> {
+        Bootloader {
+            name: "COM3".to_owned(),
+            port: MockPort {
+                written: Vec::new(),
+                answers: io::Cursor::new(answers.to_vec()),
+            },
+        }
+    }
+
+    /// A block of the given data at the given address.
+    fn block(address: u32, data: &[u8]) -> Block {
+        Block {
+            address,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn aligned_page() {
+        let pages = pages(&[block(0x80, &[1; PAGE_SIZE])]);
+        assert_eq!(pages.keys().copied().collect::<Vec<_>>(), [0x80]);
+        assert_eq!(pages[&0x80], [1; PAGE_SIZE]);
+    }
+
+    #[test]
+    fn unaligned_block_spans_two_pages() {
+        let pages = pages(&[block(0x7E, &[1, 2, 3, 4])]);
+        assert_eq!(pages.keys().copied().collect::<Vec<_>>(), [0, 0x80]);
+        assert!(pages[&0][..0x7E].iter().all(|&byte| byte == 0xFF));
+        assert_eq!(pages[&0][0x7E..], [1, 2]);
+        assert_eq!(pages[&0x80][..3], [3, 4, 0xFF]);
+    }
+
+    #[test]
+    fn gaps_are_left_erased() {
+        let pages = pages(&[
+            block(0x10, &[0xAA]),
+            block(0x14, &[0xBB]),
+            block(0x300, &[0xCC]),
+        ]);
+        assert_eq!(pages.keys().copied().collect::<Vec<_>>(), [0, 0x300]);
+        assert_eq!(pages[&0][0x10..0x15], [0xAA, 0xFF, 0xFF, 0xFF, 0xBB]);
+        assert_eq!(pages[&0x300][..2], [0xCC, 0xFF]);
+    }
+
+    #[test]
+    fn command_framing() {
+        let mut bootloader = bootloader(&[STK_INSYNC, 0x1E, 0x95, 0x0F, STK_OK]);
+        assert_eq!(bootloader.signature().unwrap(), [0x1E, 0x95, 0x0F]);
+        assert_eq!(bootloader.port.written, [STK_READ_SIGN, CRC_EOP]);
+    }
+
+    #[test]
+    fn addresses_are_sent_in_words() {
+        let mut bootloader = bootloader(&[STK_INSYNC, STK_OK]);
+        bootloader.load_address(0x7E00).unwrap();
+        assert_eq!(
+            bootloader.port.written,
+            [STK_LOAD_ADDRESS, 0x00, 0x3F, CRC_EOP]
+        );
+    }
+
+    #[test]
+    fn answer_out_of_sync() {
+        let e = bootloader(&[0x00, STK_OK])
+            .command(&[STK_GET_SYNC], 0)
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "protocol error, the bootloader on COM3 answered 0x00 ... 0x10"
+        );
+        let e = bootloader(&[STK_INSYNC, 0x11])
+            .command(&[STK_GET_SYNC], 0)
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "protocol error, the bootloader on COM3 answered 0x14 ... 0x11"
+        );
+    }
+
+    #[test]
+    fn bootloader_not_responding() {
+        let e = bootloader(&[STK_INSYNC])
+            .command(&[STK_ENTER_PROGMODE], 0)
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(e.to_string(), "the bootloader on COM3 is not responding");
+    }
+}
@@ -0,0 +1,272 @@
+//! The facts about a finished avrdude run parsed from its output
+
+use std::fmt;
+
+use crate::{group_digits, upload};
+
+/// What avrdude reported about a run, each fact is `None` if it was not found in the output
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Summary {
+    /// The operation running when avrdude reported the first error.
+    pub failed: Option<Operation>,
+    /// The number of bytes written to the first written memory.
+    pub written: Option<u64>,
+    /// The number of bytes verified of the first verified memory.
+    pub verified: Option<u64>,
+    /// The signature the chip reported.
+    pub signature: Option<[u8; 3]>,
+    /// The seconds all reads and writes took as shown by the progress bars.
+    pub seconds: Option<f32>,
+}
+
+impl Summary {
+    /// Parse the facts from the output of avrdude 6 and 7, which both report the bytes like
+    /// "31466 bytes of flash written" but print the time of a bar as "100% 5.23s" or "100% 5.23 s".
+    pub fn parse(output: &str) -> Self {
+        let seconds: Vec<f32> = output.lines().filter_map(bar_seconds).collect();
+        Self {
+            failed: failed_operation(output),
+            written: memory_bytes(output, "written"),
+            verified: memory_bytes(output, "verified"),
+            signature: upload::device_signature(output),
+            seconds: (!seconds.is_empty()).then(|| seconds.iter().sum()),
+        }
+    }
+
+    /// A compact line like "31 466 bytes written and verified in 6.2 s", `None` if nothing was
+    /// recognized.
+    pub fn describe(&self) -> Option<String> {
+        let bytes = match (self.written, self.verified) {
+            (Some(written), Some(verified)) if written == verified => Some(format!(
+                "{} bytes written and verified",
+                group_digits(written)
+            )),
+            (Some(written), Some(verified)) => Some(format!(
+                "{} bytes written, {} bytes verified",
+                group_digits(written),
+                group_digits(verified)
+            )),
+            (Some(written), None) => Some(format!("{} bytes written", group_digits(written))),
+            (None, Some(verified)) => Some(format!("{} bytes verified", group_digits(verified))),
+            (None, None) => None,
+        }
+        .map(|bytes| match self.seconds {
+            Some(seconds) => format!("{} in {:.1} s", bytes, seconds),
+            None => bytes,
+        });
+        let signature = self.signature.map(|signature| {
+            format!(
+                "device signature {:02X} {:02X} {:02X}",
+                signature[0], signature[1], signature[2]
+            )
+        });
+
+        match (bytes, signature) {
+            (Some(bytes), Some(signature)) => Some(format!("{}, {}", bytes, signature)),
+            (line, None) | (None, line) => line,
+        }
+    }
+}
+
+/// The memories avrdude names when it starts to write, read or verify one of them.
+const MEMORIES: &[&str] = &["flash", "eeprom", "lfuse", "hfuse", "efuse", "fuse", "lock"];
+
+/// A memory operation of an avrdude run, like writing the flash
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Operation {
+    /// What was done, "writing", "reading" or "verifying".
+    pub action: &'static str,
+    /// The memory it was done to, like "flash" or "lfuse".
+    pub memory: &'static str,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} the {}", self.action, self.memory)
+    }
+}
+
+/// The operation which was running when the first error was reported, `None` if no operation was
+/// running, for example when the programmer did not answer at all.
+fn failed_operation(output: &str) -> Option<Operation> {
+    let mut current: Option<Operation> = None;
+    for line in output.lines() {
+        let line = line.trim().to_lowercase();
+        let message = line
+            .strip_prefix("avrdude:")
+            .or_else(|| line.strip_prefix("avrdude error:"))
+            .unwrap_or(&line)
+            .trim_start();
+        let action = ["writing", "reading", "verifying"]
+            .into_iter()
+            .find(|action| message.starts_with(action));
+        let memory = message
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .find_map(|word| MEMORIES.iter().find(|memory| **memory == word));
+        if let (Some(action), Some(memory)) = (action, memory) {
+            // Reading the chip back is part of verifying a memory.
+            let verifying = current.is_some_and(|op| {
+                op.action == "verifying" && op.memory == *memory && action == "reading"
+            });
+            if !verifying {
+                current = Some(Operation { action, memory });
+            }
+        } else if line.contains("error") || line.contains("mismatch") || line.contains("failed") {
+            return current;
+        }
+    }
+    None
+}
+
+/// The number of bytes of the first line like "31466 bytes of flash written" with the given verb.
+fn memory_bytes(output: &str, verb: &str) -> Option<u64> {
+    output.lines().find_map(|line| {
+        let (count, memory) = line.split_once(" bytes of ")?;
+        memory.trim_end().strip_suffix(verb)?;
+        let start = count
+            .rfind(|c: char| !c.is_ascii_digit())
+            .map_or(0, |i| i + 1);
+        count[start..].parse().ok()
+    })
+}
+
+/// The seconds at the end of a progress bar like "Writing | ### | 100% 5.23s".
+fn bar_seconds(line: &str) -> Option<f32> {
+    if !line.contains(" | ") {
+        return None;
+    }
+    let after = line[line.rfind('%')? + 1..].trim();
+    let number = after.strip_suffix('s')?.trim_end();
+    number.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The output of avrdude 6.3 flashing and verifying an Uno.
+    const AVRDUDE_6: &str = "\
+avrdude: AVR device initialized and ready to accept instructions
+
+Reading | ################################################## | 100% 0.00s
+
+avrdude: Device signature = 0x1e950f (probably m328p)
+avrdude: reading input file \"blink.hex\"
+avrdude: writing flash (31466 bytes):
+
+Writing | ################################################## | 100% 5.23s
+
+avrdude: 31466 bytes of flash written
+avrdude: verifying flash memory against blink.hex:
+avrdude: load data flash data from input file blink.hex:
+avrdude: input file blink.hex contains 31466 bytes
+avrdude: reading on-chip flash data:
+
+Reading | ################################################## | 100% 4.02s
+
+avrdude: verifying ...
+avrdude: 31466 bytes of flash verified
+
+avrdude: safemode: Fuses OK (E:00, H:00, L:00)
+
+avrdude done.  Thank you.
+";
+
+    /// The output of avrdude 7.2 flashing and verifying an Uno.
+    const AVRDUDE_7: &str = "\
+avrdude: AVR device initialized and ready to accept instructions
+avrdude: device signature = 0x1e950f (probably m328p)
+avrdude: Note: flash memory has been specified, an erase cycle will be performed.
+         To disable this feature, specify the -D option.
+avrdude: erasing chip
+avrdude: reading input file blink.hex for flash
+         with 924 bytes in 1 section within [0, 0x39b]
+         using 8 pages and 100 pad bytes
+avrdude: writing 924 bytes flash ...
+
+Writing | ################################################## | 100% 0.15 s
+
+avrdude: 924 bytes of flash written
+avrdude: verifying flash memory against blink.hex
+
+Reading | ################################################## | 100% 0.12 s
+
+avrdude: 924 bytes of flash verified
+
+avrdude done.  Thank you.
+";
+
+    /// The output of avrdude 6.3 when the flash does not match after writing it.
+    const AVRDUDE_6_MISMATCH: &str = "\
+avrdude: Device signature = 0x1e950f (probably m328p)
+avrdude: writing flash (924 bytes):
+
+Writing | ################################################## | 100% 0.17s
+
+avrdude: 924 bytes of flash written
+avrdude: verifying flash memory against blink.hex:
+avrdude: load data flash data from input file blink.hex:
+avrdude: input file blink.hex contains 924 bytes
+avrdude: reading on-chip flash data:
+
+Reading | ################################################## | 100% 0.13s
+
+avrdude: verifying ...
+avrdude: verification error, first mismatch at byte 0x0000
+         0x0c != 0xff
+avrdude: verification error; content mismatch
+
+avrdude done.  Thank you.
+";
+
+    #[test]
+    fn avrdude_6() {
+        let summary = Summary::parse(AVRDUDE_6);
+        assert_eq!(
+            summary,
+            Summary {
+                failed: None,
+                written: Some(31466),
+                verified: Some(31466),
+                signature: Some([0x1e, 0x95, 0x0f]),
+                seconds: Some(9.25),
+            }
+        );
+        assert_eq!(
+            summary.describe().as_deref(),
+            Some("31 466 bytes written and verified in 9.2 s, device signature 1E 95 0F")
+        );
+    }
+
+    #[test]
+    fn avrdude_7() {
+        let summary = Summary::parse(AVRDUDE_7);
+        assert_eq!(summary.failed, None);
+        assert_eq!(summary.written, Some(924));
+        assert_eq!(summary.verified, Some(924));
+        assert_eq!(summary.signature, Some([0x1e, 0x95, 0x0f]));
+        assert!((summary.seconds.unwrap() - 0.27).abs() < 1e-6);
+    }
+
+    #[test]
+    fn verification_error() {
+        let summary = Summary::parse(AVRDUDE_6_MISMATCH);
+        assert_eq!(
+            summary.failed,
+            Some(Operation {
+                action: "verifying",
+                memory: "flash"
+            })
+        );
+        assert_eq!(summary.written, Some(924));
+        assert_eq!(summary.verified, None);
+        assert_eq!(summary.failed.unwrap().to_string(), "verifying the flash");
+    }
+
+    #[test]
+    fn nothing_recognized() {
+        let summary = Summary::parse("avrdude: ser_open(): can't open device \"/dev/ttyUSB0\"\n");
+        assert_eq!(summary, Summary::default());
+        assert_eq!(summary.describe(), None);
+    }
+}
@@ -0,0 +1,100 @@
+//! The 1200 baud touch which resets native usb boards into their bootloader
+
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+use serialport::{SerialPortInfo, SerialPortType};
+
+use crate::upload::{self, Log};
+
+/// How many seconds are waited for the bootloader port by default.
+pub const DEFAULT_TIMEOUT_SECS: u64 = 5;
+
+/// How often the ports are listed while waiting for the bootloader port.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long the touched port may stay connected before the board is taken to not drop off the
+/// bus, like the programming port of the Due.
+const STAY_TIME: Duration = Duration::from_secs(1);
+
+/// Open and close the given port at 1200 baud, which makes native usb boards erase themselves and
+/// reset into their bootloader, and wait at most the given time for the bootloader port.
+///
+/// Returns the port of the bootloader, which may differ from the given port because the board
+/// re-enumerates after the reset. The given port is returned if no bootloader port appeared.
+pub fn touch(port: &str, timeout: Duration, log: &Log) -> io::Result<String> {
+    let mut before = serialport::available_ports()?;
+    let serial = before
+        .iter()
+        .find(|p| p.port_name == port)
+        .and_then(usb_serial)
+        .map(str::to_owned);
+    log.push(&format!(
+        "Touching {} at 1200 baud to start the bootloader\n",
+        port
+    ));
+    let touched = serialport::new(port, 1200)
+        .timeout(Duration::from_millis(100))
+        .open()
+        .map_err(|e| upload::serial_open_error(port, "for the 1200 baud touch", e))?;
+    drop(touched);
+
+    log.push("Waiting for the bootloader port...\n");
+    let started = Instant::now();
+    let mut vanished = false;
+    while started.elapsed() < timeout {
+        if log.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
+        thread::sleep(POLL_INTERVAL);
+        let now = serialport::available_ports()?;
+        if let Some(new) = appeared(&before, &now, serial.as_deref()) {
+            log.push(&format!("The bootloader appeared on {}\n", new));
+            return Ok(new.to_owned());
+        }
+
+        if now.iter().all(|p| p.port_name != port) {
+            // The port may come back with the same name.
+            before.retain(|p| p.port_name != port);
+            vanished = true;
+        } else if !vanished && started.elapsed() >= STAY_TIME {
+            log.push(&format!(
+                "{} stayed connected, the board is flashed on it\n",
+                port
+            ));
+            return Ok(port.to_owned());
+        }
+    }
+
+    log.push(&format!(
+        "No bootloader port appeared within {} s, trying {} anyway\n",
+        timeout.as_secs(),
+        port
+    ));
+    Ok(port.to_owned())
+}
+
+/// The usb serial number of the given port, `None` if it is not a usb port or has none.
+fn usb_serial(port: &SerialPortInfo) -> Option<&str> {
+    match port.port_type {
+        SerialPortType::UsbPort(ref usb) => usb.serial_number.as_deref(),
+        _ => None,
+    }
+}
+
+/// The name of a port which is listed now but was not before, the one with the given usb serial
+/// number is preferred because it is the touched board.
+fn appeared<'a>(
+    before: &[SerialPortInfo],
+    now: &'a [SerialPortInfo],
+    serial: Option<&str>,
+) -> Option<&'a str> {
+    let mut new = now
+        .iter()
+        .filter(|p| before.iter().all(|b| b.port_name != p.port_name));
+    let first = new.clone().next()?;
+    let same_board = serial.and_then(|serial| new.find(|p| usb_serial(p) == Some(serial)));
+    Some(same_board.unwrap_or(first).port_name.as_str())
+}
@@ -0,0 +1,1718 @@
+//! The external programs used to install a program to a board
+
+use std::{
+    env,
+    ffi::OsStr,
+    fmt, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::{self, Command, ExitStatus, Output, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    avrdude, backup,
+    board::{BoardSpec, Chip, Uploader},
+    bootloader::{Bootloader, UNLOCK_BITS},
+    firmware::{self, FirmwareFormat},
+    fuses::FuseLayout,
+    stk500, touch,
+};
+
+/// The executable used to convert ELF files to Intel HEX.
+const OBJCOPY: &str = "avr-objcopy";
+
+/// Counts the ELF files converted by this process, so the jobs of a batch running at the same
+/// time never share a converted file.
+static CONVERTED: AtomicU32 = AtomicU32::new(0);
+
+/// How often a running command is checked for having exited or being cancelled.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The slowest transfer rate of a bootloader or programmer in bytes per second, the timeout is
+/// extended by the time the data of a job takes at this rate.
+const SLOWEST_RATE: u64 = 2048;
+
+/// How long is waited before a failed attempt is retried, so the bootloader can restart.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// The messages of avrdude failures which often go away when avrdude is run again.
+const TRANSIENT_FAILURES: [&str; 5] = [
+    "not in sync",
+    "not responding",
+    "resource busy",
+    "Access is denied",
+    "protocol error",
+];
+
+/// The slower bit clocks tried in this order when the chip answered an ISP programmer with a blank
+/// signature, like fresh chips running at 1 MHz do when the programmer is too fast for them.
+const SLOW_BITCLOCKS: [&str; 2] = ["10", "32"];
+
+/// How long is waited after a reset pulse, so the bootloader is started before avrdude connects.
+const RESET_SETTLE: Duration = Duration::from_millis(250);
+
+/// A reset of the board through the DTR and RTS lines of its serial port, like the auto reset
+/// circuit of an Arduino does
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResetPulse {
+    /// Wether the pulse is sent before avrdude is run.
+    pub enabled: bool,
+    /// Wether RTS is pulsed together with DTR.
+    pub rts: bool,
+    /// How long the lines are held in milliseconds.
+    pub width_ms: u64,
+    /// Wether the lines are released instead of asserted during the pulse.
+    pub inverted: bool,
+}
+
+impl Default for ResetPulse {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rts: false,
+            width_ms: 100,
+            inverted: false,
+        }
+    }
+}
+
+/// The placeholders replaced in the command line of a hook.
+pub const HOOK_PLACEHOLDERS: [&str; 3] = ["{port}", "{file}", "{mcu}"];
+
+/// A command run before or after flashing, like a relay board switching on the power of the board
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hook {
+    /// Wether the command is run.
+    pub enabled: bool,
+    /// The command line, split like a shell does, with placeholders for the port, the file and the
+    /// mcu of the flash.
+    pub command: String,
+    /// The directory the command runs in, empty for the working directory of the installer.
+    pub working_dir: String,
+    /// The seconds the command may run before it is killed, 0 to wait forever.
+    pub timeout_secs: u64,
+}
+
+impl Default for Hook {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: String::new(),
+            working_dir: String::new(),
+            timeout_secs: 30,
+        }
+    }
+}
+
+impl Hook {
+    /// Wether the hook is run, an enabled hook without a command does nothing.
+    pub fn is_active(&self) -> bool {
+        self.enabled && !self.command.trim().is_empty()
+    }
+
+    /// Check that the command line can be split and the working directory exists.
+    pub fn validate(&self) -> Result<(), String> {
+        avrdude::split_args(&self.command)?;
+        let dir = self.working_dir.trim();
+        if !dir.is_empty() && !Path::new(dir).is_dir() {
+            return Err(format!(
+                "Error: the working directory {} does not exist",
+                dir
+            ));
+        }
+        Ok(())
+    }
+
+    /// Build the command with the placeholders replaced by the given port, file and mcu.
+    fn build(&self, port: &str, file: &Path, mcu: &str) -> io::Result<Command> {
+        let file = file.to_string_lossy();
+        let values = [port, &file, mcu];
+        let mut args = avrdude::split_args(&self.command)
+            .map_err(io::Error::other)?
+            .into_iter()
+            .map(|arg| {
+                HOOK_PLACEHOLDERS
+                    .iter()
+                    .zip(values)
+                    .fold(arg, |arg, (placeholder, value)| {
+                        arg.replace(placeholder, value)
+                    })
+            });
+        let program = args
+            .next()
+            .ok_or_else(|| io::Error::other("the command is empty"))?;
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+        let dir = self.working_dir.trim();
+        if !dir.is_empty() {
+            cmd.current_dir(dir);
+        }
+        Ok(cmd)
+    }
+}
+
+/// Enumeration of the things a job can do
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JobKind {
+    /// Install the program to the board
+    Flash,
+    /// Write the fuses and then burn the program as the bootloader through an ISP programmer
+    BurnBootloader(Bootloader),
+    /// Compare the flash of the board with the program without writing anything
+    Verify,
+    /// Save the content of the flash of the board to the program file
+    ReadFlash,
+    /// Save the content of the EEPROM of the board to the program file
+    ReadEeprom,
+    /// Write the program file to the EEPROM of the board without erasing the chip
+    WriteEeprom,
+    /// Print the fuse bytes of the board, nothing is written
+    ReadFuses(FuseLayout),
+    /// Write the given fuse values, in the order of the fuses of the layout
+    WriteFuses(FuseLayout, [u8; 3]),
+    /// Erase the flash of the chip without writing anything
+    Erase,
+    /// Talk to the chip with writing disabled, to check the connection
+    TestCommunication,
+}
+
+impl JobKind {
+    /// The name of the job shown in its result.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Flash => "Flash",
+            Self::BurnBootloader(_) => "Bootloader burn",
+            Self::Verify => "Verification",
+            Self::ReadFlash => "Flash read",
+            Self::ReadEeprom => "EEPROM read",
+            Self::WriteEeprom => "EEPROM write",
+            Self::ReadFuses(layout) if layout.is_lock() => "Lock bit read",
+            Self::ReadFuses(_) => "Fuse read",
+            Self::WriteFuses(layout, _) if layout.is_lock() => "Lock bit write",
+            Self::WriteFuses(..) => "Fuse write",
+            Self::Erase => "Chip erase",
+            Self::TestCommunication => "Communication test",
+        }
+    }
+
+    /// Wether the job writes to the chip.
+    pub fn writes(&self) -> bool {
+        matches!(
+            self,
+            Self::Flash
+                | Self::BurnBootloader(_)
+                | Self::WriteEeprom
+                | Self::WriteFuses(..)
+                | Self::Erase
+        )
+    }
+
+    /// The avrdude name of the memory the job saves to the program file, `None` if it reads
+    /// nothing.
+    pub fn read_memory(&self) -> Option<&'static str> {
+        match self {
+            Self::ReadFlash => Some("flash"),
+            Self::ReadEeprom => Some("eeprom"),
+            _ => None,
+        }
+    }
+}
+
+/// Everything required to run an upload.
+pub struct UploadJob {
+    /// What the job does.
+    pub kind: JobKind,
+    /// The spec of the board the program is installed to.
+    pub spec: BoardSpec,
+    /// The port the board is connected to, `None` if the uploader finds the board on its own.
+    pub port: Option<String>,
+    /// The program which should be installed.
+    pub program: PathBuf,
+    /// The format of the program.
+    pub format: FirmwareFormat,
+    /// The data written to the EEPROM, `None` to leave the EEPROM untouched.
+    pub eeprom: Option<MemoryFile>,
+    /// The fuses written in the same avrdude run as the program, `None` to keep them.
+    pub fuses: Option<(FuseLayout, [u8; 3])>,
+    /// The lock bits written after the program was flashed and verified, `None` to keep them.
+    pub lock: Option<u8>,
+    /// The file the flash is read to before it is overwritten and the number of backups of the
+    /// port which are kept, `None` to overwrite the flash without a backup.
+    pub backup: Option<(PathBuf, usize)>,
+    /// The command run before the program is flashed, the flash is aborted if it fails.
+    pub before_flash: Option<Hook>,
+    /// The command run after the program was flashed successfully.
+    pub after_flash: Option<Hook>,
+    /// The signature the chip is checked for before flashing, `None` to flash without checking.
+    pub signature_check: Option<SignatureCheck>,
+    /// The device updated over the air, `None` if the board is not flashed over the network.
+    pub ota: Option<OtaTarget>,
+}
+
+/// A file written to a memory of the chip.
+#[derive(Debug, Clone)]
+pub struct MemoryFile {
+    /// The file which is written.
+    pub path: PathBuf,
+    /// The format of the file.
+    pub format: FirmwareFormat,
+}
+
+/// A device running an over the air update service.
+#[derive(Debug, Clone)]
+pub struct OtaTarget {
+    /// The ip address or host name of the device.
+    pub host: String,
+    /// The port of the update service.
+    pub port: u16,
+    /// The password of the update service, empty if none is set.
+    pub password: String,
+}
+
+/// The chip a board is expected to have
+pub struct SignatureCheck {
+    /// The signature avrdude has to read from the chip.
+    pub expected: [u8; 3],
+    /// The name of the selected board, shown in the error.
+    pub board: String,
+}
+
+impl UploadJob {
+    /// A job of the given kind with the given program, without EEPROM file, fuses, lock bits,
+    /// backup, hooks, signature check or over the air target.
+    pub fn new(
+        kind: JobKind,
+        spec: BoardSpec,
+        port: Option<String>,
+        program: PathBuf,
+        format: FirmwareFormat,
+    ) -> Self {
+        Self {
+            kind,
+            spec,
+            port,
+            program,
+            format,
+            eeprom: None,
+            fuses: None,
+            lock: None,
+            backup: None,
+            before_flash: None,
+            after_flash: None,
+            signature_check: None,
+            ota: None,
+        }
+    }
+
+    /// Wether the job writes the flash without verifying it afterwards.
+    pub fn skips_verification(&self) -> bool {
+        self.kind == JobKind::Flash && self.spec.extra_args.iter().any(|arg| arg == "-V")
+    }
+
+    /// Build the commands of the job with the name of their stage, empty if the upload is done
+    /// without an external program.
+    pub fn commands(&self) -> Vec<(&'static str, Command)> {
+        match self.kind {
+            JobKind::Flash => {
+                // A separate run before the flash, which is only written if the read succeeded.
+                let backup = self.backup.as_ref().map(|(path, _)| {
+                    let port = self.port.as_deref().unwrap_or_default();
+                    let cmd =
+                        avrdude_read(&self.spec, port, "flash", path, FirmwareFormat::IntelHex);
+                    ("Backing up the flash", cmd)
+                });
+                let flash = self.command().map(|cmd| ("Flashing", cmd));
+                let mut commands: Vec<_> = backup.into_iter().chain(flash).collect();
+                // A separate run, so the lock bits are only written if avrdude verified the flash.
+                if let (Some(lock), Ok(layout)) = (self.lock, FuseLayout::lock(&self.spec.partno)) {
+                    commands.push((
+                        "Writing the lock bits",
+                        avrdude_write_fuses(
+                            &self.spec,
+                            self.port.as_deref().unwrap_or_default(),
+                            &layout,
+                            [lock, 0xFF, 0xFF],
+                        ),
+                    ));
+                }
+                commands
+            }
+            JobKind::BurnBootloader(ref bootloader) => {
+                let port = self.port.as_deref().unwrap_or_default();
+                vec![
+                    (
+                        "Writing the fuses",
+                        avrdude_fuses(&self.spec, bootloader, port),
+                    ),
+                    (
+                        "Burning the bootloader",
+                        avrdude_bootloader(&self.spec, bootloader, port, &self.program),
+                    ),
+                ]
+            }
+            JobKind::ReadFlash => vec![(
+                "Reading the flash",
+                avrdude_read(
+                    &self.spec,
+                    self.port.as_deref().unwrap_or_default(),
+                    "flash",
+                    &partial_path(&self.program),
+                    self.format,
+                ),
+            )],
+            JobKind::ReadEeprom => vec![(
+                "Reading the EEPROM",
+                avrdude_read(
+                    &self.spec,
+                    self.port.as_deref().unwrap_or_default(),
+                    "eeprom",
+                    &partial_path(&self.program),
+                    self.format,
+                ),
+            )],
+            JobKind::WriteEeprom => vec![(
+                "Writing the EEPROM",
+                avrdude_write_eeprom(
+                    &self.spec,
+                    self.port.as_deref().unwrap_or_default(),
+                    &self.program,
+                    self.format,
+                ),
+            )],
+            JobKind::ReadFuses(ref layout) => vec![(
+                "Reading the fuses",
+                avrdude_read_fuses(&self.spec, self.port.as_deref().unwrap_or_default(), layout),
+            )],
+            JobKind::WriteFuses(ref layout, values) => vec![(
+                "Writing the fuses",
+                avrdude_write_fuses(
+                    &self.spec,
+                    self.port.as_deref().unwrap_or_default(),
+                    layout,
+                    values,
+                ),
+            )],
+            JobKind::Erase => vec![(
+                "Erasing the chip",
+                avrdude_erase(&self.spec, self.port.as_deref().unwrap_or_default()),
+            )],
+            JobKind::TestCommunication => {
+                let port = self.port.as_deref().unwrap_or_default();
+                let mut cmd = if self.program.as_os_str().is_empty() {
+                    avrdude_base(&self.spec, port)
+                } else {
+                    avrdude(&self.spec, port, &self.program, self.format, None, None)
+                };
+                // avrdude does everything except writing to the chip.
+                cmd.arg("-n");
+                vec![("Testing the communication", cmd)]
+            }
+            JobKind::Verify => vec![(
+                "Verifying",
+                avrdude_verify(
+                    &self.spec,
+                    self.port.as_deref().unwrap_or_default(),
+                    &self.program,
+                    self.format,
+                ),
+            )],
+        }
+    }
+
+    /// Build the command running the uploader of the job, `None` if the upload is done without
+    /// an external program.
+    fn command(&self) -> Option<Command> {
+        let port = self.port.as_deref().unwrap_or_default();
+        let cmd = match self.spec.uploader {
+            Uploader::Avrdude => avrdude(
+                &self.spec,
+                port,
+                &self.program,
+                self.format,
+                self.eeprom.as_ref(),
+                self.fuses.as_ref(),
+            ),
+            Uploader::Bossac => bossac(&self.spec, port, &self.program),
+            Uploader::Micronucleus => micronucleus(&self.program),
+            Uploader::Esptool => esptool(&self.spec, port, &self.program),
+            Uploader::Espota => espota(self.ota.as_ref()?, &self.program),
+            Uploader::TeensyLoader => teensy_loader(&self.spec, &self.program),
+            Uploader::Stm32flash => stm32flash(port, &self.program),
+            Uploader::DfuUtil => dfu_util(&self.spec, &self.program),
+            Uploader::Picotool => picotool(&self.program),
+            Uploader::Stk500 | Uploader::Uf2Copy => return None,
+        };
+        Some(cmd)
+    }
+
+    /// Describe what the job does, shown to the user.
+    pub fn describe(&self) -> String {
+        let lines = self.command_lines();
+        if lines.is_empty() {
+            let action = match self.spec.uploader {
+                Uploader::Stk500 => "STK500",
+                _ => "COPY",
+            };
+            return format!(
+                "{}: {} to {}",
+                action,
+                self.program.display(),
+                self.port.as_deref().unwrap_or_default()
+            );
+        }
+        lines
+            .iter()
+            .map(|line| format!("CMD: {}", line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The commands of the job as they would be typed into a shell, empty if no command is run.
+    pub fn command_lines(&self) -> Vec<String> {
+        let commands = self.commands();
+        if commands.is_empty() {
+            return Vec::new();
+        }
+        let port = self.port.as_deref().unwrap_or_default();
+        let hook_line = |hook: &Option<Hook>| {
+            let cmd = hook
+                .as_ref()?
+                .build(port, &self.program, &self.spec.partno)
+                .ok()?;
+            Some(command_line(&cmd))
+        };
+        hook_line(&self.before_flash)
+            .into_iter()
+            .chain(commands.iter().map(|(_, cmd)| command_line(cmd)))
+            .chain(hook_line(&self.after_flash))
+            .collect()
+    }
+
+    /// Run the job to completion.
+    fn run(mut self, log: &Log) -> io::Result<Output> {
+        // The hooks get the port of the program, not the one of the bootloader after a touch.
+        let port = self.port.clone().unwrap_or_default();
+        if let Some(ref hook) = self.before_flash {
+            self.run_hook("before flashing", hook, &port, log)?;
+        }
+        let res = self.run_attempts(log);
+        if let Some((path, keep)) = self.backup.as_ref().filter(|(path, _)| path.is_file()) {
+            log.push(&format!("The flash was backed up to {}\n", path.display()));
+            if let Err(e) = backup::prune(path, &port, *keep) {
+                log.push(&format!("Old backups could not be removed: {}\n", e));
+            }
+        }
+        if let (Some(ref hook), Ok(ref out)) = (&self.after_flash, &res) {
+            if out.status.success() {
+                self.run_hook("after flashing", hook, &port, log)
+                    .map_err(|e| {
+                        io::Error::new(e.kind(), format!("the program was flashed but {}", e))
+                    })?;
+            }
+        }
+        if self.kind.read_memory().is_none() {
+            return res;
+        }
+
+        // The memory is read to a partial file first, so a failed read leaves no broken dump.
+        let partial = partial_path(&self.program);
+        match res {
+            Ok(ref out) if out.status.success() => {
+                fs::rename(&partial, &self.program)?;
+                let size = fs::metadata(&self.program)?.len();
+                log.push(&format!(
+                    "Saved {} bytes to {}\n",
+                    size,
+                    self.program.display()
+                ));
+            }
+            _ => {
+                let _ = fs::remove_file(&partial);
+            }
+        }
+        res
+    }
+
+    /// Run the stages until they succeed, transient avrdude failures are retried until the
+    /// attempts of the spec are used up.
+    fn run_attempts(&mut self, log: &Log) -> io::Result<Output> {
+        let attempts = match self.spec.uploader {
+            Uploader::Avrdude | Uploader::Stk500 => self.spec.attempts.max(1),
+            _ => 1,
+        };
+        // The touch replaces the port with the one of the bootloader.
+        let port = self.port.clone();
+        let mut fallback = self.spec.fallback_baud.filter(|_| {
+            matches!(self.spec.uploader, Uploader::Avrdude | Uploader::Stk500)
+                && self.spec.isp.is_none()
+        });
+        let remember = fallback.is_some();
+        for attempt in 1.. {
+            log.attempt.store(attempt, Ordering::Relaxed);
+            if attempts > 1 {
+                log.push(&format!("Attempt {}/{}\n", attempt, attempts));
+            }
+            let mut res = self.run_stages(log);
+            // Only a sync failure hints at the other bootloader, the alternate baud rate is tried
+            // once per job.
+            if let Some(alternate) = fallback.filter(|_| not_in_sync(&res)) {
+                fallback = None;
+                log.push(&format!(
+                    "\nThe bootloader did not sync at {} baud, trying {} baud\n",
+                    self.spec.baud.unwrap_or_default(),
+                    alternate
+                ));
+                self.spec.baud = Some(alternate);
+                self.port = port.clone();
+                thread::sleep(RETRY_DELAY);
+                res = self.run_stages(log);
+            }
+            if remember && is_success(&res) {
+                log.baud
+                    .store(self.spec.baud.unwrap_or_default(), Ordering::Relaxed);
+            }
+            if self.spec.uses_isp() {
+                res = self.run_slower(res, log);
+            }
+            let transient = match res {
+                Ok(ref out) => {
+                    !out.status.success() && is_transient(&String::from_utf8_lossy(&out.stderr))
+                }
+                Err(ref e) => {
+                    e.kind() != io::ErrorKind::Interrupted && is_transient(&e.to_string())
+                }
+            };
+            if !transient || attempt >= attempts {
+                return res;
+            }
+
+            thread::sleep(RETRY_DELAY);
+            if log.is_cancelled() {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+            }
+            self.port = port.clone();
+            if let Some(port) = self
+                .port
+                .as_deref()
+                .filter(|_| self.spec.uses_serial_port())
+            {
+                let exists = serialport::available_ports()
+                    .map_or(true, |ports| ports.iter().any(|p| p.port_name == port));
+                if !exists && !is_network_port(port) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("The port {} disappeared, the board was disconnected", port),
+                    ));
+                }
+            }
+        }
+        unreachable!("the last attempt returns")
+    }
+
+    /// Run the stages again with slower bit clocks while the chip answers with a blank signature,
+    /// the bit clock which worked is kept in the log. Bit clocks which are not slower than the
+    /// chosen one are skipped.
+    fn run_slower(&mut self, mut res: io::Result<Output>, log: &Log) -> io::Result<Output> {
+        let chosen = self
+            .spec
+            .bitclock
+            .as_deref()
+            .and_then(|bitclock| bitclock.parse::<f32>().ok());
+        let slower = SLOW_BITCLOCKS.iter().filter(|bitclock| {
+            chosen.is_none_or(|chosen| bitclock.parse().is_ok_and(|b: f32| b > chosen))
+        });
+        for &bitclock in slower {
+            if !blank_signature(&res) || log.is_cancelled() {
+                break;
+            }
+            log.push(&format!(
+                "\nThe chip answered with a blank signature, its clock may be too slow for the \
+                programmer, trying again with -B {}\n",
+                bitclock
+            ));
+            self.spec.bitclock = Some(bitclock.to_owned());
+            res = self.run_stages(log);
+            if is_success(&res) {
+                if let Ok(mut slow) = log.bitclock.lock() {
+                    *slow = Some(bitclock.to_owned());
+                }
+            }
+        }
+        res
+    }
+
+    /// Touch the port if required and run all commands of the job.
+    fn run_stages(&mut self, log: &Log) -> io::Result<Output> {
+        if self.spec.touch_1200bps {
+            // Network ports are reset by the remote end, if at all.
+            if let Some(port) = self.port.as_deref().filter(|port| !is_network_port(port)) {
+                self.port = Some(touch::touch(port, self.spec.touch_timeout, log)?);
+            }
+        }
+
+        if let Some(ref check) = self.signature_check {
+            self.reset(log)?;
+            if let Some(out) = self.check_signature(check, log)? {
+                return Ok(out);
+            }
+        }
+
+        let commands = self.commands();
+        if commands.is_empty() && self.spec.uploader == Uploader::Stk500 {
+            let blocks =
+                firmware::flash_image(&self.program, self.format).map_err(io::Error::other)?;
+            return stk500::flash(
+                self.port.as_deref().unwrap_or_default(),
+                self.spec.upload_baud().unwrap_or(115_200),
+                &blocks,
+                self.spec.chip().and_then(|chip| chip.signature),
+                log,
+            );
+        }
+        if commands.is_empty() {
+            let drive = Path::new(self.port.as_deref().unwrap_or_default());
+            let out = copy_uf2(&self.program, drive)?;
+            log.push(&String::from_utf8_lossy(&out.stdout));
+            return Ok(out);
+        }
+
+        let stages = commands.len();
+        let mut output = None;
+        for (stage, cmd) in commands {
+            if stages > 1 {
+                log.push(&format!("{}...\n", stage));
+            }
+            self.reset(log)?;
+            let mut out = run_command(cmd, self.spec.uploader.executable(), self.timeout(), log)?;
+            if matches!(self.kind, JobKind::Flash | JobKind::Verify)
+                && self.spec.uploader == Uploader::Avrdude
+                && self.format == FirmwareFormat::Elf
+                && !out.status.success()
+                && rejected_elf(&out)
+            {
+                out = self.flash_converted(log)?;
+            }
+            if self.spec.uploader == Uploader::Avrdude && !out.status.success() {
+                if let Some(e) = unknown_programmer(&self.spec, &out) {
+                    return Err(e);
+                }
+            }
+            if self.spec.uploader == Uploader::Espota && !out.status.success() {
+                if let Some(e) = self.ota.as_ref().and_then(|ota| unreachable_ota(ota, &out)) {
+                    return Err(e);
+                }
+            }
+            // A single stage reports its failure through its own output.
+            if stages > 1 && !out.status.success() {
+                return Err(io::Error::other(format!(
+                    "{} failed ({}): {}",
+                    stage,
+                    out.status,
+                    String::from_utf8_lossy(&out.stderr).trim()
+                )));
+            }
+            output = Some(out);
+        }
+        Ok(output.expect("at least one command ran"))
+    }
+
+    /// Run the given hook with the given port, its output is appended to the log between lines
+    /// naming it. Fails if the hook could not be run or exited with an error.
+    fn run_hook(&self, name: &str, hook: &Hook, port: &str, log: &Log) -> io::Result<()> {
+        let cmd = hook.build(port, &self.program, &self.spec.partno)?;
+        log.push(&format!(
+            "Running the command {}: {}\n",
+            name,
+            command_line(&cmd)
+        ));
+        let executable = cmd.get_program().to_string_lossy().into_owned();
+        let timeout = Some(Duration::from_secs(hook.timeout_secs)).filter(|t| !t.is_zero());
+        let out = run_command(cmd, &executable, timeout, log).map_err(|e| match e.kind() {
+            io::ErrorKind::TimedOut => io::Error::new(
+                e.kind(),
+                format!(
+                    "the command {} timed out after {} seconds",
+                    name, hook.timeout_secs
+                ),
+            ),
+            _ => e,
+        })?;
+        log.push(&format!("The command {} finished ({})\n", name, out.status));
+        if !out.status.success() {
+            return Err(io::Error::other(format!(
+                "the command {} failed ({})",
+                name, out.status
+            )));
+        }
+        Ok(())
+    }
+
+    /// Send the reset pulse of the spec if it has one, each avrdude run needs its own reset to
+    /// find the bootloader waiting.
+    fn reset(&self, log: &Log) -> io::Result<()> {
+        let (Some(pulse), Some(port)) = (self.spec.reset_pulse, self.port.as_deref()) else {
+            return Ok(());
+        };
+        if is_network_port(port) {
+            return Ok(());
+        }
+        log.push("Resetting the board through DTR...\n");
+        pulse_reset(port, &pulse)
+    }
+
+    /// How long each avrdude command of the job may run, the timeout of the spec extended by the
+    /// time the data takes to transfer, like the whole flash of a Mega for a read.
+    fn timeout(&self) -> Option<Duration> {
+        let timeout = self
+            .spec
+            .timeout
+            .filter(|_| self.spec.uploader == Uploader::Avrdude)?;
+        let chip = self.spec.chip();
+        let bytes = match self.kind {
+            JobKind::ReadFlash => chip.map_or(0, |chip| u64::from(chip.flash_size)),
+            JobKind::ReadEeprom | JobKind::WriteEeprom => {
+                chip.map_or(0, |chip| u64::from(chip.eeprom_size))
+            }
+            JobKind::ReadFuses(_)
+            | JobKind::WriteFuses(..)
+            | JobKind::Erase
+            | JobKind::TestCommunication => 0,
+            // The file is at least as large as the data written and verified.
+            JobKind::Flash | JobKind::BurnBootloader(_) | JobKind::Verify => {
+                let backup = match self.backup {
+                    Some(_) => chip.map_or(0, |chip| u64::from(chip.flash_size)),
+                    None => 0,
+                };
+                fs::metadata(&self.program).map_or(0, |meta| meta.len()) + backup
+            }
+        };
+        Some(timeout + Duration::from_secs(bytes / SLOWEST_RATE))
+    }
+
+    /// Connect to the chip with the settings of the flash and compare its signature with the
+    /// expected one, the output is returned if avrdude could not read the signature.
+    fn check_signature(&self, check: &SignatureCheck, log: &Log) -> io::Result<Option<Output>> {
+        log.push("Checking the chip signature...\n");
+        let cmd = avrdude_base(&self.spec, self.port.as_deref().unwrap_or_default());
+        let out = run_command(cmd, self.spec.uploader.executable(), self.timeout(), log)?;
+        // A blank signature means the chip did not answer, which avrdude explains as well.
+        let signature = device_signature(&String::from_utf8_lossy(&out.stderr))
+            .filter(|signature| !is_blank(signature));
+        let Some(signature) = signature else {
+            // avrdude explains itself why it could not connect.
+            return Ok((!out.status.success()).then_some(out));
+        };
+        if signature == check.expected {
+            return Ok(None);
+        }
+
+        let device = match Chip::with_signature(signature) {
+            Some(chip) => chip.name.to_owned(),
+            None => format!(
+                "the unknown signature {:02X} {:02X} {:02X}",
+                signature[0], signature[1], signature[2]
+            ),
+        };
+        Err(WrongChip::error(format!(
+            "device reports {} but {} is selected",
+            device, check.board
+        )))
+    }
+
+    /// Convert the ELF program to Intel HEX with avr-objcopy and flash or verify the converted
+    /// file.
+    fn flash_converted(&self, log: &Log) -> io::Result<Output> {
+        let hex = env::temp_dir().join(format!(
+            "arduino_installer_gui-{}-{}.hex",
+            process::id(),
+            CONVERTED.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut objcopy = Command::new(OBJCOPY);
+        objcopy
+            .arg("-O")
+            .arg("ihex")
+            .arg("-R")
+            .arg(".eeprom")
+            .arg(&self.program)
+            .arg(&hex);
+        let converted = run_command(objcopy, OBJCOPY, None, log)?;
+        if !converted.status.success() {
+            return Err(io::Error::other(format!(
+                "{} could not convert {}: {}",
+                OBJCOPY,
+                self.program.display(),
+                String::from_utf8_lossy(&converted.stderr).trim()
+            )));
+        }
+
+        let note = format!(
+            "avrdude rejected the ELF file, it was converted to Intel HEX with {} and used instead\n",
+            OBJCOPY
+        );
+        log.push(&note);
+        let port = self.port.as_deref().unwrap_or_default();
+        let cmd = match self.kind {
+            JobKind::Verify => avrdude_verify(&self.spec, port, &hex, FirmwareFormat::IntelHex),
+            _ => avrdude(
+                &self.spec,
+                port,
+                &hex,
+                FirmwareFormat::IntelHex,
+                self.eeprom.as_ref(),
+                self.fuses.as_ref(),
+            ),
+        };
+        let res = run_command(cmd, Uploader::Avrdude.executable(), self.timeout(), log);
+        // The converted file is only needed for this run.
+        let _ = fs::remove_file(&hex);
+
+        let mut out = res?;
+        let mut note = note.into_bytes();
+        note.append(&mut out.stdout);
+        out.stdout = note;
+        Ok(out)
+    }
+}
+
+/// Run the given command to completion, its output is appended to the log while it runs. A
+/// missing executable gets a readable error and the command is killed if it runs longer than the
+/// timeout.
+fn run_command(
+    mut cmd: Command,
+    executable: &str,
+    timeout: Option<Duration>,
+    log: &Log,
+) -> io::Result<Output> {
+    let started = Instant::now();
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(
+                e.kind(),
+                format!(
+                    "{} is not installed or could not be found in PATH",
+                    executable
+                ),
+            ),
+            _ => e,
+        })?;
+
+    let stdout = child.stdout.take().map(|pipe| log.read_from(pipe));
+    let stderr = child.stderr.take().map(|pipe| log.read_from(pipe));
+    let status = loop {
+        if log.is_cancelled() {
+            // Killing fails if the command exited in the meantime, which wait handles.
+            let _ = child.kill();
+            child.wait()?;
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
+        if let Some(timeout) = timeout.filter(|&timeout| started.elapsed() > timeout) {
+            let _ = child.kill();
+            // Waiting reaps the killed child, so it does not stay a zombie.
+            child.wait()?;
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "timed out after {} seconds, check that the selected port and board are right",
+                    timeout.as_secs()
+                ),
+            ));
+        }
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    let collect = |reader: Option<thread::JoinHandle<Vec<u8>>>| {
+        reader
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default()
+    };
+    Ok(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+/// The given command as it would be typed into a shell, arguments with spaces or special
+/// characters are quoted.
+fn command_line(cmd: &Command) -> String {
+    // Backslashes separate the directories of paths on Windows.
+    let plain = |c: char| {
+        c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c) || (cfg!(windows) && c == '\\')
+    };
+    let quote = |arg: &OsStr| {
+        let arg = arg.to_string_lossy();
+        if !arg.is_empty() && arg.chars().all(plain) {
+            arg.into_owned()
+        } else if cfg!(windows) {
+            format!("\"{}\"", arg.replace('"', "\\\""))
+        } else {
+            format!("'{}'", arg.replace('\'', "'\\''"))
+        }
+    };
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Wether the given run failed because the bootloader did not sync, which usually means that it
+/// listens at another baud rate.
+fn not_in_sync(res: &io::Result<Output>) -> bool {
+    match res {
+        Ok(out) => {
+            !out.status.success() && String::from_utf8_lossy(&out.stderr).contains("not in sync")
+        }
+        Err(e) => e.to_string().contains("not in sync"),
+    }
+}
+
+/// Wether the given run failed because the chip answered with a signature of only zeros or ones.
+fn blank_signature(res: &io::Result<Output>) -> bool {
+    match res {
+        Ok(out) if !out.status.success() => {
+            device_signature(&String::from_utf8_lossy(&out.stderr)).is_some_and(|s| is_blank(&s))
+        }
+        _ => false,
+    }
+}
+
+/// Wether the given signature is only zeros or ones, which is read when the chip does not answer.
+fn is_blank(signature: &[u8; 3]) -> bool {
+    *signature == [0x00; 3] || *signature == [0xFF; 3]
+}
+
+/// Wether the given run succeeded.
+fn is_success(res: &io::Result<Output>) -> bool {
+    res.as_ref().is_ok_and(|out| out.status.success())
+}
+
+/// Wether the given avrdude output shows a failure which may go away when avrdude is run again,
+/// like a bootloader which did not sync. Wrong signatures or missing files are not retried.
+fn is_transient(output: &str) -> bool {
+    let output = output.to_ascii_lowercase();
+    TRANSIENT_FAILURES
+        .iter()
+        .any(|failure| output.contains(&failure.to_ascii_lowercase()))
+}
+
+/// Wether avrdude failed because it can't read ELF files, which older builds can't.
+fn rejected_elf(out: &Output) -> bool {
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    stderr.contains("invalid file format") || stderr.contains("can't determine file format")
+}
+
+/// The signature avrdude printed like "Device signature = 0x1e950f" or, since avrdude 7,
+/// "Device signature = 1E 95 0F".
+pub fn device_signature(stderr: &str) -> Option<[u8; 3]> {
+    stderr.lines().find_map(|line| {
+        let start = line.to_ascii_lowercase().find("device signature = ")?;
+        let value = line[start + "device signature = ".len()..]
+            .split('(')
+            .next()?
+            .trim();
+        let digits: String = value
+            .trim_start_matches("0x")
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        if digits.len() != 6 {
+            return None;
+        }
+        let byte = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+        Some([byte(0)?, byte(2)?, byte(4)?])
+    })
+}
+
+/// A clear error if avrdude failed because it does not know the programmer, which happens with
+/// versions older than 7.0 for serialupdi.
+fn unknown_programmer(spec: &BoardSpec, out: &Output) -> Option<io::Error> {
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    if !stderr.contains("Can't find programmer id") && !stderr.contains("invalid programmer") {
+        return None;
+    }
+
+    let programmer = spec.upload_programmer();
+    let hint = if programmer == "serialupdi" {
+        ", serialupdi requires avrdude 7.0 or newer"
+    } else {
+        ""
+    };
+    Some(io::Error::other(format!(
+        "The installed avrdude does not support the programmer {}{}",
+        programmer, hint
+    )))
+}
+
+/// A readable error if espota gave up waiting for the device.
+fn unreachable_ota(ota: &OtaTarget, out: &Output) -> Option<io::Error> {
+    let log = format!(
+        "{}{}",
+        String::from_utf8_lossy(&out.stderr),
+        String::from_utf8_lossy(&out.stdout)
+    );
+    if !log.contains("No response from") && !log.contains("No Answer") && !log.contains("Not Found")
+    {
+        return None;
+    }
+
+    Some(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!(
+            "Timed out reaching {}:{}, check the address and that the running sketch accepts \
+            over the air updates",
+            ota.host, ota.port
+        ),
+    ))
+}
+
+/// The output of a running job, shared with the threads reading the output of its commands and
+/// the gui which can cancel the job through it.
+#[derive(Clone)]
+pub struct Log {
+    /// The output in the order it arrived.
+    text: Arc<Mutex<String>>,
+    /// Wether the user cancelled the job.
+    cancelled: Arc<AtomicBool>,
+    /// The number of the running attempt, starting with 1.
+    attempt: Arc<AtomicU8>,
+    /// The baud rate the bootloader answered at, 0 unless the job could fall back to another one.
+    baud: Arc<AtomicU32>,
+    /// The slower bit clock the chip answered with, `None` if it was not needed.
+    bitclock: Arc<Mutex<Option<String>>>,
+    /// The context repainted when output arrives.
+    ctx: egui::Context,
+}
+
+impl Log {
+    /// An empty log repainting the given context.
+    fn new(ctx: egui::Context) -> Self {
+        Self {
+            text: Arc::default(),
+            cancelled: Arc::default(),
+            attempt: Arc::default(),
+            baud: Arc::default(),
+            bitclock: Arc::default(),
+            ctx,
+        }
+    }
+
+    /// Kill the running command of the job and skip the remaining ones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Wether the job was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// The number of the running or last attempt of the job.
+    pub fn attempt(&self) -> u8 {
+        self.attempt.load(Ordering::Relaxed)
+    }
+
+    /// The baud rate the bootloader answered at, `None` unless the board has a fallback baud
+    /// rate and the job succeeded.
+    pub fn synced_baud(&self) -> Option<u32> {
+        Some(self.baud.load(Ordering::Relaxed)).filter(|&baud| baud != 0)
+    }
+
+    /// The slower bit clock the job only succeeded with, `None` if the chosen one worked.
+    pub fn slow_bitclock(&self) -> Option<String> {
+        self.bitclock
+            .lock()
+            .ok()
+            .and_then(|bitclock| bitclock.clone())
+    }
+
+    /// Append the given output, a carriage return moves back to the start of the line like in a
+    /// terminal.
+    pub fn push(&self, output: &str) {
+        let Ok(mut text) = self.text.lock() else {
+            return;
+        };
+        for c in output.chars() {
+            if text.ends_with('\r') && c != '\n' {
+                let line_start = text.rfind('\n').map_or(0, |i| i + 1);
+                text.truncate(line_start);
+            }
+            if c == '\n' && text.ends_with('\r') {
+                text.pop();
+            }
+            text.push(c);
+        }
+        drop(text);
+        self.ctx.request_repaint();
+    }
+
+    /// The output so far.
+    pub fn text(&self) -> String {
+        let text = self
+            .text
+            .lock()
+            .map(|text| text.clone())
+            .unwrap_or_default();
+        text.trim_end_matches('\r').to_owned()
+    }
+
+    /// Append everything read from the given pipe on a new thread, which returns all read bytes.
+    fn read_from(&self, mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+        let log = self.clone();
+        thread::spawn(move || {
+            let mut all = Vec::new();
+            let mut buf = [0; 1024];
+            // Multi byte characters may be split between two reads.
+            let mut pending = Vec::new();
+            while let Ok(read @ 1..) = pipe.read(&mut buf) {
+                all.extend_from_slice(&buf[..read]);
+                pending.extend_from_slice(&buf[..read]);
+                let valid = match std::str::from_utf8(&pending) {
+                    Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                    _ => pending.len(),
+                };
+                log.push(&String::from_utf8_lossy(&pending[..valid]));
+                pending.drain(..valid);
+            }
+            log.push(&String::from_utf8_lossy(&pending));
+            all
+        })
+    }
+}
+
+/// An upload running on a background thread.
+pub struct RunningUpload {
+    /// The uploader which is running.
+    pub uploader: Uploader,
+    /// What the running job does.
+    pub kind: JobKind,
+    /// The port the job runs on, `None` if the uploader finds the board on its own.
+    pub port: Option<String>,
+    /// When the job was started.
+    pub started: Instant,
+    /// Wether the flash is written without verifying it.
+    pub verification_skipped: bool,
+    /// Receives the output once the uploader exited.
+    result: Receiver<io::Result<Output>>,
+    /// The output of the uploader so far.
+    pub log: Log,
+}
+
+impl RunningUpload {
+    /// Run the given job on a background thread, the context is repainted when it is done.
+    pub fn start(job: UploadJob, uploader: Uploader, ctx: egui::Context) -> Self {
+        let kind = job.kind;
+        let port = job.port.clone();
+        let verification_skipped = job.skips_verification();
+        let (sender, result) = mpsc::channel();
+        let log = Log::new(ctx.clone());
+        let job_log = log.clone();
+        let started = Instant::now();
+        thread::spawn(move || {
+            let res = job.run(&job_log);
+            // The output is saved and copied with the time the job took.
+            job_log.push(&format!(
+                "\nFinished in {:.1} s\n",
+                started.elapsed().as_secs_f32()
+            ));
+            // The receiver is only dropped when the gui closed, so the result is not needed.
+            let _ = sender.send(res);
+            ctx.request_repaint();
+        });
+
+        Self {
+            uploader,
+            kind,
+            port,
+            started,
+            verification_skipped,
+            result,
+            log,
+        }
+    }
+
+    /// The output of the uploader, if it exited.
+    pub fn poll(&self) -> Option<io::Result<Output>> {
+        match self.result.try_recv() {
+            Ok(res) => Some(res),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(io::Error::other(
+                "The upload thread stopped unexpectedly",
+            ))),
+        }
+    }
+}
+
+/// Wether the given port is a network port like "net:host:port" passed to avrdude.
+fn is_network_port(port: &str) -> bool {
+    port.starts_with("net:")
+}
+
+/// Build the avrdude command to flash the given program in the given format with the given spec to
+/// the device connected on the given port, the EEPROM and the fuses are written in the same run if
+/// they are given.
+pub fn avrdude(
+    spec: &BoardSpec,
+    port: &str,
+    program_to_flash: &Path,
+    format: FirmwareFormat,
+    eeprom: Option<&MemoryFile>,
+    fuses: Option<&(FuseLayout, [u8; 3])>,
+) -> Command {
+    let mut cmd = avrdude_base(spec, port);
+    if let Some(arg) = spec.erase.avrdude_arg() {
+        cmd.arg(arg);
+    }
+    cmd.arg("-U").arg(format!(
+        "flash:w:{}:{}",
+        program_to_flash.display(),
+        format.avrdude_suffix()
+    ));
+
+    // Written in the same run, so the chip is not reset between flash and EEPROM.
+    if let Some(eeprom) = eeprom {
+        cmd.arg("-U").arg(format!(
+            "eeprom:w:{}:{}",
+            eeprom.path.display(),
+            eeprom.format.avrdude_suffix()
+        ));
+    }
+
+    // The fuses always come last, a new clock or a disabled reset pin only takes effect after the
+    // flash and the EEPROM were written and verified at the clock the bitclock was chosen for, and
+    // a wrong clock source can't leave the chip without its program.
+    if let Some((layout, values)) = fuses {
+        cmd.args(fuse_operations(layout, *values));
+    }
+
+    cmd
+}
+
+/// The file a memory is read to before it is moved to the given destination.
+fn partial_path(destination: &Path) -> PathBuf {
+    let mut name = destination.file_name().unwrap_or_default().to_owned();
+    name.push(".part");
+    destination.with_file_name(name)
+}
+
+/// Build the avrdude command saving the given memory of the device connected on the given port to
+/// the given file in the given format.
+pub fn avrdude_read(
+    spec: &BoardSpec,
+    port: &str,
+    memory: &str,
+    destination: &Path,
+    format: FirmwareFormat,
+) -> Command {
+    let mut cmd = avrdude_base(spec, port);
+    cmd.arg("-U").arg(format!(
+        "{}:r:{}:{}",
+        memory,
+        destination.display(),
+        format.avrdude_suffix()
+    ));
+    cmd
+}
+
+/// Build the avrdude command writing the given file to the EEPROM of the device connected on the
+/// given port, the chip is never erased so the flash is kept.
+pub fn avrdude_write_eeprom(
+    spec: &BoardSpec,
+    port: &str,
+    eeprom: &Path,
+    format: FirmwareFormat,
+) -> Command {
+    let mut cmd = avrdude_base(spec, port);
+    cmd.arg("-D").arg("-U").arg(format!(
+        "eeprom:w:{}:{}",
+        eeprom.display(),
+        format.avrdude_suffix()
+    ));
+    cmd
+}
+
+/// Build the avrdude command printing the fuse bytes of the given layout of the device connected on
+/// the given port to stdout, one hex value per line.
+pub fn avrdude_read_fuses(spec: &BoardSpec, port: &str, layout: &FuseLayout) -> Command {
+    let mut cmd = avrdude_base(spec, port);
+    for fuse in layout.names {
+        cmd.arg("-U").arg(format!("{}:r:-:h", fuse));
+    }
+    cmd
+}
+
+/// Build the avrdude command writing the given values to the fuses of the given layout of the
+/// device connected on the given port, the chip is not erased.
+pub fn avrdude_write_fuses(
+    spec: &BoardSpec,
+    port: &str,
+    layout: &FuseLayout,
+    values: [u8; 3],
+) -> Command {
+    let mut cmd = avrdude_base(spec, port);
+    cmd.args(fuse_operations(layout, values));
+    cmd
+}
+
+/// The `-U` arguments writing the given fuse values, the low fuse selects the clock, so it is
+/// written last like when burning the bootloader.
+fn fuse_operations(layout: &FuseLayout, values: [u8; 3]) -> Vec<String> {
+    layout
+        .names
+        .iter()
+        .zip(values)
+        .rev()
+        .flat_map(|(fuse, value)| ["-U".to_owned(), format!("{}:w:0x{:02X}:m", fuse, value)])
+        .collect()
+}
+
+/// Build the avrdude command erasing the chip connected on the given port without writing anything.
+pub fn avrdude_erase(spec: &BoardSpec, port: &str) -> Command {
+    let mut cmd = avrdude_base(spec, port);
+    cmd.arg("-e");
+    cmd
+}
+
+/// Build the avrdude command comparing the flash of the device connected on the given port with
+/// the given program, nothing is erased or written.
+pub fn avrdude_verify(
+    spec: &BoardSpec,
+    port: &str,
+    program: &Path,
+    format: FirmwareFormat,
+) -> Command {
+    let mut cmd = avrdude_base(spec, port);
+    cmd.arg("-U").arg(format!(
+        "flash:v:{}:{}",
+        program.display(),
+        format.avrdude_suffix()
+    ));
+    cmd
+}
+
+/// Build the avrdude command which erases the chip, unlocks the bootloader section and writes the
+/// fuses of the bootloader.
+pub fn avrdude_fuses(spec: &BoardSpec, bootloader: &Bootloader, port: &str) -> Command {
+    let mut cmd = avrdude_base(spec, port);
+    cmd.arg("-e")
+        .arg("-U")
+        .arg(format!("lock:w:0x{:02X}:m", UNLOCK_BITS))
+        .arg("-U")
+        .arg(format!("efuse:w:0x{:02X}:m", bootloader.efuse))
+        .arg("-U")
+        .arg(format!("hfuse:w:0x{:02X}:m", bootloader.hfuse))
+        .arg("-U")
+        .arg(format!("lfuse:w:0x{:02X}:m", bootloader.lfuse));
+    cmd
+}
+
+/// Build the avrdude command which writes the given bootloader image and locks its section.
+pub fn avrdude_bootloader(
+    spec: &BoardSpec,
+    bootloader: &Bootloader,
+    port: &str,
+    image: &Path,
+) -> Command {
+    let mut cmd = avrdude_base(spec, port);
+    cmd.arg("-U")
+        .arg(format!("flash:w:{}:i", image.display()))
+        .arg("-U")
+        .arg(format!("lock:w:0x{:02X}:m", bootloader.lock_bits));
+    cmd
+}
+
+/// Build an avrdude command talking to the device connected on the given port, without any
+/// operation.
+fn avrdude_base(spec: &BoardSpec, port: &str) -> Command {
+    let mut cmd = Command::new(spec.avrdude_executable());
+    if let Some(ref conf) = spec.avrdude_conf {
+        cmd.arg("-C").arg(conf);
+    }
+    cmd.arg("-c")
+        .arg(spec.upload_programmer())
+        .arg("-p")
+        .arg(&*spec.partno)
+        .arg("-P")
+        .arg(port);
+
+    if let Some(baud) = spec.upload_baud() {
+        cmd.arg("-b").arg(baud.to_string());
+    }
+    if let Some(ref bitclock) = spec.bitclock {
+        cmd.arg("-B").arg(bitclock);
+    }
+    cmd.args(spec.verbosity.avrdude_args());
+    for (key, value) in &spec.extended_params {
+        cmd.arg("-x");
+        match value.as_str() {
+            "" => cmd.arg(key),
+            value => cmd.arg(format!("{}={}", key, value)),
+        };
+    }
+
+    for arg in &spec.extra_args {
+        cmd.arg(&**arg);
+    }
+
+    cmd
+}
+
+/// Build the micronucleus command to flash the given program, micronucleus waits for the device to
+/// be plugged in.
+pub fn micronucleus(program_to_flash: &Path) -> Command {
+    let mut cmd = Command::new(Uploader::Micronucleus.executable());
+    cmd.arg("--run").arg(program_to_flash);
+    cmd
+}
+
+/// Build the bossac command to erase the device on the given port and flash the given program.
+pub fn bossac(spec: &BoardSpec, port: &str, program_to_flash: &Path) -> Command {
+    // bossac expects the bare port name (ttyACM0) instead of its device path.
+    let port = Path::new(port)
+        .file_name()
+        .map_or_else(|| port.into(), |name| name.to_string_lossy());
+
+    let mut cmd = Command::new(Uploader::Bossac.executable());
+    match spec.flash_offset {
+        // Boards with the bootloader in flash, the program starts after it.
+        Some(offset) => cmd
+            .arg("-i")
+            .arg("-d")
+            .arg(format!("--port={}", port))
+            .arg("-U")
+            .arg("-i")
+            .arg(format!("--offset={:#x}", offset))
+            .arg("-w")
+            .arg("-v")
+            .arg(program_to_flash)
+            .arg("-R"),
+        // The SAM3X boots its ROM bootloader after an erase, `-b` makes it boot from flash again.
+        None if spec.partno == "at91sam3x8e" => cmd
+            .arg(format!("--port={}", port))
+            .arg("-e")
+            .arg("-w")
+            .arg("-v")
+            .arg("-b")
+            .arg(program_to_flash)
+            .arg("-R"),
+        None => cmd
+            .arg(format!("--port={}", port))
+            .arg("-U")
+            .arg("-e")
+            .arg("-w")
+            .arg(program_to_flash)
+            .arg("-R"),
+    };
+    cmd
+}
+
+/// Build the esptool command to write the given program to the flash of the chip on the given port.
+pub fn esptool(spec: &BoardSpec, port: &str, program_to_flash: &Path) -> Command {
+    let mut cmd = Command::new(Uploader::Esptool.executable());
+    cmd.arg("--chip").arg(&*spec.partno).arg("--port").arg(port);
+
+    if let Some(baud) = spec.baud {
+        cmd.arg("--baud").arg(baud.to_string());
+    }
+
+    cmd.arg("write_flash")
+        .arg(format!("{:#x}", spec.flash_offset.unwrap_or_default()))
+        .arg(program_to_flash);
+    cmd
+}
+
+/// Build the espota command to update the given device over the air with the given program.
+pub fn espota(ota: &OtaTarget, program_to_flash: &Path) -> Command {
+    let mut cmd = Command::new(Uploader::Espota.executable());
+    cmd.arg("-r")
+        .arg("-i")
+        .arg(&ota.host)
+        .arg("-p")
+        .arg(ota.port.to_string());
+
+    if !ota.password.is_empty() {
+        cmd.arg("-a").arg(&ota.password);
+    }
+
+    cmd.arg("-f").arg(program_to_flash);
+    cmd
+}
+
+/// Build the teensy_loader_cli command to flash the given program, the loader waits until the
+/// button on the board is pressed.
+pub fn teensy_loader(spec: &BoardSpec, program_to_flash: &Path) -> Command {
+    let mut cmd = Command::new(Uploader::TeensyLoader.executable());
+    cmd.arg(format!("--mcu={}", spec.partno))
+        .arg("-w")
+        .arg("-v")
+        .arg(program_to_flash);
+    cmd
+}
+
+/// Build the stm32flash command to flash the given program through the serial bootloader on the
+/// given port and run it afterwards.
+pub fn stm32flash(port: &str, program_to_flash: &Path) -> Command {
+    let mut cmd = Command::new(Uploader::Stm32flash.executable());
+    cmd.arg("-w")
+        .arg(program_to_flash)
+        .arg("-v")
+        .arg("-g")
+        .arg("0x0")
+        .arg(port);
+    cmd
+}
+
+/// Build the dfu-util command to flash the given program through the dfu bootloader of the board.
+pub fn dfu_util(spec: &BoardSpec, program_to_flash: &Path) -> Command {
+    let mut cmd = Command::new(Uploader::DfuUtil.executable());
+    if let Some(device) = spec.dfu_device {
+        cmd.arg("--device").arg(device);
+    }
+
+    cmd.arg("-a")
+        .arg(spec.dfu_alt.to_string())
+        .arg("-D")
+        .arg(program_to_flash);
+    cmd
+}
+
+/// Build the picotool command to load the given program onto a Pico in BOOTSEL mode and run it.
+pub fn picotool(program_to_flash: &Path) -> Command {
+    let mut cmd = Command::new(Uploader::Picotool.executable());
+    cmd.arg("load").arg("-x").arg(program_to_flash);
+    cmd
+}
+
+/// Copy the given uf2 file onto the mass storage drive of a Pico in BOOTSEL mode.
+///
+/// The copy is reported like a successful run of an uploader.
+fn copy_uf2(program_to_flash: &Path, drive: &Path) -> io::Result<Output> {
+    let file_name = program_to_flash
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not a file"))?;
+    let bytes = fs::copy(program_to_flash, drive.join(file_name))?;
+
+    Ok(Output {
+        status: ExitStatus::default(),
+        stdout: format!("Copied {} bytes to {}", bytes, drive.display()).into_bytes(),
+        stderr: Vec::new(),
+    })
+}
+
+/// Find the mounted mass storage drives of Picos in BOOTSEL mode.
+pub fn find_uf2_drives() -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    if cfg!(windows) {
+        candidates
+            .extend((b'A'..=b'Z').map(|letter| PathBuf::from(format!("{}:\\", letter as char))));
+    } else if cfg!(target_os = "macos") {
+        candidates.push(PathBuf::from("/Volumes/RPI-RP2"));
+    } else if let Ok(mounts) = fs::read_to_string("/proc/mounts") {
+        // Mount points with spaces are escaped, which never matters for RPI-RP2.
+        candidates.extend(
+            mounts
+                .lines()
+                .filter_map(|line| line.split_whitespace().nth(1))
+                .map(PathBuf::from),
+        );
+    }
+
+    // Every uf2 bootloader places this file on its drive, the board id tells it is a Pico.
+    candidates
+        .into_iter()
+        .filter(|drive| {
+            fs::read_to_string(drive.join("INFO_UF2.TXT"))
+                .is_ok_and(|info| info.contains("RPI-RP2"))
+        })
+        .collect()
+}
+
+/// Reset the board connected to the given port with the given pulse and wait until its bootloader
+/// started.
+fn pulse_reset(port: &str, pulse: &ResetPulse) -> io::Result<()> {
+    let mut serial = serialport::new(port, 115_200)
+        .timeout(Duration::from_millis(100))
+        .open()
+        .map_err(|e| serial_open_error(port, "for the reset pulse", e))?;
+    let mut set_lines = |level: bool| -> serialport::Result<()> {
+        serial.write_data_terminal_ready(level)?;
+        if pulse.rts {
+            serial.write_request_to_send(level)?;
+        }
+        Ok(())
+    };
+    let pulsed = set_lines(!pulse.inverted).and_then(|()| {
+        thread::sleep(Duration::from_millis(pulse.width_ms));
+        set_lines(pulse.inverted)
+    });
+    pulsed.map_err(|e| io::Error::other(format!("Could not toggle DTR/RTS of {}: {}", port, e)))?;
+    drop(serial);
+    thread::sleep(RESET_SETTLE);
+    Ok(())
+}
+
+/// The error of a flash which stopped before writing, because the chip reports another signature
+/// than the selected board has
+#[derive(Debug)]
+pub struct WrongChip(pub String);
+
+impl WrongChip {
+    /// The error of a chip which does not match, with the given explanation.
+    pub fn error(explanation: String) -> io::Error {
+        io::Error::other(Self(explanation))
+    }
+
+    /// Wether the given error is the one of a chip which does not match.
+    pub fn is(e: &io::Error) -> bool {
+        e.get_ref().is_some_and(|inner| inner.is::<Self>())
+    }
+}
+
+impl fmt::Display for WrongChip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for WrongChip {}
+
+/// A readable error for the given port which could not be opened for the given purpose, like
+/// "for the reset pulse".
+pub fn serial_open_error(port: &str, purpose: &str, e: serialport::Error) -> io::Error {
+    let description = e.description.to_lowercase();
+    let message = match e.kind() {
+        serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied) => format!(
+            "No permission to open {} {}, add your user to the group owning the port (usually \
+            dialout or uucp)",
+            port, purpose
+        ),
+        _ if description.contains("busy") || description.contains("access is denied") => format!(
+            "{} is busy, close the serial monitor or other programs using it",
+            port
+        ),
+        serialport::ErrorKind::NoDevice => format!(
+            "{} does not exist anymore, the board was disconnected",
+            port
+        ),
+        _ => format!("Could not open {} {}: {}", port, purpose, e),
+    };
+    io::Error::other(message)
+}